@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use spin_sdk::key_value::Store;
+
+use crate::{
+    auth::{sha256_hex, unix_now, Claims},
+    providers::UserContext,
+};
+
+/// Issuer recorded in claims for API key authentication
+const API_KEY_ISSUER: &str = "api_key";
+
+/// Prefix for API key entries in the key-value store
+const STORE_KEY_PREFIX: &str = "apikey:";
+
+/// A stored API key and the identity it authenticates as
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiKeyEntry {
+    /// Hex-encoded SHA-256 of the key (implied by the store key for KV entries)
+    #[serde(default)]
+    pub hash: String,
+    /// User ID the key authenticates as
+    pub id: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Tenant the key is bound to
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Unix timestamp after which the key is rejected
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+/// API key authentication settings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ApiKeyConfig {
+    /// Keys configured inline
+    #[serde(default)]
+    pub keys: Vec<ApiKeyEntry>,
+    /// Key-value store holding entries under `apikey:<hash>`
+    #[serde(default)]
+    pub store: Option<String>,
+}
+
+impl ApiKeyConfig {
+    /// Whether any key source is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty() || self.store.is_some()
+    }
+}
+
+/// Hex-encoded SHA-256 of an API key
+pub fn hash_api_key(key: &str) -> String {
+    sha256_hex(key)
+}
+
+/// Compare two hashes without short-circuiting on the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Look up a key hash in the configured key-value store
+fn lookup_in_store(label: &str, hash: &str) -> Result<Option<ApiKeyEntry>, String> {
+    let store = Store::open(label).map_err(|e| {
+        eprintln!("Failed to open API key store '{label}': {e}");
+        "API key validation failed".to_string()
+    })?;
+
+    let Some(value) = store
+        .get(&format!("{STORE_KEY_PREFIX}{hash}"))
+        .map_err(|e| {
+            eprintln!("Failed to read API key store '{label}': {e}");
+            "API key validation failed".to_string()
+        })?
+    else {
+        return Ok(None);
+    };
+
+    serde_json::from_slice::<ApiKeyEntry>(&value)
+        .map(Some)
+        .map_err(|e| {
+            eprintln!("Malformed API key entry in store '{label}': {e}");
+            "API key validation failed".to_string()
+        })
+}
+
+/// Verify an API key.
+///
+/// Returns `Ok(None)` when the key is unknown, so callers can fall back to other providers.
+pub fn verify_api_key(
+    key: &str,
+    config: &ApiKeyConfig,
+) -> Result<Option<(Claims, UserContext)>, String> {
+    let hash = hash_api_key(key);
+
+    let inline = config
+        .keys
+        .iter()
+        .find(|entry| constant_time_eq(entry.hash.to_lowercase().as_bytes(), hash.as_bytes()))
+        .cloned();
+
+    let entry = match (inline, config.store.as_deref()) {
+        (Some(entry), _) => entry,
+        (None, Some(label)) => match lookup_in_store(label, &hash)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        },
+        (None, None) => return Ok(None),
+    };
+
+    let now = unix_now();
+    if entry.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        eprintln!("API key for '{}' has expired", entry.id);
+        return Err("API key has expired".to_string());
+    }
+
+    let claims = Claims {
+        sub: entry.id.clone(),
+        iss: API_KEY_ISSUER.to_string(),
+        aud: None,
+        exp: entry.expires_at.unwrap_or(i64::MAX),
+        iat: now,
+        email: entry.email.clone(),
+        extra: serde_json::json!({ "scope": entry.scopes.join(" ") }),
+    };
+    let user_context = UserContext {
+        id: entry.id,
+        email: entry.email,
+        provider: API_KEY_ISSUER.to_string(),
+        tenant: entry.tenant,
+        ..UserContext::default()
+    };
+
+    Ok(Some((claims, user_context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ApiKeyConfig {
+        ApiKeyConfig {
+            keys: vec![
+                ApiKeyEntry {
+                    hash: hash_api_key("ci-secret"),
+                    id: "ci-bot".to_string(),
+                    email: None,
+                    scopes: vec!["mcp:tools".to_string()],
+                    tenant: Some("acme".to_string()),
+                    expires_at: None,
+                },
+                ApiKeyEntry {
+                    hash: hash_api_key("old-secret"),
+                    id: "retired".to_string(),
+                    email: None,
+                    scopes: Vec::new(),
+                    tenant: None,
+                    expires_at: Some(1),
+                },
+            ],
+            store: None,
+        }
+    }
+
+    #[test]
+    fn test_hash_api_key() {
+        assert_eq!(
+            hash_api_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_verify_known_key() {
+        let (claims, user) = verify_api_key("ci-secret", &config()).unwrap().unwrap();
+        assert_eq!(user.id, "ci-bot");
+        assert_eq!(user.provider, "api_key");
+        assert_eq!(user.tenant.as_deref(), Some("acme"));
+        assert_eq!(claims.scopes(), vec!["mcp:tools"]);
+    }
+
+    #[test]
+    fn test_verify_unknown_and_expired_keys() {
+        assert!(verify_api_key("nope", &config()).unwrap().is_none());
+        assert!(verify_api_key("old-secret", &config()).is_err());
+    }
+}
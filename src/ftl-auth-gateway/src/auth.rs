@@ -1,21 +1,31 @@
-use jsonwebtoken::{decode, decode_header, Validation};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use spin_sdk::http::{Request, Response};
+use std::fmt::Write;
 
 use crate::{
-    jwks,
-    providers::{AuthProvider, UserContext},
+    api_keys::{self, ApiKeyConfig},
+    dpop::{self, ProofTarget},
+    headers::HeaderPolicy,
+    introspection, jwks,
+    providers::{AuthProvider, ProviderRegistry, UserContext},
+    revocation::LogoutClaims,
+    token_cache,
 };
 
 /// Authentication gateway configuration
 #[derive(Debug, Clone)]
 pub struct AuthConfig {
     pub mcp_gateway_url: String,
+    pub headers: HeaderPolicy,
+    pub tenant_secret: Option<String>,
 }
 
 /// `JWT` Claims structure
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Claims {
     pub sub: String,
     pub iss: String,
@@ -27,55 +37,259 @@ pub struct Claims {
     pub extra: Value,
 }
 
-/// Extract bearer token from authorization header
+impl Claims {
+    /// Scopes granted to the token, read from `scope` or `scp`
+    pub fn scopes(&self) -> Vec<String> {
+        let split = |s: &str| s.split_whitespace().map(String::from).collect();
+        match self.extra.get("scope").or_else(|| self.extra.get("scp")) {
+            Some(Value::String(scopes)) => split(scopes),
+            Some(Value::Array(scopes)) => scopes
+                .iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Current Unix time in seconds
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+/// Hex-encoded SHA-256 digest, used wherever secrets serve as lookup keys
+pub fn sha256_hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Extract the token from a `Bearer` authorization header (the scheme is case-insensitive)
 fn extract_bearer_token(auth_header: &str) -> Option<&str> {
-    auth_header.strip_prefix("Bearer ").map(str::trim)
+    let auth_header = auth_header.trim();
+    let (scheme, token) = auth_header.split_once(' ').unwrap_or((auth_header, ""));
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// The access token a request presents, under the `Bearer` or `DPoP` scheme
+pub fn presented_token(req: &Request) -> Option<&str> {
+    let auth = req
+        .headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.as_str())?;
+    extract_bearer_token(auth)
+        .or_else(|| dpop::extract_dpop_token(auth))
+        .filter(|token| !token.is_empty())
+}
+
+/// RFC 6750 bearer token error codes, plus the RFC 9449 `DPoP` proof error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BearerErrorCode {
+    InvalidRequest,
+    InvalidToken,
+    InsufficientScope,
+    InvalidDpopProof,
+}
+
+impl BearerErrorCode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::InvalidToken => "invalid_token",
+            Self::InsufficientScope => "insufficient_scope",
+            Self::InvalidDpopProof => "invalid_dpop_proof",
+        }
+    }
+
+    pub const fn status(self) -> u16 {
+        match self {
+            Self::InvalidRequest => 400,
+            Self::InvalidToken | Self::InvalidDpopProof => 401,
+            Self::InsufficientScope => 403,
+        }
+    }
+}
+
+/// Why a request failed authentication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthError {
+    /// `None` when the request carried no bearer credentials, which RFC 6750 answers
+    /// without an error code
+    pub code: Option<BearerErrorCode>,
+    pub description: String,
+}
+
+impl AuthError {
+    pub fn missing_credentials(description: impl Into<String>) -> Self {
+        Self {
+            code: None,
+            description: description.into(),
+        }
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self {
+            code: Some(BearerErrorCode::InvalidRequest),
+            description: description.into(),
+        }
+    }
+
+    pub fn invalid_token(description: impl Into<String>) -> Self {
+        Self {
+            code: Some(BearerErrorCode::InvalidToken),
+            description: description.into(),
+        }
+    }
+
+    pub fn insufficient_scope(description: impl Into<String>) -> Self {
+        Self {
+            code: Some(BearerErrorCode::InsufficientScope),
+            description: description.into(),
+        }
+    }
+
+    pub fn invalid_dpop_proof(description: impl Into<String>) -> Self {
+        Self {
+            code: Some(BearerErrorCode::InvalidDpopProof),
+            description: description.into(),
+        }
+    }
+
+    /// HTTP status for the error
+    pub fn status(&self) -> u16 {
+        self.code.map_or(401, BearerErrorCode::status)
+    }
+}
+
+/// Render a value as an RFC 7230 quoted-string
+fn quoted_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    // Control characters cannot appear in a header value at all
+    for c in value.chars().filter(|c| !c.is_control()) {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Build the `WWW-Authenticate` challenge for an authentication error. When `DPoP` is accepted,
+/// a `DPoP` challenge listing its algorithms follows and carries proof errors (RFC 9449).
+pub fn www_authenticate(
+    error: &AuthError,
+    scope: Option<&str>,
+    resource_metadata: Option<&str>,
+    dpop_algs: Option<&str>,
+) -> String {
+    let dpop_error = dpop_algs.is_some() && error.code == Some(BearerErrorCode::InvalidDpopProof);
+
+    let mut params = Vec::new();
+    if let Some(code) = error.code.filter(|_| !dpop_error) {
+        params.push(format!("error={}", quoted_string(code.as_str())));
+        params.push(format!(
+            "error_description={}",
+            quoted_string(&error.description)
+        ));
+    }
+    if let Some(scope) = scope.filter(|scope| !scope.is_empty()) {
+        params.push(format!("scope={}", quoted_string(scope)));
+    }
+    if let Some(url) = resource_metadata {
+        params.push(format!("resource_metadata={}", quoted_string(url)));
+    }
+
+    let bearer = if params.is_empty() {
+        "Bearer".to_string()
+    } else {
+        format!("Bearer {}", params.join(", "))
+    };
+
+    let Some(algs) = dpop_algs else {
+        return bearer;
+    };
+    let mut dpop_params = vec![format!("algs={}", quoted_string(algs))];
+    if dpop_error {
+        dpop_params.push(format!(
+            "error={}",
+            quoted_string(BearerErrorCode::InvalidDpopProof.as_str())
+        ));
+        dpop_params.push(format!(
+            "error_description={}",
+            quoted_string(&error.description)
+        ));
+    }
+    format!("{bearer}, DPoP {}", dpop_params.join(", "))
 }
 
 /// Build authentication error response
-pub fn auth_error_response(error: &str, host: Option<&str>, trace_id: Option<&str>) -> Response {
-    let www_auth = host.map_or_else(
-        || format!(r#"Bearer error="unauthorized", error_description="{error}""#),
-        |h| format!(
-            r#"Bearer error="unauthorized", error_description="{error}", resource_metadata="https://{h}/.well-known/oauth-protected-resource""#
-        ),
-    );
-
-    let body = serde_json::json!({
-        "error": "unauthorized",
-        "error_description": error
+pub fn auth_error_response(
+    error: &AuthError,
+    scope: Option<&str>,
+    resource_metadata: Option<&str>,
+    dpop_algs: Option<&str>,
+    trace_id: Option<&str>,
+) -> Response {
+    let mut body = serde_json::json!({
+        "error": error.code.map_or("unauthorized", BearerErrorCode::as_str),
+        "error_description": error.description
     });
+    if let (Some(scope), Some(body)) = (
+        scope.filter(|scope| !scope.is_empty()),
+        body.as_object_mut(),
+    ) {
+        body.insert("scope".to_string(), Value::from(scope));
+    }
 
+    let mut builder = Response::builder();
+    builder
+        .status(error.status())
+        .header(
+            "WWW-Authenticate",
+            www_authenticate(error, scope, resource_metadata, dpop_algs),
+        )
+        .header("Content-Type", "application/json");
     if let Some(trace_id) = trace_id {
-        Response::builder()
-            .status(401)
-            .header("WWW-Authenticate", www_auth)
-            .header("Content-Type", "application/json")
-            .header("X-Trace-Id", trace_id)
-            .body(body.to_string())
-            .build()
-    } else {
-        Response::builder()
-            .status(401)
-            .header("WWW-Authenticate", www_auth)
-            .header("Content-Type", "application/json")
-            .body(body.to_string())
-            .build()
+        builder.header("X-Trace-Id", trace_id);
     }
+    builder.body(body.to_string()).build()
 }
 
-/// Verify `JWT` token with proper signature verification
-async fn verify_token(token: &str, provider: &dyn AuthProvider) -> Result<Claims, String> {
-    // Decode the header to get the key ID and algorithm
-    let header = decode_header(token).map_err(|_| "Invalid token format".to_string())?;
+/// Build the RFC 6750 response for a token that lacks required scopes
+pub fn insufficient_scope_response(
+    missing: &[String],
+    resource_metadata: Option<&str>,
+    trace_id: Option<&str>,
+) -> Response {
+    auth_error_response(
+        &AuthError::insufficient_scope("The access token does not grant the required scope"),
+        Some(&missing.join(" ")),
+        resource_metadata,
+        None,
+        trace_id,
+    )
+}
 
-    // Get the key ID from header
-    let kid = header
-        .kid
-        .ok_or_else(|| "Invalid token format".to_string())?;
+/// Fetch the key a token was signed with from the provider's JWKS
+async fn fetch_decoding_key(provider: &dyn AuthProvider, kid: &str) -> Result<DecodingKey, String> {
+    if !provider.allows_url(provider.jwks_uri()) {
+        eprintln!(
+            "JWKS URI {} is outside the allowed domains of provider '{}'",
+            provider.jwks_uri(),
+            provider.name()
+        );
+        return Err("Token validation failed".to_string());
+    }
 
-    // Fetch the appropriate decoding key from JWKS
-    let decoding_key = jwks::get_decoding_key(provider.jwks_uri(), &kid)
+    jwks::get_decoding_key(provider.jwks_uri(), kid)
         .await
         .map_err(|e| {
             eprintln!(
@@ -83,49 +297,274 @@ async fn verify_token(token: &str, provider: &dyn AuthProvider) -> Result<Claims
                 provider.jwks_uri()
             );
             "Token validation failed".to_string()
-        })?;
+        })
+}
+
+/// The key a token was signed with. Local providers hold their own keys; everyone else
+/// publishes them as a JWKS.
+async fn provider_decoding_key(
+    provider: &dyn AuthProvider,
+    kid: &str,
+) -> Result<DecodingKey, String> {
+    match provider.decoding_key(kid) {
+        Some(key) => Ok(key),
+        None => fetch_decoding_key(provider, kid).await,
+    }
+}
+
+/// Verify a bearer token, introspecting opaque tokens when the provider allows it. Positive
+/// introspection results are reused for at most `cache_ttl` seconds.
+async fn verify_token(
+    token: &str,
+    provider: &dyn AuthProvider,
+    audiences: &[String],
+    cache_ttl: u64,
+) -> Result<Claims, String> {
+    // Anything that does not parse as a JWT is treated as an opaque token
+    let Ok(header) = decode_header(token) else {
+        let claims = match provider.introspection() {
+            Some(config) => {
+                introspection::introspect_token(token, provider, config, audiences, cache_ttl)
+                    .await?
+            }
+            None => return Err("Invalid token format".to_string()),
+        };
+        return enforce_policy(provider, claims);
+    };
+
+    // Providers that sign other JWTs, such as authorization codes, mark their access tokens
+    if let Some(typ) = provider.access_token_type() {
+        if !header
+            .typ
+            .as_deref()
+            .is_some_and(|actual| actual.eq_ignore_ascii_case(typ))
+        {
+            eprintln!("Token of provider '{}' is not typ {typ}", provider.name());
+            return Err("Token validation failed".to_string());
+        }
+    }
+
+    // Get the key ID from header
+    let kid = header
+        .kid
+        .ok_or_else(|| "Invalid token format".to_string())?;
+
+    let decoding_key = provider_decoding_key(provider, &kid).await?;
 
     // Set up validation parameters
     let mut validation = Validation::new(header.alg);
     validation.set_issuer(&[provider.issuer()]);
 
-    if let Some(aud) = provider.audience() {
-        if aud.is_empty() {
-            // Empty audience means don't validate
-            eprintln!("Skipping audience validation (empty audience configured)");
-            validation.validate_aud = false;
-        } else {
-            eprintln!("Validating audience: {aud}");
-            validation.set_audience(&[aud]);
-        }
-    } else {
+    if audiences.is_empty() {
         // No audience configured means don't validate
         eprintln!("Skipping audience validation (no audience configured)");
         validation.validate_aud = false;
+    } else {
+        // The token is accepted if its `aud` contains any of the expected audiences
+        eprintln!("Validating audience: {}", audiences.join(", "));
+        validation.set_audience(audiences);
     }
 
     // Validate required claims
     validation.validate_exp = true;
     validation.validate_nbf = true;
+    validation.leeway = provider.options().validation.leeway;
 
     // Decode and verify the token with signature
     let token_data = decode::<Claims>(token, &decoding_key, &validation).map_err(|e| {
         eprintln!("JWT verification failed: {e:?}");
-        "Token validation failed".to_string()
+        // Tell clients when refreshing or re-requesting the token can help
+        match e.kind() {
+            ErrorKind::ExpiredSignature => "Token has expired",
+            ErrorKind::ImmatureSignature => "Token is not yet valid",
+            ErrorKind::InvalidAudience => "Token audience is not accepted",
+            ErrorKind::InvalidIssuer => "Token issuer is not accepted",
+            _ => "Token validation failed",
+        }
+        .to_string()
     })?;
 
-    let sub = &token_data.claims.sub;
+    let claims = enforce_policy(provider, token_data.claims)?;
+    let sub = &claims.sub;
     eprintln!("Token verified successfully for subject: {sub}");
-    Ok(token_data.claims)
+    Ok(claims)
+}
+
+/// Hold verified claims to the provider's validation policy
+fn enforce_policy(provider: &dyn AuthProvider, claims: Claims) -> Result<Claims, String> {
+    match provider.options().validation.check(&claims) {
+        Ok(()) => Ok(claims),
+        Err(violation) => {
+            eprintln!(
+                "Token rejected by validation policy of provider '{}': {violation}",
+                provider.name()
+            );
+            Err(violation.description().to_string())
+        }
+    }
+}
+
+/// Read a JWT's `iss` claim without verifying it. This only routes the token to a provider,
+/// which then verifies the signature and the issuer.
+fn unverified_issuer(token: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Unverified {
+        iss: String,
+    }
+
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<Unverified>(&bytes)
+        .ok()
+        .map(|claims| claims.iss)
+}
+
+/// Pick the provider for a token: JWTs by their issuer, opaque tokens by the first provider
+/// that can introspect them
+fn select_provider<'a>(
+    token: &str,
+    registry: &'a ProviderRegistry,
+) -> Result<&'a dyn AuthProvider, AuthError> {
+    if decode_header(token).is_ok() {
+        let issuer = unverified_issuer(token)
+            .ok_or_else(|| AuthError::invalid_token("Invalid token format"))?;
+        return registry.find_by_issuer(&issuer).ok_or_else(|| {
+            eprintln!("No provider configured for token issuer {issuer}");
+            AuthError::invalid_token("Token issuer is not accepted")
+        });
+    }
+
+    registry
+        .providers()
+        .iter()
+        .find(|provider| provider.introspection().is_some())
+        .map(std::convert::AsRef::as_ref)
+        .ok_or_else(|| AuthError::invalid_token("Invalid token format"))
+}
+
+/// Audiences a token may be issued for: the provider's configured audiences, plus the
+/// resource URL when tokens are bound to it (RFC 8707). A token naming any of them is accepted,
+/// so a strict binding leaves the provider audience unset.
+pub fn expected_audiences(provider: &dyn AuthProvider, resource_url: Option<&str>) -> Vec<String> {
+    let mut audiences: Vec<String> = provider.audiences().into_iter().map(String::from).collect();
+
+    if let Some(resource_url) = resource_url {
+        // Accept the resource URL with or without a trailing slash
+        let trimmed = resource_url.trim_end_matches('/');
+        for aud in [trimmed.to_string(), format!("{trimmed}/")] {
+            if !audiences.contains(&aud) {
+                audiences.push(aud);
+            }
+        }
+    }
+
+    audiences
+}
+
+/// Verify an OIDC back-channel logout token. Only signed JWTs from a configured provider are
+/// accepted, and only when addressed to its audience, the client ID.
+pub async fn verify_logout_token(
+    token: &str,
+    registry: &ProviderRegistry,
+) -> Result<LogoutClaims, String> {
+    let Ok(header) = decode_header(token) else {
+        return Err("Logout token must be a signed JWT".to_string());
+    };
+    let provider = select_provider(token, registry).map_err(|e| e.description)?;
+
+    // Without an audience, a logout token for any client of the provider would be accepted
+    let audiences = expected_audiences(provider, None);
+    if audiences.is_empty() {
+        return Err(format!(
+            "Provider '{}' has no audience to check logout tokens against",
+            provider.name()
+        ));
+    }
+
+    let kid = header
+        .kid
+        .ok_or_else(|| "Invalid token format".to_string())?;
+    let decoding_key = provider_decoding_key(provider, &kid).await?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[provider.issuer()]);
+    validation.set_audience(&audiences);
+    validation.leeway = provider.options().validation.leeway;
+
+    decode::<LogoutClaims>(token, &decoding_key, &validation)
+        .map(|data| data.claims)
+        .map_err(|e| {
+            eprintln!("Logout token verification failed: {e:?}");
+            "Logout token validation failed".to_string()
+        })
+}
+
+/// Verify a provider token, serving signed tokens verified within the cache TTL from the cache
+async fn verify_provider_token(
+    token: &str,
+    provider: &dyn AuthProvider,
+    audiences: &[String],
+    token_cache_ttl: u64,
+) -> Result<(Claims, UserContext), AuthError> {
+    // Signed tokens verified within the cache TTL skip the signature check
+    let kid = (token_cache_ttl > 0)
+        .then(|| decode_header(token).ok()?.kid)
+        .flatten();
+    if kid.is_some() {
+        if let Some((claims, user_context)) = token_cache::get(token, audiences).await {
+            // Time-based policy such as the maximum age still applies to cached tokens
+            return enforce_policy(provider, claims)
+                .map(|claims| (claims, user_context))
+                .map_err(AuthError::invalid_token);
+        }
+    }
+
+    let claims = verify_token(token, provider, audiences, token_cache_ttl)
+        .await
+        .map_err(AuthError::invalid_token)?;
+    let user_context = provider.extract_user_context(&claims);
+    if let Some(kid) = &kid {
+        token_cache::insert(
+            token,
+            audiences,
+            provider.jwks_uri(),
+            kid,
+            token_cache_ttl,
+            &claims,
+            &user_context,
+        )
+        .await;
+    }
+    Ok((claims, user_context))
 }
 
 /// Verify the request has valid authentication
 pub async fn verify_request(
     req: &Request,
-    provider: &dyn AuthProvider,
-    host: Option<&str>,
-    trace_id: Option<&str>,
-) -> Result<(Claims, UserContext), Response> {
+    registry: &ProviderRegistry,
+    api_keys: Option<&ApiKeyConfig>,
+    resource_audience: Option<&str>,
+    token_cache_ttl: u64,
+    dpop: &ProofTarget<'_>,
+) -> Result<(Claims, UserContext), AuthError> {
+    let api_keys = api_keys.filter(|config| config.is_enabled());
+
+    // A dedicated API key header is only ever checked against API keys
+    if let Some(api_keys) = api_keys {
+        let api_key_header = req
+            .headers()
+            .find(|(name, _)| name.eq_ignore_ascii_case("x-api-key"))
+            .and_then(|(_, value)| value.as_str());
+
+        if let Some(key) = api_key_header {
+            return match api_keys::verify_api_key(key.trim(), api_keys) {
+                Ok(Some(result)) => Ok(result),
+                Ok(None) => Err(AuthError::invalid_token("Invalid API key")),
+                Err(e) => Err(AuthError::invalid_token(e)),
+            };
+        }
+    }
+
     // Extract authorization header
     let auth_header = req
         .headers()
@@ -133,30 +572,185 @@ pub async fn verify_request(
         .and_then(|(_, value)| value.as_str());
 
     let Some(auth) = auth_header else {
-        return Err(auth_error_response(
+        return Err(AuthError::missing_credentials(
             "Missing authorization header",
-            host,
-            trace_id,
         ));
     };
 
-    let Some(token) = extract_bearer_token(auth) else {
-        return Err(auth_error_response(
-            "Invalid authorization header format",
-            host,
-            trace_id,
+    // DPoP-bound tokens use their own scheme when DPoP is accepted (RFC 9449)
+    let dpop_token = dpop
+        .config
+        .is_enabled()
+        .then(|| dpop::extract_dpop_token(auth))
+        .flatten();
+
+    // Other schemes carry no bearer credentials, so they get a challenge without an error
+    let Some(token) = dpop_token.or_else(|| extract_bearer_token(auth)) else {
+        return Err(AuthError::missing_credentials(
+            "Unsupported authorization scheme",
         ));
     };
 
-    // Debug logging - remove or reduce for production
-    // eprintln!("Verifying token with issuer: {}", &config.issuer);
-    // eprintln!("JWKS URI: {}", &config.jwks_uri);
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        return Err(AuthError::invalid_request("Malformed bearer token"));
+    }
 
-    match verify_token(token, provider).await {
-        Ok(claims) => {
-            let user_context = provider.extract_user_context(&claims);
-            Ok((claims, user_context))
+    // Bearer values that are not JWTs may be API keys
+    if let Some(api_keys) = api_keys.filter(|_| dpop_token.is_none()) {
+        if decode_header(token).is_err() {
+            match api_keys::verify_api_key(token, api_keys) {
+                Ok(Some(result)) => return Ok(result),
+                Ok(None) => {}
+                Err(e) => return Err(AuthError::invalid_token(e)),
+            }
         }
-        Err(e) => Err(auth_error_response(&e, host, trace_id)),
+    }
+
+    if registry.providers().is_empty() {
+        return Err(AuthError::invalid_token("Invalid API key"));
+    }
+    let provider = select_provider(token, registry)?;
+
+    let audiences = expected_audiences(provider, resource_audience);
+
+    let (claims, user_context) =
+        verify_provider_token(token, provider, &audiences, token_cache_ttl).await?;
+
+    // The proof is checked on every request, including tokens served from the cache
+    dpop::check_binding(req, token, &claims, dpop, dpop_token.is_some())?;
+
+    Ok((claims, user_context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::AuthKitProvider;
+
+    #[test]
+    fn test_expected_audiences() {
+        let provider = AuthKitProvider::new(
+            "https://example.authkit.app".to_string(),
+            None,
+            Some("api-one, api-two".to_string()),
+        );
+
+        assert_eq!(
+            expected_audiences(&provider, None),
+            vec!["api-one", "api-two"]
+        );
+        assert_eq!(
+            expected_audiences(&provider, Some("https://mcp.example.com/mcp")),
+            vec![
+                "api-one",
+                "api-two",
+                "https://mcp.example.com/mcp",
+                "https://mcp.example.com/mcp/"
+            ]
+        );
+
+        let unbound = AuthKitProvider::new("https://example.authkit.app".to_string(), None, None);
+        assert!(expected_audiences(&unbound, None).is_empty());
+    }
+
+    #[test]
+    fn test_extract_bearer_token() {
+        assert_eq!(extract_bearer_token("Bearer abc.def"), Some("abc.def"));
+        assert_eq!(extract_bearer_token("bearer  abc "), Some("abc"));
+        assert_eq!(extract_bearer_token("Bearer"), Some(""));
+        assert_eq!(extract_bearer_token("Basic dXNlcjpwYXNz"), None);
+    }
+
+    #[test]
+    fn test_error_status() {
+        assert_eq!(AuthError::missing_credentials("missing").status(), 401);
+        assert_eq!(AuthError::invalid_request("malformed").status(), 400);
+        assert_eq!(AuthError::invalid_token("expired").status(), 401);
+        assert_eq!(AuthError::insufficient_scope("scope").status(), 403);
+        assert_eq!(AuthError::invalid_dpop_proof("replayed").status(), 401);
+    }
+
+    #[test]
+    fn test_www_authenticate() {
+        assert_eq!(
+            www_authenticate(&AuthError::missing_credentials("missing"), None, None, None),
+            "Bearer"
+        );
+        assert_eq!(
+            www_authenticate(
+                &AuthError::missing_credentials("missing"),
+                Some("mcp:read"),
+                Some("https://mcp.example.com/.well-known/oauth-protected-resource"),
+                None
+            ),
+            r#"Bearer scope="mcp:read", resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource""#
+        );
+        assert_eq!(
+            www_authenticate(
+                &AuthError::invalid_token("Token has expired"),
+                None,
+                None,
+                None
+            ),
+            r#"Bearer error="invalid_token", error_description="Token has expired""#
+        );
+        assert_eq!(
+            www_authenticate(
+                &AuthError::insufficient_scope("Needs scope"),
+                Some("tools:call admin"),
+                None,
+                None
+            ),
+            r#"Bearer error="insufficient_scope", error_description="Needs scope", scope="tools:call admin""#
+        );
+        assert_eq!(
+            www_authenticate(
+                &AuthError::invalid_dpop_proof("Replayed proof"),
+                None,
+                None,
+                Some("ES256 EdDSA")
+            ),
+            r#"Bearer, DPoP algs="ES256 EdDSA", error="invalid_dpop_proof", error_description="Replayed proof""#
+        );
+    }
+
+    #[test]
+    fn test_quoted_string_escaping() {
+        assert_eq!(quoted_string("plain"), r#""plain""#);
+        assert_eq!(quoted_string("say \"hi\" \\ bye"), r#""say \"hi\" \\ bye""#);
+        assert_eq!(quoted_string("line\r\nbreak"), r#""linebreak""#);
+    }
+
+    #[test]
+    fn test_select_provider_by_issuer() {
+        let mut registry = ProviderRegistry::new();
+        registry.add_provider(Box::new(AuthKitProvider::new(
+            "https://one.authkit.app".to_string(),
+            None,
+            None,
+        )));
+        registry.add_provider(Box::new(AuthKitProvider::new(
+            "https://two.authkit.app".to_string(),
+            None,
+            None,
+        )));
+
+        let token = |iss: &str| {
+            let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","kid":"k1"}"#);
+            let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"iss":"{iss}","sub":"u"}}"#));
+            format!("{header}.{payload}.c2ln")
+        };
+
+        let selected = select_provider(&token("https://two.authkit.app"), &registry).unwrap();
+        assert_eq!(selected.issuer(), "https://two.authkit.app");
+
+        let unknown = select_provider(&token("https://evil.example.com"), &registry);
+        assert_eq!(
+            unknown.err(),
+            Some(AuthError::invalid_token("Token issuer is not accepted"))
+        );
+
+        // Opaque tokens need a provider that can introspect them
+        assert!(select_provider("opaque-token", &registry).is_err());
     }
 }
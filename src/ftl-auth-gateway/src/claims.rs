@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::providers::UserContext;
+
+/// Claims read when no mapping is configured, covering common identity provider conventions
+const DEFAULT_NAME_CLAIMS: &[&str] = &["name"];
+const DEFAULT_TENANT_CLAIMS: &[&str] = &["org_id", "tenant_id", "tid"];
+const DEFAULT_ROLE_CLAIMS: &[&str] = &["roles", "role"];
+const DEFAULT_GROUP_CLAIMS: &[&str] = &["groups"];
+
+/// Where the extended user context is read from in token claims.
+///
+/// Each path is a claim name (`org_id`, `https://example.com/groups`), a dotted path
+/// (`realm_access.roles`) or a JSON pointer (`/realm_access/roles`).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClaimMappings {
+    /// Display name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Tenant or organization ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Roles, from a string or an array of strings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<String>,
+    /// Groups, from a string or an array of strings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<String>,
+    /// Additional attributes, keyed by the name they are forwarded under
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Find a claim by exact name, JSON pointer or dotted path
+pub fn lookup<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.starts_with('/') {
+        return claims.pointer(path);
+    }
+
+    // Claim names may themselves contain dots, as namespaced URLs do
+    claims.get(path).or_else(|| {
+        path.split('.')
+            .try_fold(claims, |value, segment| value.get(segment))
+    })
+}
+
+/// A claim value as a single string
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// A claim value as a list of strings
+fn as_strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values.iter().filter_map(as_string).collect(),
+        value => as_string(value).into_iter().collect(),
+    }
+}
+
+/// The value at the configured path, or at the first default claim present
+fn resolve<'a>(claims: &'a Value, path: Option<&str>, defaults: &[&str]) -> Option<&'a Value> {
+    path.map_or_else(
+        || defaults.iter().find_map(|claim| claims.get(*claim)),
+        |path| lookup(claims, path),
+    )
+}
+
+impl ClaimMappings {
+    /// Populate the extended user context from a token's claims
+    pub fn apply(&self, claims: &Value, user_context: &mut UserContext) {
+        user_context.name =
+            resolve(claims, self.name.as_deref(), DEFAULT_NAME_CLAIMS).and_then(as_string);
+        user_context.tenant =
+            resolve(claims, self.tenant.as_deref(), DEFAULT_TENANT_CLAIMS).and_then(as_string);
+        user_context.roles = resolve(claims, self.roles.as_deref(), DEFAULT_ROLE_CLAIMS)
+            .map(as_strings)
+            .unwrap_or_default();
+        user_context.groups = resolve(claims, self.groups.as_deref(), DEFAULT_GROUP_CLAIMS)
+            .map(as_strings)
+            .unwrap_or_default();
+        user_context.attributes = self
+            .attributes
+            .iter()
+            .filter_map(|(key, path)| Some((key.clone(), lookup(claims, path)?.clone())))
+            .collect();
+    }
+
+    /// Check every mapping names a claim
+    pub fn validate(&self) -> Result<(), String> {
+        let mut paths = [&self.name, &self.tenant, &self.roles, &self.groups]
+            .into_iter()
+            .flatten()
+            .chain(self.attributes.values());
+        if paths.any(|path| path.trim().is_empty()) {
+            return Err("claim mapping paths must not be empty".to_string());
+        }
+        if self.attributes.keys().any(|key| key.trim().is_empty()) {
+            return Err("claim mapping attribute names must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> UserContext {
+        UserContext {
+            id: "user_123".to_string(),
+            provider: "authkit".to_string(),
+            ..UserContext::default()
+        }
+    }
+
+    fn claims() -> Value {
+        serde_json::json!({
+            "sub": "user_123",
+            "name": "Ada Lovelace",
+            "org_id": "org_456",
+            "role": "admin",
+            "realm_access": { "roles": ["reader", "writer"] },
+            "https://example.com/groups": ["eng", "ops"],
+            "plan": { "tier": "pro", "seats": 25 }
+        })
+    }
+
+    #[test]
+    fn test_lookup() {
+        let claims = claims();
+        assert_eq!(
+            lookup(&claims, "realm_access.roles"),
+            lookup(&claims, "/realm_access/roles")
+        );
+        assert!(lookup(&claims, "https://example.com/groups").is_some());
+        assert_eq!(lookup(&claims, "plan.seats"), Some(&Value::from(25)));
+        assert!(lookup(&claims, "plan.missing").is_none());
+    }
+
+    #[test]
+    fn test_default_mappings() {
+        let mut user = user();
+        ClaimMappings::default().apply(&claims(), &mut user);
+
+        assert_eq!(user.name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(user.tenant.as_deref(), Some("org_456"));
+        assert_eq!(user.roles, vec!["admin"]);
+        assert!(user.groups.is_empty());
+        assert!(user.attributes.is_empty());
+    }
+
+    #[test]
+    fn test_configured_mappings() {
+        let mappings: ClaimMappings = serde_json::from_value(serde_json::json!({
+            "roles": "realm_access.roles",
+            "groups": "https://example.com/groups",
+            "tenant": "/plan/tier",
+            "attributes": { "seats": "plan.seats", "missing": "nope" }
+        }))
+        .unwrap();
+        let mut user = user();
+        mappings.apply(&claims(), &mut user);
+
+        assert_eq!(user.roles, vec!["reader", "writer"]);
+        assert_eq!(user.groups, vec!["eng", "ops"]);
+        assert_eq!(user.tenant.as_deref(), Some("pro"));
+        assert_eq!(user.attributes.get("seats"), Some(&Value::from(25)));
+        assert!(!user.attributes.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ClaimMappings::default().validate().is_ok());
+
+        let empty = ClaimMappings {
+            roles: Some(String::new()),
+            ..ClaimMappings::default()
+        };
+        assert!(empty.validate().is_err());
+    }
+}
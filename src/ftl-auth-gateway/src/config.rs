@@ -1,16 +1,117 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use spin_sdk::variables;
 
-use crate::providers::{AuthKitProvider, OidcProvider, OidcProviderConfig, ProviderRegistry};
+use crate::api_keys::{ApiKeyConfig, ApiKeyEntry};
+use crate::claims::ClaimMappings;
+use crate::cors::{self, CorsConfig};
+use crate::dev::{self, DevProvider};
+use crate::discovery;
+use crate::domains;
+use crate::dpop::DpopConfig;
+use crate::forwarded::Cidr;
+use crate::headers::{self, HeaderPolicy};
+use crate::introspection::IntrospectionConfig;
+use crate::policy::{AnonymousPolicy, ScopePolicy};
+use crate::providers::{
+    AuthKitProvider, AuthProvider, OidcProvider, OidcProviderConfig, ProviderOptions,
+    ProviderRegistry,
+};
+use crate::registration::RegistrationConfig;
+use crate::revocation::RevocationConfig;
+use crate::token_exchange::TokenExchangeConfig;
+use crate::validation::ValidationPolicy;
 
-/// Gateway configuration
+/// Gateway configuration, read from the structured `auth_config` variable or from the flat
+/// `auth_*` variables
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct GatewayConfig {
+    #[serde(default = "default_mcp_gateway_url")]
     pub mcp_gateway_url: String,
+    #[serde(default = "default_trace_id_header")]
     pub trace_id_header: String,
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
-    pub provider: Option<ProviderConfig>,
+    /// Identity providers; tokens are matched to one by issuer, and the first is primary
+    #[serde(default)]
+    pub providers: Vec<ProviderConfig>,
+    #[serde(default)]
+    pub scope_policy: ScopePolicy,
+    /// Calls allowed without credentials
+    #[serde(default)]
+    pub anonymous: AnonymousPolicy,
+    #[serde(default)]
+    pub api_keys: Option<ApiKeyConfig>,
+    #[serde(default)]
+    pub registration: RegistrationConfig,
+    /// Fields replaced in the served authorization server metadata
+    #[serde(default)]
+    pub metadata_overrides: Map<String, Value>,
+    /// Canonical resource URL of the MCP endpoint; derived from the request when unset
+    #[serde(default)]
+    pub resource_url: Option<String>,
+    /// Require tokens to carry the resource URL as an audience (RFC 8707). Needs `resource_url`
+    /// or `public_url`, so the audience never comes from the request's `Host`. Provider
+    /// audiences stay accepted alongside it.
+    #[serde(default)]
+    pub resource_audience: bool,
+    /// Public base URL of the gateway; derived from the request when unset
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// Path of the MCP endpoint under the public base URL
+    #[serde(default = "default_mcp_path")]
+    pub mcp_path: String,
+    /// Proxies whose forwarding headers are honored
+    #[serde(default)]
+    pub trusted_proxies: Vec<Cidr>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub headers: HeaderPolicy,
+    /// Reject tokens that carry no tenant claim
+    #[serde(default)]
+    pub require_tenant: bool,
+    /// Secret shared with the MCP gateway, which only trusts tenant headers signed with it
+    #[serde(default)]
+    pub tenant_secret: Option<String>,
+    #[serde(default)]
+    pub revocation: RevocationConfig,
+    /// Seconds a verified token is cached, capped at its `exp`; 0 disables the cache. The cache is
+    /// per component instance, so it only helps hosts that reuse instances across requests.
+    #[serde(default = "default_token_cache_ttl")]
+    pub token_cache_ttl: u64,
+    /// Sender-constrained tokens (RFC 9449)
+    #[serde(default)]
+    pub dpop: DpopConfig,
+    /// Origins allowed to call the MCP endpoint; loopback origins when empty
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Mirror of the component's `allowed_outbound_hosts`, used to catch unreachable hosts
+    #[serde(default)]
+    pub allowed_outbound_hosts: Vec<String>,
+}
+
+fn default_mcp_gateway_url() -> String {
+    "http://ftl-mcp-gateway.spin.internal/mcp-internal".to_string()
+}
+
+fn default_trace_id_header() -> String {
+    "X-Trace-Id".to_string()
+}
+
+/// A structured configuration is only written to turn authentication on
+const fn default_enabled() -> bool {
+    true
+}
+
+const fn default_token_cache_ttl() -> u64 {
+    60
+}
+
+fn default_mcp_path() -> String {
+    "/mcp".to_string()
 }
 
 /// Provider configuration enum
@@ -24,6 +125,8 @@ pub enum ProviderConfig {
         jwks_uri: Option<String>,
         #[serde(default)]
         audience: Option<String>,
+        #[serde(flatten)]
+        options: ProviderOptions,
     },
     Oidc {
         name: String,
@@ -37,17 +140,238 @@ pub enum ProviderConfig {
         userinfo_endpoint: Option<String>,
         #[serde(default)]
         allowed_domains: Vec<String>,
+        #[serde(flatten)]
+        options: ProviderOptions,
+    },
+    /// OIDC provider whose endpoints are read from the issuer's discovery document
+    OidcDiscovery {
+        name: String,
+        issuer: String,
+        #[serde(default)]
+        audience: Option<String>,
+        #[serde(default)]
+        allowed_domains: Vec<String>,
+        #[serde(flatten)]
+        options: ProviderOptions,
+    },
+    /// Local provider that mints its own tokens, for development only
+    Dev {
+        /// Must be `true`; the gateway refuses to start with a dev provider otherwise
+        #[serde(default)]
+        dev_mode: bool,
+        #[serde(default = "default_dev_issuer")]
+        issuer: String,
+        #[serde(default)]
+        audience: Option<String>,
+        /// Base64url-encoded 32-byte Ed25519 seed; a fixed, public key when unset
+        #[serde(default)]
+        signing_seed: Option<String>,
+        /// Subject of minted tokens when the request names none
+        #[serde(default)]
+        subject: Option<String>,
+        /// Claims added to every minted token
+        #[serde(default)]
+        claims: Map<String, Value>,
+        #[serde(flatten)]
+        options: ProviderOptions,
     },
 }
 
+fn default_dev_issuer() -> String {
+    dev::DEFAULT_ISSUER.to_string()
+}
+
+impl ProviderConfig {
+    pub fn issuer(&self) -> &str {
+        match self {
+            Self::AuthKit { issuer, .. }
+            | Self::Oidc { issuer, .. }
+            | Self::OidcDiscovery { issuer, .. }
+            | Self::Dev { issuer, .. } => issuer,
+        }
+    }
+
+    pub fn audience(&self) -> Option<&str> {
+        match self {
+            Self::AuthKit { audience, .. }
+            | Self::Oidc { audience, .. }
+            | Self::OidcDiscovery { audience, .. }
+            | Self::Dev { audience, .. } => audience.as_deref(),
+        }
+    }
+
+    /// Apply the HTTPS rules of the flat variables to every configured URL
+    fn normalized(mut self) -> Result<Self> {
+        let https = GatewayConfig::ensure_https_url;
+        let https_opt = |url: Option<String>| url.map(https).transpose();
+
+        match &mut self {
+            Self::AuthKit {
+                issuer,
+                jwks_uri,
+                options,
+                ..
+            } => {
+                *issuer = https(std::mem::take(issuer))?;
+                *jwks_uri = https_opt(jwks_uri.take())?;
+                Self::normalize_options(options)?;
+            }
+            Self::Oidc {
+                issuer,
+                jwks_uri,
+                authorization_endpoint,
+                token_endpoint,
+                userinfo_endpoint,
+                options,
+                ..
+            } => {
+                *issuer = https(std::mem::take(issuer))?;
+                *jwks_uri = https(std::mem::take(jwks_uri))?;
+                *authorization_endpoint = https(std::mem::take(authorization_endpoint))?;
+                *token_endpoint = https(std::mem::take(token_endpoint))?;
+                *userinfo_endpoint = https_opt(userinfo_endpoint.take())?;
+                Self::normalize_options(options)?;
+            }
+            Self::OidcDiscovery {
+                issuer, options, ..
+            } => {
+                *issuer = https(std::mem::take(issuer))?;
+                Self::normalize_options(options)?;
+            }
+            // The development provider is served by the gateway itself, often over plain HTTP
+            Self::Dev {
+                dev_mode,
+                issuer,
+                signing_seed,
+                options,
+                ..
+            } => {
+                if !*dev_mode {
+                    anyhow::bail!(
+                        "The dev provider mints its own tokens and requires dev_mode = true"
+                    );
+                }
+                if !(issuer.starts_with("http://") || issuer.starts_with("https://")) {
+                    anyhow::bail!("Development provider issuer must be an http(s) URL: {issuer}");
+                }
+                if let Some(seed) = signing_seed {
+                    dev::validate_seed(seed).map_err(anyhow::Error::msg)?;
+                }
+                Self::normalize_options(options)?;
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn normalize_options(options: &mut ProviderOptions) -> Result<()> {
+        options
+            .claim_mappings
+            .validate()
+            .map_err(anyhow::Error::msg)?;
+        options
+            .validation
+            .validate()
+            .map_err(|e| anyhow::anyhow!("validation: {e}"))?;
+        if let Some(introspection) = &mut options.introspection {
+            introspection.endpoint = introspection
+                .endpoint
+                .take()
+                .map(GatewayConfig::ensure_https_url)
+                .transpose()?;
+        }
+        if let Some(token_exchange) = &mut options.token_exchange {
+            token_exchange
+                .validate()
+                .map_err(|e| anyhow::anyhow!("token_exchange: {e}"))?;
+            token_exchange.endpoint = token_exchange
+                .endpoint
+                .take()
+                .map(GatewayConfig::ensure_https_url)
+                .transpose()?;
+        }
+        Ok(())
+    }
+
+    /// Domains outbound fetches may reach besides the issuer's own host
+    pub fn allowed_domains(&self) -> Vec<&str> {
+        match self {
+            Self::AuthKit { .. } => AuthKitProvider::ALLOWED_DOMAINS.to_vec(),
+            Self::Oidc {
+                allowed_domains, ..
+            }
+            | Self::OidcDiscovery {
+                allowed_domains, ..
+            } => allowed_domains.iter().map(String::as_str).collect(),
+            Self::Dev { .. } => Vec::new(),
+        }
+    }
+
+    /// Configured URLs the gateway fetches for this provider, with the variable naming them
+    fn outbound_urls(&self) -> Vec<(&'static str, &str)> {
+        let (jwks_uri, options) = match self {
+            Self::AuthKit {
+                jwks_uri, options, ..
+            } => (jwks_uri.as_deref(), options),
+            Self::Oidc {
+                jwks_uri, options, ..
+            } => (Some(jwks_uri.as_str()), options),
+            Self::OidcDiscovery { options, .. } | Self::Dev { options, .. } => (None, options),
+        };
+        let introspection_endpoint = options
+            .introspection
+            .as_ref()
+            .and_then(|config| config.endpoint.as_deref());
+        let token_exchange_endpoint = options
+            .token_exchange
+            .as_ref()
+            .and_then(|config| config.endpoint.as_deref());
+
+        jwks_uri
+            .map(|url| ("auth_provider_jwks_uri", url))
+            .into_iter()
+            .chain(introspection_endpoint.map(|url| ("auth_provider_introspection_endpoint", url)))
+            .chain(
+                token_exchange_endpoint.map(|url| ("auth_provider_token_exchange_endpoint", url)),
+            )
+            .collect()
+    }
+}
+
+/// Read a boolean variable, `false` when unset or invalid
+fn bool_variable(name: &str) -> bool {
+    variables::get(name)
+        .ok()
+        .and_then(|s| s.parse::<bool>().ok())
+        .unwrap_or(false)
+}
+
+/// Read a comma-separated list variable, `None` when unset or empty
+fn list_variable(name: &str) -> Option<Vec<String>> {
+    variables::get(name)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            s.split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+}
+
 impl GatewayConfig {
     /// Load configuration from Spin variables
     pub fn from_spin_vars() -> Result<Self> {
+        // A structured `auth_config` takes precedence over the flat variables
+        if let Some(raw) = variables::get("auth_config")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        {
+            return Self::from_structured(&raw);
+        }
+
         // Read core settings
-        let enabled = variables::get("auth_enabled")
-            .unwrap_or_else(|_| "false".to_string())
-            .parse::<bool>()
-            .unwrap_or(false);
+        let enabled = bool_variable("auth_enabled");
 
         let mcp_gateway_url = variables::get("auth_gateway_url")
             .unwrap_or_else(|_| "http://ftl-mcp-gateway.spin.internal/mcp-internal".to_string());
@@ -64,14 +388,489 @@ impl GatewayConfig {
             Some(Self::load_provider_config(&provider_type)?)
         };
 
+        let scope_policy = Self::load_scope_policy()?;
+
+        let anonymous = Self::load_anonymous_policy();
+
+        let api_keys = Self::load_api_key_config()?;
+
+        let registration = Self::load_registration_config()?;
+
+        // Mirror of the component's `allowed_outbound_hosts`, used to catch unreachable hosts
+        let allowed_outbound_hosts: Vec<String> = variables::get("auth_allowed_outbound_hosts")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        if let Some(provider) = &provider {
+            Self::validate_provider_domains(provider, &registration, &allowed_outbound_hosts)?;
+        }
+
+        // Read authorization server metadata overrides (JSON object, `null` removes a field)
+        let metadata_overrides = variables::get("auth_metadata_overrides")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| serde_json::from_str::<Map<String, Value>>(&s))
+            .transpose()
+            .context("auth_metadata_overrides must be a JSON object")?
+            .unwrap_or_default();
+
+        // Read the canonical resource URL, audience binding and public URL
+        let (resource_url, resource_audience, public_url) = Self::load_resource_urls()?;
+
+        // Read the MCP path and the proxies allowed to set forwarding headers
+        let mcp_path = variables::get("auth_mcp_path")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(default_mcp_path);
+        if !mcp_path.starts_with('/') {
+            anyhow::bail!("auth_mcp_path must start with '/': {mcp_path}");
+        }
+
+        let trusted_proxies = variables::get("auth_trusted_proxies")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(str::parse::<Cidr>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::msg)?;
+
+        let cors = Self::load_cors_config()?;
+
+        let headers = Self::load_header_policy()?;
+
+        let require_tenant = bool_variable("auth_require_tenant");
+        let tenant_secret = variables::get("auth_tenant_secret")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
+        let revocation = Self::load_revocation_config()?;
+        Self::validate_logout_audiences(&revocation, provider.iter())?;
+
+        let token_cache_ttl = Self::load_token_cache_ttl()?;
+
+        let dpop = Self::load_dpop_config()?;
+
+        // Read the Origin allowlist used for DNS-rebinding protection
+        let allowed_origins = variables::get("auth_allowed_origins")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
         Ok(Self {
             mcp_gateway_url,
             trace_id_header,
             enabled,
-            provider,
+            providers: provider.into_iter().collect(),
+            scope_policy,
+            anonymous,
+            api_keys,
+            registration,
+            metadata_overrides,
+            resource_url,
+            resource_audience,
+            public_url,
+            mcp_path,
+            trusted_proxies,
+            cors,
+            headers,
+            require_tenant,
+            tenant_secret,
+            revocation,
+            token_cache_ttl,
+            dpop,
+            allowed_origins,
+            allowed_outbound_hosts,
         })
     }
 
+    /// Parse a structured configuration: JSON when it is an object, TOML otherwise
+    pub fn from_structured(raw: &str) -> Result<Self> {
+        let config: Self = if raw.trim_start().starts_with('{') {
+            serde_json::from_str(raw).context("auth_config is not a valid JSON configuration")?
+        } else {
+            toml::from_str(raw).context("auth_config is not a valid TOML configuration")?
+        };
+
+        config.validated()
+    }
+
+    /// Hold a structured configuration to the same rules as the flat variables
+    fn validated(mut self) -> Result<Self> {
+        self.providers = self
+            .providers
+            .into_iter()
+            .map(ProviderConfig::normalized)
+            .collect::<Result<_>>()?;
+
+        // Tokens are routed to providers by issuer, so each issuer may only appear once
+        for (index, provider) in self.providers.iter().enumerate() {
+            let issuer = provider.issuer();
+            if self
+                .providers
+                .iter()
+                .skip(index + 1)
+                .any(|other| other.issuer() == issuer)
+            {
+                anyhow::bail!("Issuer {issuer} is configured for more than one provider");
+            }
+        }
+
+        if let RegistrationConfig::Proxy {
+            endpoint: endpoint @ Some(_),
+        } = &mut self.registration
+        {
+            *endpoint = endpoint.take().map(Self::ensure_https_url).transpose()?;
+        }
+
+        for provider in &self.providers {
+            Self::validate_provider_domains(
+                provider,
+                &self.registration,
+                &self.allowed_outbound_hosts,
+            )?;
+        }
+
+        if let Some(api_keys) = &self.api_keys {
+            Self::validate_api_keys(&api_keys.keys)?;
+        }
+        self.api_keys = self.api_keys.filter(ApiKeyConfig::is_enabled);
+
+        self.resource_url = self
+            .resource_url
+            .map(|url| Self::validate_public_url("resource_url", &url))
+            .transpose()?;
+        self.public_url = self
+            .public_url
+            .map(|url| Self::validate_public_url("public_url", &url))
+            .transpose()?;
+
+        if self.resource_audience && self.resource_url.is_none() && self.public_url.is_none() {
+            anyhow::bail!(
+                "resource_audience requires resource_url or public_url, so the audience does not \
+                 come from the request's Host header"
+            );
+        }
+
+        if !self.mcp_path.starts_with('/') {
+            anyhow::bail!("mcp_path must start with '/': {}", self.mcp_path);
+        }
+
+        if self.cors.allow_credentials && self.cors.allowed_origins.iter().any(|o| o == "*") {
+            anyhow::bail!("cors.allow_credentials requires explicit cors.allowed_origins");
+        }
+
+        self.headers
+            .validate()
+            .map_err(|e| anyhow::anyhow!("headers.forward_request: {e}"))?;
+
+        self.revocation
+            .validate()
+            .map_err(|e| anyhow::anyhow!("revocation: {e}"))?;
+        Self::validate_logout_audiences(&self.revocation, self.providers.iter())?;
+
+        self.dpop
+            .validate()
+            .map_err(|e| anyhow::anyhow!("dpop: {e}"))?;
+
+        Ok(self)
+    }
+
+    /// Load scope requirements from variables (JSON object with `methods` and `tools` maps)
+    fn load_scope_policy() -> Result<ScopePolicy> {
+        let policy = variables::get("auth_required_scopes")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| serde_json::from_str::<ScopePolicy>(&s))
+            .transpose()
+            .context("auth_required_scopes must be a JSON object with `methods` and `tools` maps")?
+            .unwrap_or_default();
+
+        Ok(policy)
+    }
+
+    /// Load the calls allowed without credentials from variables
+    fn load_anonymous_policy() -> AnonymousPolicy {
+        AnonymousPolicy {
+            methods: list_variable("auth_anonymous_methods").unwrap_or_default(),
+            tools: list_variable("auth_anonymous_tools").unwrap_or_default(),
+        }
+    }
+
+    /// Load the CORS policy from variables
+    fn load_cors_config() -> Result<CorsConfig> {
+        let config = CorsConfig {
+            allowed_origins: list_variable("auth_cors_allowed_origins")
+                .unwrap_or_else(|| vec!["*".to_string()]),
+            allow_credentials: bool_variable("auth_cors_allow_credentials"),
+            allowed_headers: list_variable("auth_cors_allowed_headers")
+                .unwrap_or_else(cors::default_allowed_headers),
+            exposed_headers: list_variable("auth_cors_exposed_headers")
+                .unwrap_or_else(cors::default_exposed_headers),
+            ..CorsConfig::default()
+        };
+
+        if config.allow_credentials && config.allowed_origins.iter().any(|o| o == "*") {
+            anyhow::bail!(
+                "auth_cors_allow_credentials requires explicit origins in auth_cors_allowed_origins"
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Load the header forwarding policy from variables
+    fn load_header_policy() -> Result<HeaderPolicy> {
+        let policy = HeaderPolicy {
+            forward_request: list_variable("auth_forward_request_headers")
+                .unwrap_or_else(headers::default_forward_request_headers),
+            deny_request: list_variable("auth_deny_request_headers").unwrap_or_default(),
+            forward_response: list_variable("auth_forward_response_headers")
+                .unwrap_or_else(headers::default_forward_response_headers),
+            deny_response: list_variable("auth_deny_response_headers").unwrap_or_default(),
+        };
+
+        policy
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_forward_request_headers: {e}"))?;
+
+        Ok(policy)
+    }
+
+    /// Logout tokens are addressed to the client ID, so back-channel logout needs every provider
+    /// to name its audience; otherwise a logout token for any client of the provider would pass
+    fn validate_logout_audiences<'a>(
+        revocation: &RevocationConfig,
+        mut providers: impl Iterator<Item = &'a ProviderConfig>,
+    ) -> Result<()> {
+        if !revocation.backchannel_logout {
+            return Ok(());
+        }
+        if let Some(provider) = providers.find(|provider| provider.audience().is_none()) {
+            anyhow::bail!(
+                "Back-channel logout requires an audience (the client ID) for provider {}",
+                provider.issuer()
+            );
+        }
+        Ok(())
+    }
+
+    /// Load token revocation settings from variables
+    fn load_revocation_config() -> Result<RevocationConfig> {
+        let config = RevocationConfig {
+            store: variables::get("auth_revocation_store")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+            admin_keys: list_variable("auth_revocation_admin_keys").unwrap_or_default(),
+            backchannel_logout: bool_variable("auth_backchannel_logout"),
+        };
+
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_revocation_store: {e}"))?;
+
+        Ok(config)
+    }
+
+    /// Load `DPoP` settings from variables
+    fn load_dpop_config() -> Result<DpopConfig> {
+        let max_proof_age = variables::get("auth_dpop_max_proof_age")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse::<u64>())
+            .transpose()
+            .context("auth_dpop_max_proof_age must be a number of seconds")?;
+
+        let config = DpopConfig {
+            store: variables::get("auth_dpop_store")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+            required: bool_variable("auth_dpop_required"),
+            max_proof_age: max_proof_age.unwrap_or_else(|| DpopConfig::default().max_proof_age),
+        };
+
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_dpop_store: {e}"))?;
+
+        Ok(config)
+    }
+
+    /// Load the canonical resource URL, whether tokens are bound to it, and the public URL
+    fn load_resource_urls() -> Result<(Option<String>, bool, Option<String>)> {
+        let resource_url = variables::get("auth_resource_url")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|url| Self::validate_public_url("auth_resource_url", &url))
+            .transpose()?;
+
+        let resource_audience = bool_variable("auth_resource_audience");
+
+        let public_url = variables::get("auth_public_url")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(|url| Self::validate_public_url("auth_public_url", &url))
+            .transpose()?;
+
+        if resource_audience && resource_url.is_none() && public_url.is_none() {
+            anyhow::bail!(
+                "auth_resource_audience requires auth_resource_url or auth_public_url, so the \
+                 audience does not come from the request's Host header"
+            );
+        }
+
+        Ok((resource_url, resource_audience, public_url))
+    }
+
+    /// Load how long verified tokens are cached from variables
+    fn load_token_cache_ttl() -> Result<u64> {
+        let ttl = variables::get("auth_token_cache_ttl")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse::<u64>())
+            .transpose()
+            .context("auth_token_cache_ttl must be a number of seconds")?;
+
+        Ok(ttl.unwrap_or_else(default_token_cache_ttl))
+    }
+
+    /// Check that every URL fetched for the provider is inside its allowed domains, and that
+    /// those hosts are reachable under the component's `allowed_outbound_hosts`
+    fn validate_provider_domains(
+        provider: &ProviderConfig,
+        registration: &RegistrationConfig,
+        outbound_hosts: &[String],
+    ) -> Result<()> {
+        // The development provider serves its own keys and metadata, so nothing is fetched
+        if matches!(provider, ProviderConfig::Dev { .. }) {
+            return Ok(());
+        }
+
+        let allowed_domains = provider.allowed_domains();
+        for pattern in &allowed_domains {
+            domains::validate_pattern(pattern).map_err(anyhow::Error::msg)?;
+        }
+
+        let mut urls = provider.outbound_urls();
+        if let RegistrationConfig::Proxy {
+            endpoint: Some(endpoint),
+        } = registration
+        {
+            urls.push(("auth_registration_endpoint", endpoint));
+        }
+
+        let issuer = provider.issuer();
+        for (variable, url) in &urls {
+            if !domains::is_url_allowed(url, issuer, &allowed_domains) {
+                anyhow::bail!(
+                    "{variable} {url} is outside the provider's allowed domains; \
+                    add its host to the provider's allowed_domains"
+                );
+            }
+        }
+
+        if outbound_hosts.is_empty() {
+            return Ok(());
+        }
+
+        // Built-in `AuthKit` domains are only checked through the concrete URLs in use
+        let configured_domains: &[String] = match provider {
+            ProviderConfig::Oidc {
+                allowed_domains, ..
+            }
+            | ProviderConfig::OidcDiscovery {
+                allowed_domains, ..
+            } => allowed_domains,
+            ProviderConfig::AuthKit { .. } | ProviderConfig::Dev { .. } => &[],
+        };
+        let hosts = std::iter::once(issuer)
+            .chain(urls.iter().map(|(_, url)| *url))
+            .filter_map(domains::url_host)
+            .chain(configured_domains.iter().cloned());
+
+        for host in hosts {
+            if !outbound_hosts
+                .iter()
+                .any(|outbound| domains::outbound_host_covers(outbound, &host))
+            {
+                anyhow::bail!(
+                    "'{host}' is not covered by auth_allowed_outbound_hosts; \
+                    add \"https://{host}\" to the component's allowed_outbound_hosts"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a public or resource URL: absolute HTTP(S), no fragment (RFC 8707, section 2)
+    fn validate_public_url(variable: &str, url: &str) -> Result<String> {
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            anyhow::bail!("{variable} must be an absolute http(s) URL: {url}");
+        }
+        if url.contains('#') {
+            anyhow::bail!("{variable} must not contain a fragment: {url}");
+        }
+        Ok(url.trim_end_matches('/').to_string())
+    }
+
+    /// Load dynamic client registration settings from variables
+    fn load_registration_config() -> Result<RegistrationConfig> {
+        let mode = variables::get("auth_registration_mode").unwrap_or_default();
+
+        match mode.trim() {
+            "" | "disabled" => Ok(RegistrationConfig::Disabled),
+            "proxy" => {
+                let endpoint = variables::get("auth_registration_endpoint")
+                    .ok()
+                    .filter(|s| !s.is_empty())
+                    .map(Self::ensure_https_url)
+                    .transpose()?;
+                Ok(RegistrationConfig::Proxy { endpoint })
+            }
+            other => anyhow::bail!(
+                "Unknown auth_registration_mode: {other}. Expected 'disabled' or 'proxy'"
+            ),
+        }
+    }
+
+    /// Load API key settings from variables (inline hashed keys and/or a key-value store)
+    fn load_api_key_config() -> Result<Option<ApiKeyConfig>> {
+        let keys = variables::get("auth_api_keys")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| serde_json::from_str::<Vec<ApiKeyEntry>>(&s))
+            .transpose()
+            .context("auth_api_keys must be a JSON array of API key entries")?
+            .unwrap_or_default();
+
+        Self::validate_api_keys(&keys)?;
+
+        let store = variables::get("auth_api_key_store")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let config = ApiKeyConfig { keys, store };
+        Ok(config.is_enabled().then_some(config))
+    }
+
+    /// Check that API keys are stored as SHA-256 hashes
+    fn validate_api_keys(keys: &[ApiKeyEntry]) -> Result<()> {
+        if let Some(entry) = keys.iter().find(|entry| {
+            entry.hash.len() != 64 || !entry.hash.chars().all(|c| c.is_ascii_hexdigit())
+        }) {
+            anyhow::bail!(
+                "API key for '{}' must be stored as a hex-encoded SHA-256 hash",
+                entry.id
+            );
+        }
+        Ok(())
+    }
+
     /// Ensure URL uses HTTPS protocol. Adds https:// if no protocol specified.
     /// Returns error if http:// is explicitly used.
     fn ensure_https_url(url: String) -> Result<String> {
@@ -90,6 +889,10 @@ impl GatewayConfig {
 
     /// Load provider configuration from variables
     fn load_provider_config(provider_type: &str) -> Result<ProviderConfig> {
+        if provider_type == "dev" {
+            return Self::load_dev_provider_config();
+        }
+
         let issuer = variables::get("auth_provider_issuer")
             .context("auth_provider_issuer is required when auth_provider_type is set")?;
         let issuer = Self::ensure_https_url(issuer)?;
@@ -98,6 +901,15 @@ impl GatewayConfig {
             .ok()
             .filter(|s| !s.is_empty());
 
+        let allowed_domains = variables::get("auth_provider_allowed_domains")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        let options = Self::load_provider_options()?;
+
         match provider_type {
             "authkit" => {
                 let jwks_uri = variables::get("auth_provider_jwks_uri")
@@ -108,6 +920,7 @@ impl GatewayConfig {
                     issuer,
                     jwks_uri,
                     audience,
+                    options,
                 })
             }
             "oidc" => {
@@ -132,13 +945,6 @@ impl GatewayConfig {
                     .map(Self::ensure_https_url)
                     .transpose()?;
 
-                let allowed_domains = variables::get("auth_provider_allowed_domains")
-                    .unwrap_or_default()
-                    .split(',')
-                    .filter(|s| !s.trim().is_empty())
-                    .map(|s| s.trim().to_string())
-                    .collect();
-
                 Ok(ProviderConfig::Oidc {
                     name,
                     issuer,
@@ -148,57 +954,324 @@ impl GatewayConfig {
                     token_endpoint,
                     userinfo_endpoint,
                     allowed_domains,
+                    options,
+                })
+            }
+            "oidc_discovery" => {
+                let name = variables::get("auth_provider_name")
+                    .context("auth_provider_name is required for OIDC discovery provider")?;
+
+                Ok(ProviderConfig::OidcDiscovery {
+                    name,
+                    issuer,
+                    audience,
+                    allowed_domains,
+                    options,
                 })
             }
             _ => anyhow::bail!(
-                "Unknown auth provider type: {}. Expected 'authkit' or 'oidc'",
-                provider_type
+                "Unknown auth provider type: {provider_type}. Expected 'authkit', 'oidc', 'oidc_discovery' or 'dev'"
             ),
         }
     }
 
-    /// Build provider registry from configuration
-    pub fn build_registry(&self) -> ProviderRegistry {
+    /// Load the development provider, which is refused unless `auth_dev_mode` is set
+    fn load_dev_provider_config() -> Result<ProviderConfig> {
+        if !bool_variable("auth_dev_mode") {
+            anyhow::bail!(
+                "The dev provider mints its own tokens and requires auth_dev_mode = true"
+            );
+        }
+
+        let optional = |name: &str| variables::get(name).ok().filter(|s| !s.trim().is_empty());
+        let claims = optional("auth_dev_claims")
+            .map(|s| serde_json::from_str::<Map<String, Value>>(&s))
+            .transpose()
+            .context("auth_dev_claims must be a JSON object")?
+            .unwrap_or_default();
+
+        ProviderConfig::Dev {
+            dev_mode: true,
+            issuer: optional("auth_provider_issuer").unwrap_or_else(default_dev_issuer),
+            audience: optional("auth_provider_audience"),
+            signing_seed: optional("auth_dev_signing_seed"),
+            subject: optional("auth_dev_subject"),
+            claims,
+            options: Self::load_provider_options()?,
+        }
+        .normalized()
+    }
+
+    /// Load the settings shared by all provider types from variables
+    fn load_provider_options() -> Result<ProviderOptions> {
+        let client_id = variables::get("auth_provider_introspection_client_id")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        // Introspection is enabled by configuring client credentials
+        let introspection = client_id
+            .map(|client_id| -> Result<IntrospectionConfig> {
+                let client_secret = variables::get("auth_provider_introspection_client_secret")
+                    .context(
+                        "auth_provider_introspection_client_secret is required when \
+                        auth_provider_introspection_client_id is set",
+                    )?;
+                let endpoint = variables::get("auth_provider_introspection_endpoint")
+                    .ok()
+                    .filter(|s| !s.is_empty())
+                    .map(Self::ensure_https_url)
+                    .transpose()?;
+
+                Ok(IntrospectionConfig {
+                    endpoint,
+                    client_id,
+                    client_secret,
+                })
+            })
+            .transpose()?;
+
+        // Claim mappings are a JSON object with `name`, `tenant`, `roles`, `groups` and
+        // `attributes`
+        let claim_mappings = variables::get("auth_provider_claim_mappings")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| serde_json::from_str::<ClaimMappings>(&s))
+            .transpose()
+            .context("auth_provider_claim_mappings must be a JSON object of claim paths")?
+            .unwrap_or_default();
+        claim_mappings
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_provider_claim_mappings: {e}"))?;
+
+        let validation = Self::load_validation_policy()?;
+        let token_exchange = Self::load_token_exchange_config()?;
+
+        Ok(ProviderOptions {
+            introspection,
+            claim_mappings,
+            validation,
+            token_exchange,
+        })
+    }
+
+    /// Load the provider's token exchange settings from variables; enabled by a client ID
+    fn load_token_exchange_config() -> Result<Option<TokenExchangeConfig>> {
+        let Some(client_id) = variables::get("auth_provider_token_exchange_client_id")
+            .ok()
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let config = TokenExchangeConfig {
+            endpoint: variables::get("auth_provider_token_exchange_endpoint")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(Self::ensure_https_url)
+                .transpose()?,
+            client_id,
+            client_secret: variables::get("auth_provider_token_exchange_client_secret").context(
+                "auth_provider_token_exchange_client_secret is required when \
+                auth_provider_token_exchange_client_id is set",
+            )?,
+            // Tools are a JSON object of tool name to `audience` and optional `scope`
+            tools: variables::get("auth_provider_token_exchange_tools")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| serde_json::from_str(&s))
+                .transpose()
+                .context("auth_provider_token_exchange_tools must be a JSON object of tools")?
+                .unwrap_or_default(),
+        };
+
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_provider_token_exchange: {e}"))?;
+
+        Ok(Some(config))
+    }
+
+    /// Load the provider's token validation policy from variables
+    fn load_validation_policy() -> Result<ValidationPolicy> {
+        let seconds = |name: &str| -> Result<Option<u64>> {
+            variables::get(name)
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.trim().parse::<u64>())
+                .transpose()
+                .with_context(|| format!("{name} must be a number of seconds"))
+        };
+
+        let defaults = ValidationPolicy::default();
+        let policy = ValidationPolicy {
+            leeway: seconds("auth_provider_leeway")?.unwrap_or(defaults.leeway),
+            required_claims: variables::get("auth_provider_required_claims")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| serde_json::from_str(&s))
+                .transpose()
+                .context("auth_provider_required_claims must be a JSON object of claim values")?
+                .unwrap_or_default(),
+            max_age: seconds("auth_provider_max_token_age")?,
+            authorized_clients: list_variable("auth_provider_authorized_clients")
+                .unwrap_or_default(),
+        };
+
+        policy
+            .validate()
+            .map_err(|e| anyhow::anyhow!("auth_provider_required_claims: {e}"))?;
+
+        Ok(policy)
+    }
+
+    /// Build provider registry from configuration.
+    ///
+    /// Discovery-based providers fetch (or reuse the cached) discovery document here. A provider
+    /// that cannot be built is skipped, unless no provider can be built at all.
+    pub async fn build_registry(&self) -> Result<ProviderRegistry> {
         let mut registry = ProviderRegistry::new();
+        let mut last_error = None;
 
-        if let Some(provider_config) = &self.provider {
-            match provider_config {
-                ProviderConfig::AuthKit {
-                    issuer,
-                    jwks_uri,
-                    audience,
-                } => {
-                    let provider =
-                        AuthKitProvider::new(issuer.clone(), jwks_uri.clone(), audience.clone());
-                    registry.add_provider(Box::new(provider));
-                }
-                ProviderConfig::Oidc {
-                    name,
-                    issuer,
-                    jwks_uri,
-                    audience,
-                    authorization_endpoint,
-                    token_endpoint,
-                    userinfo_endpoint,
-                    allowed_domains,
-                } => {
-                    let config = OidcProviderConfig {
-                        name: name.clone(),
-                        issuer: issuer.clone(),
-                        jwks_uri: jwks_uri.clone(),
-                        audience: audience.clone(),
-                        authorization_endpoint: authorization_endpoint.clone(),
-                        token_endpoint: token_endpoint.clone(),
-                        userinfo_endpoint: userinfo_endpoint.clone(),
-                        allowed_domains: allowed_domains.clone(),
-                    };
-                    let provider = OidcProvider::new(config);
-                    registry.add_provider(Box::new(provider));
+        for provider_config in &self.providers {
+            // One unreachable issuer must not take down the other providers
+            match Self::build_provider(provider_config).await {
+                Ok(provider) => registry.add_provider(provider),
+                Err(e) => {
+                    eprintln!("Skipping provider {}: {e:#}", provider_config.issuer());
+                    last_error = Some(e);
                 }
             }
         }
 
-        registry
+        match last_error {
+            Some(e) if registry.providers().is_empty() => Err(e),
+            _ => Ok(registry),
+        }
+    }
+
+    /// Build a single provider, fetching its discovery document if needed
+    async fn build_provider(provider_config: &ProviderConfig) -> Result<Box<dyn AuthProvider>> {
+        let provider: Box<dyn AuthProvider> = match provider_config {
+            ProviderConfig::AuthKit {
+                issuer,
+                jwks_uri,
+                audience,
+                options,
+            } => Box::new(
+                AuthKitProvider::new(issuer.clone(), jwks_uri.clone(), audience.clone())
+                    .with_options(options.clone()),
+            ),
+            ProviderConfig::Oidc {
+                name,
+                issuer,
+                jwks_uri,
+                audience,
+                authorization_endpoint,
+                token_endpoint,
+                userinfo_endpoint,
+                allowed_domains,
+                options,
+            } => {
+                let config = OidcProviderConfig {
+                    name: name.clone(),
+                    issuer: issuer.clone(),
+                    jwks_uri: jwks_uri.clone(),
+                    audience: audience.clone(),
+                    authorization_endpoint: authorization_endpoint.clone(),
+                    token_endpoint: token_endpoint.clone(),
+                    userinfo_endpoint: userinfo_endpoint.clone(),
+                    revocation_endpoint: None,
+                    introspection_endpoint: None,
+                    registration_endpoint: None,
+                    allowed_domains: allowed_domains.clone(),
+                };
+                Box::new(OidcProvider::new(config).with_options(options.clone()))
+            }
+            ProviderConfig::OidcDiscovery {
+                name,
+                issuer,
+                audience,
+                allowed_domains,
+                options,
+            } => {
+                let config =
+                    Self::discover_provider(name, issuer, audience.as_deref(), allowed_domains)
+                        .await?;
+                Box::new(OidcProvider::new(config).with_options(options.clone()))
+            }
+            ProviderConfig::Dev {
+                issuer,
+                audience,
+                signing_seed,
+                subject,
+                claims,
+                options,
+                ..
+            } => Box::new(
+                DevProvider::new(issuer, audience.clone(), signing_seed.as_deref())
+                    .map_err(anyhow::Error::msg)?
+                    .with_identity(subject.clone(), claims.clone())
+                    .with_options(options.clone()),
+            ),
+        };
+
+        Ok(provider)
+    }
+
+    /// Build an OIDC provider from the issuer's discovery document
+    async fn discover_provider(
+        name: &str,
+        issuer: &str,
+        audience: Option<&str>,
+        allowed_domains: &[String],
+    ) -> Result<OidcProviderConfig> {
+        let document = discovery::fetch_openid_configuration(issuer)
+            .await
+            .with_context(|| format!("OIDC discovery failed for issuer {issuer}"))?;
+
+        // Discovered endpoints are held to the same HTTPS rule as configured ones
+        let ensure_https = |url: String| -> Result<String> {
+            if url.starts_with("https://") {
+                Ok(url)
+            } else {
+                anyhow::bail!("Discovered endpoint must use HTTPS: {url}")
+            }
+        };
+
+        // Endpoints the gateway fetches must also be inside the allowed domains
+        let domains: Vec<&str> = allowed_domains.iter().map(String::as_str).collect();
+        let ensure_allowed = |url: String| -> Result<String> {
+            let url = ensure_https(url)?;
+            if domains::is_url_allowed(&url, issuer, &domains) {
+                Ok(url)
+            } else {
+                anyhow::bail!(
+                    "Discovered endpoint is outside the allowed domains of provider \
+                    '{name}': {url}"
+                )
+            }
+        };
+
+        Ok(OidcProviderConfig {
+            name: name.to_string(),
+            // Tokens carry the issuer exactly as the document spells it
+            issuer: document.issuer,
+            jwks_uri: ensure_allowed(document.jwks_uri)?,
+            audience: audience.map(String::from),
+            authorization_endpoint: ensure_https(document.authorization_endpoint)?,
+            token_endpoint: ensure_https(document.token_endpoint)?,
+            userinfo_endpoint: document.userinfo_endpoint.map(ensure_https).transpose()?,
+            revocation_endpoint: document.revocation_endpoint.map(ensure_https).transpose()?,
+            introspection_endpoint: document
+                .introspection_endpoint
+                .map(ensure_allowed)
+                .transpose()?,
+            registration_endpoint: document
+                .registration_endpoint
+                .map(ensure_allowed)
+                .transpose()?,
+            allowed_domains: allowed_domains.to_vec(),
+        })
     }
 }
 
@@ -212,6 +1285,7 @@ mod tests {
             issuer: "https://example.authkit.app".to_string(),
             jwks_uri: None,
             audience: Some("my-api".to_string()),
+            options: ProviderOptions::default(),
         };
 
         // Test serialization
@@ -231,6 +1305,7 @@ mod tests {
             token_endpoint: "https://example.auth0.com/oauth/token".to_string(),
             userinfo_endpoint: None,
             allowed_domains: vec!["*.auth0.com".to_string()],
+            options: ProviderOptions::default(),
         };
 
         // Test serialization
@@ -239,21 +1314,178 @@ mod tests {
         assert!(json.contains("auth0"));
     }
 
+    #[test]
+    fn test_oidc_discovery_provider_config() {
+        let provider: ProviderConfig = serde_json::from_value(serde_json::json!({
+            "type": "oidc_discovery",
+            "name": "keycloak",
+            "issuer": "https://login.example.com/realms/mcp"
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            provider,
+            ProviderConfig::OidcDiscovery {
+                ref name,
+                ref issuer,
+                audience: None,
+                ref allowed_domains,
+                ..
+            } if name == "keycloak"
+                && issuer == "https://login.example.com/realms/mcp"
+                && allowed_domains.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_discovered_endpoints_are_checked() {
+        let document =
+            |issuer: &str, jwks_uri: &str, token_endpoint: &str| discovery::OpenIdConfiguration {
+                issuer: issuer.to_string(),
+                authorization_endpoint: format!("{issuer}/authorize"),
+                token_endpoint: token_endpoint.to_string(),
+                jwks_uri: jwks_uri.to_string(),
+                userinfo_endpoint: None,
+                revocation_endpoint: None,
+                introspection_endpoint: None,
+                registration_endpoint: None,
+            };
+        let registry = |issuer: &str| {
+            let config = GatewayConfig::from_structured(&format!(
+                r#"{{"providers": [{{"type": "oidc_discovery", "name": "idp", "issuer": "{issuer}"}}]}}"#
+            ))
+            .unwrap();
+            futures::executor::block_on(config.build_registry())
+        };
+
+        futures::executor::block_on(async {
+            discovery::cache_openid_configuration(document(
+                "https://good.example.com",
+                "https://good.example.com/jwks",
+                "https://good.example.com/token",
+            ))
+            .await;
+            discovery::cache_openid_configuration(document(
+                "https://http.example.com",
+                "https://http.example.com/jwks",
+                "http://http.example.com/token",
+            ))
+            .await;
+            discovery::cache_openid_configuration(document(
+                "https://outside.example.com",
+                "https://keys.attacker.example/jwks",
+                "https://outside.example.com/token",
+            ))
+            .await;
+        });
+
+        let error = |issuer: &str| registry(issuer).err().map(|e| format!("{e:#}"));
+        assert!(error("https://good.example.com").is_none());
+        assert!(error("https://http.example.com").is_some_and(|e| e.contains("must use HTTPS")));
+        assert!(error("https://outside.example.com")
+            .is_some_and(|e| e.contains("outside the allowed domains")));
+    }
+
+    #[test]
+    fn test_provider_introspection_config() {
+        let provider: ProviderConfig = serde_json::from_value(serde_json::json!({
+            "type": "authkit",
+            "issuer": "https://example.authkit.app",
+            "introspection": {
+                "client_id": "gateway",
+                "client_secret": "secret"
+            }
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            provider,
+            ProviderConfig::AuthKit { ref options, .. }
+                if options.introspection.as_ref().is_some_and(|introspection| {
+                    introspection.client_id == "gateway" && introspection.endpoint.is_none()
+                })
+        ));
+    }
+
+    #[test]
+    fn test_dev_provider_requires_dev_mode() {
+        let raw = r#"{"providers": [{"type": "dev", "audience": "mcp-api"}]}"#;
+        let error = GatewayConfig::from_structured(raw).unwrap_err();
+        assert!(error.to_string().contains("dev_mode"));
+
+        let raw = r#"{"providers": [{"type": "dev", "dev_mode": true, "audience": "mcp-api"}]}"#;
+        let config = GatewayConfig::from_structured(raw).unwrap();
+        assert_eq!(config.providers[0].issuer(), "http://localhost:3000");
+
+        let raw = r#"{"providers": [{"type": "dev", "dev_mode": true, "signing_seed": "abc"}]}"#;
+        assert!(GatewayConfig::from_structured(raw).is_err());
+    }
+
+    #[test]
+    fn test_backchannel_logout_requires_audience() {
+        let revocation = RevocationConfig {
+            store: Some("default".to_string()),
+            backchannel_logout: true,
+            ..RevocationConfig::default()
+        };
+        let provider = |audience: Option<&str>| ProviderConfig::AuthKit {
+            issuer: "https://example.authkit.app".to_string(),
+            jwks_uri: None,
+            audience: audience.map(str::to_string),
+            options: ProviderOptions::default(),
+        };
+
+        let with_audience = [provider(Some("client_123"))];
+        assert!(
+            GatewayConfig::validate_logout_audiences(&revocation, with_audience.iter()).is_ok()
+        );
+
+        let without_audience = [provider(Some("client_123")), provider(None)];
+        assert!(
+            GatewayConfig::validate_logout_audiences(&revocation, without_audience.iter()).is_err()
+        );
+        assert!(GatewayConfig::validate_logout_audiences(
+            &RevocationConfig::default(),
+            without_audience.iter()
+        )
+        .is_ok());
+    }
+
     #[test]
     fn test_gateway_config_with_provider() {
         let config = GatewayConfig {
             mcp_gateway_url: "http://gateway.internal".to_string(),
             trace_id_header: "X-Request-ID".to_string(),
             enabled: true,
-            provider: Some(ProviderConfig::AuthKit {
+            providers: vec![ProviderConfig::AuthKit {
                 issuer: "https://example.authkit.app".to_string(),
                 jwks_uri: None,
                 audience: None,
-            }),
+                options: ProviderOptions::default(),
+            }],
+            scope_policy: ScopePolicy::default(),
+            anonymous: AnonymousPolicy::default(),
+            api_keys: None,
+            registration: RegistrationConfig::default(),
+            metadata_overrides: Map::new(),
+            resource_url: None,
+            resource_audience: false,
+            public_url: None,
+            mcp_path: default_mcp_path(),
+            trusted_proxies: Vec::new(),
+            cors: CorsConfig::default(),
+            headers: HeaderPolicy::default(),
+            require_tenant: false,
+            tenant_secret: None,
+            revocation: RevocationConfig::default(),
+            token_cache_ttl: 0,
+            dpop: DpopConfig::default(),
+            allowed_origins: Vec::new(),
+            allowed_outbound_hosts: Vec::new(),
         };
 
         assert!(config.enabled);
-        assert!(config.provider.is_some());
+        assert_eq!(config.providers.len(), 1);
     }
 
     #[test]
@@ -262,11 +1494,30 @@ mod tests {
             mcp_gateway_url: "http://gateway.internal".to_string(),
             trace_id_header: "X-Request-ID".to_string(),
             enabled: false,
-            provider: None,
+            providers: Vec::new(),
+            scope_policy: ScopePolicy::default(),
+            anonymous: AnonymousPolicy::default(),
+            api_keys: None,
+            registration: RegistrationConfig::default(),
+            metadata_overrides: Map::new(),
+            resource_url: None,
+            resource_audience: false,
+            public_url: None,
+            mcp_path: default_mcp_path(),
+            trusted_proxies: Vec::new(),
+            cors: CorsConfig::default(),
+            headers: HeaderPolicy::default(),
+            require_tenant: false,
+            tenant_secret: None,
+            revocation: RevocationConfig::default(),
+            token_cache_ttl: 0,
+            dpop: DpopConfig::default(),
+            allowed_origins: Vec::new(),
+            allowed_outbound_hosts: Vec::new(),
         };
 
         assert!(!config.enabled);
-        assert!(config.provider.is_none());
+        assert!(config.providers.is_empty());
     }
 
     #[test]
@@ -308,4 +1559,201 @@ mod tests {
         let result = GatewayConfig::ensure_https_url("http://localhost:8080".to_string());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_validate_public_url() {
+        let validate = |url| GatewayConfig::validate_public_url("auth_public_url", url);
+        assert_eq!(
+            validate("https://mcp.example.com/mcp/").unwrap(),
+            "https://mcp.example.com/mcp"
+        );
+        assert!(validate("http://localhost:3000").is_ok());
+        assert!(validate("mcp.example.com/mcp").is_err());
+        assert!(validate("https://mcp.example.com/mcp#a").is_err());
+    }
+
+    #[test]
+    fn test_validate_provider_domains() {
+        let provider = ProviderConfig::Oidc {
+            name: "auth0".to_string(),
+            issuer: "https://example.auth0.com".to_string(),
+            jwks_uri: "https://keys.example.net/jwks.json".to_string(),
+            audience: None,
+            authorization_endpoint: "https://example.auth0.com/authorize".to_string(),
+            token_endpoint: "https://example.auth0.com/oauth/token".to_string(),
+            userinfo_endpoint: None,
+            allowed_domains: vec!["*.example.net".to_string()],
+            options: ProviderOptions::default(),
+        };
+        let disabled = RegistrationConfig::Disabled;
+
+        assert!(GatewayConfig::validate_provider_domains(&provider, &disabled, &[]).is_ok());
+
+        let outbound = |hosts: &[&str]| -> Vec<String> {
+            hosts.iter().map(|host| (*host).to_string()).collect()
+        };
+        assert!(GatewayConfig::validate_provider_domains(
+            &provider,
+            &disabled,
+            &outbound(&["https://*.auth0.com", "https://*.example.net"])
+        )
+        .is_ok());
+        assert!(GatewayConfig::validate_provider_domains(
+            &provider,
+            &disabled,
+            &outbound(&["https://*.auth0.com", "https://keys.example.net"])
+        )
+        .is_err());
+
+        let proxy = RegistrationConfig::Proxy {
+            endpoint: Some("https://evil.com/register".to_string()),
+        };
+        assert!(GatewayConfig::validate_provider_domains(&provider, &proxy, &[]).is_err());
+
+        let unlisted = ProviderConfig::Oidc {
+            name: "auth0".to_string(),
+            issuer: "https://example.auth0.com".to_string(),
+            jwks_uri: "https://keys.example.net/jwks.json".to_string(),
+            audience: None,
+            authorization_endpoint: "https://example.auth0.com/authorize".to_string(),
+            token_endpoint: "https://example.auth0.com/oauth/token".to_string(),
+            userinfo_endpoint: None,
+            allowed_domains: Vec::new(),
+            options: ProviderOptions::default(),
+        };
+        assert!(GatewayConfig::validate_provider_domains(&unlisted, &disabled, &[]).is_err());
+    }
+
+    #[test]
+    fn test_structured_json_config() {
+        let config = GatewayConfig::from_structured(
+            r#"{
+                "trace_id_header": "X-Request-Id",
+                "providers": [
+                    {
+                        "type": "authkit",
+                        "issuer": "example.authkit.app",
+                        "audience": "mcp-api"
+                    },
+                    {
+                        "type": "oidc",
+                        "name": "auth0",
+                        "issuer": "https://example.auth0.com",
+                        "jwks_uri": "https://example.auth0.com/.well-known/jwks.json",
+                        "authorization_endpoint": "https://example.auth0.com/authorize",
+                        "token_endpoint": "https://example.auth0.com/oauth/token",
+                        "audience": "https://api.example.com",
+                        "claim_mappings": { "roles": "https://example.com/roles" },
+                        "validation": {
+                            "max_age": 3600,
+                            "required_claims": { "email_verified": true }
+                        }
+                    }
+                ],
+                "scope_policy": { "methods": { "tools/call": ["mcp:tools"] } },
+                "cors": { "allowed_origins": ["https://app.example.com"] },
+                "headers": { "deny_response": ["Server"] }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(config.enabled);
+        assert_eq!(config.trace_id_header, "X-Request-Id");
+        assert_eq!(config.mcp_gateway_url, default_mcp_gateway_url());
+        assert_eq!(config.mcp_path, "/mcp");
+        assert_eq!(config.providers.len(), 2);
+        assert_eq!(
+            config.providers.first().map(ProviderConfig::issuer),
+            Some("https://example.authkit.app")
+        );
+        assert!(!config.scope_policy.is_empty());
+        assert_eq!(config.cors.allowed_origins, vec!["https://app.example.com"]);
+        assert_eq!(config.headers.deny_response, vec!["Server"]);
+        assert_eq!(
+            config.headers.forward_request,
+            headers::default_forward_request_headers()
+        );
+    }
+
+    #[test]
+    fn test_structured_config_validation() {
+        let provider = |issuer: &str| format!(r#"{{"type": "authkit", "issuer": "{issuer}"}}"#);
+
+        // Unknown sections are typos, not silently ignored settings
+        assert!(GatewayConfig::from_structured(r#"{"provider": []}"#).is_err());
+
+        let http = format!(
+            r#"{{"providers": [{}]}}"#,
+            provider("http://example.authkit.app")
+        );
+        assert!(GatewayConfig::from_structured(&http).is_err());
+
+        let duplicate = format!(
+            r#"{{"providers": [{}, {}]}}"#,
+            provider("https://example.authkit.app"),
+            provider("example.authkit.app")
+        );
+        assert!(GatewayConfig::from_structured(&duplicate).is_err());
+
+        assert!(GatewayConfig::from_structured(r#"{"mcp_path": "mcp"}"#).is_err());
+        // The bound audience must not be derived from the client's Host header
+        assert!(GatewayConfig::from_structured(r#"{"resource_audience": true}"#).is_err());
+        for canonical in [
+            r#"{"resource_audience": true, "resource_url": "https://mcp.example.com/mcp"}"#,
+            r#"{"resource_audience": true, "public_url": "https://mcp.example.com"}"#,
+        ] {
+            assert!(
+                GatewayConfig::from_structured(canonical).is_ok(),
+                "{canonical}"
+            );
+        }
+        assert!(GatewayConfig::from_structured(
+            r#"{"cors": {"allowed_origins": ["*"], "allow_credentials": true}}"#
+        )
+        .is_err());
+        assert!(GatewayConfig::from_structured(
+            r#"{"headers": {"forward_request": ["Accept", "authorization"]}}"#
+        )
+        .is_err());
+        let empty_mapping = r#"{"providers": [{
+            "type": "authkit",
+            "issuer": "https://example.authkit.app",
+            "claim_mappings": { "roles": "" }
+        }]}"#;
+        assert!(GatewayConfig::from_structured(empty_mapping).is_err());
+        let empty_client = r#"{"providers": [{
+            "type": "authkit",
+            "issuer": "https://example.authkit.app",
+            "validation": { "authorized_clients": [" "] }
+        }]}"#;
+        assert!(GatewayConfig::from_structured(empty_client).is_err());
+        assert!(GatewayConfig::from_structured("{").is_err());
+    }
+
+    #[test]
+    fn test_structured_toml_config() {
+        let config = GatewayConfig::from_structured(
+            r#"
+            enabled = true
+            allowed_origins = ["https://app.example.com"]
+
+            [[providers]]
+            type = "oidc_discovery"
+            name = "keycloak"
+            issuer = "https://login.example.com/realms/mcp"
+
+            [providers.introspection]
+            client_id = "gateway"
+            client_secret = "secret"
+
+            [scope_policy.tools]
+            delete_repo = ["repo:admin"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.providers.len(), 1);
+        assert_eq!(config.allowed_origins, vec!["https://app.example.com"]);
+        assert!(!config.scope_policy.is_empty());
+    }
 }
@@ -0,0 +1,191 @@
+use ftl_origin::origin_matches;
+use serde::{Deserialize, Serialize};
+use spin_sdk::http::{Request, Response};
+
+/// Cross-origin resource sharing policy
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CorsConfig {
+    /// Allowed origins: exact origins, `https://*.example.com` patterns, or `*` for any
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+    /// Whether browsers may send credentials (requires explicit origins)
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Request headers browsers may send
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Response headers browser scripts may read
+    #[serde(default = "default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    /// How long browsers may cache preflight results, in seconds
+    #[serde(default = "default_max_age")]
+    pub max_age: u32,
+}
+
+fn default_allowed_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+/// Headers MCP clients send, including the Streamable HTTP session and version headers
+pub fn default_allowed_headers() -> Vec<String> {
+    [
+        "Content-Type",
+        "Authorization",
+        "X-API-Key",
+        "Mcp-Session-Id",
+        "MCP-Protocol-Version",
+        "Last-Event-ID",
+        "traceparent",
+        "tracestate",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Headers MCP clients need to read from responses
+pub fn default_exposed_headers() -> Vec<String> {
+    [
+        "Mcp-Session-Id",
+        "MCP-Protocol-Version",
+        "WWW-Authenticate",
+        "X-Trace-Id",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+const fn default_max_age() -> u32 {
+    86400
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: default_allowed_origins(),
+            allow_credentials: false,
+            allowed_headers: default_allowed_headers(),
+            exposed_headers: default_exposed_headers(),
+            max_age: default_max_age(),
+        }
+    }
+}
+
+/// The request's `Origin` header
+pub fn request_origin(req: &Request) -> Option<&str> {
+    req.headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("origin"))
+        .and_then(|(_, value)| value.as_str())
+}
+
+impl CorsConfig {
+    /// Whether any origin is allowed without reflecting it
+    fn allows_any_origin(&self) -> bool {
+        !self.allow_credentials && self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Value for `Access-Control-Allow-Origin`, if the origin is allowed
+    pub fn allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+        if self.allows_any_origin() {
+            return Some("*".to_string());
+        }
+
+        let origin = origin?;
+        self.allowed_origins
+            .iter()
+            .any(|pattern| origin_matches(pattern, origin))
+            .then(|| origin.to_string())
+    }
+
+    /// CORS headers for a response to the given origin
+    pub fn response_headers(&self, origin: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        // Responses differ per origin unless every origin gets `*`
+        if !self.allows_any_origin() {
+            headers.push(("Vary", "Origin".to_string()));
+        }
+
+        let Some(allowed) = self.allowed_origin(origin) else {
+            return headers;
+        };
+
+        headers.push(("Access-Control-Allow-Origin", allowed));
+        if self.allow_credentials {
+            headers.push(("Access-Control-Allow-Credentials", "true".to_string()));
+        }
+        if !self.exposed_headers.is_empty() {
+            headers.push((
+                "Access-Control-Expose-Headers",
+                self.exposed_headers.join(", "),
+            ));
+        }
+
+        headers
+    }
+
+    /// Build the response to a CORS preflight request
+    pub fn preflight_response(&self, origin: Option<&str>) -> Response {
+        let mut response = Response::builder().status(204).build();
+
+        if self.allowed_origin(origin).is_some() {
+            response.set_header("Access-Control-Allow-Methods", "GET, POST, DELETE, OPTIONS");
+            response.set_header(
+                "Access-Control-Allow-Headers",
+                self.allowed_headers.join(", "),
+            );
+            response.set_header("Access-Control-Max-Age", self.max_age.to_string());
+        }
+        for (name, value) in self.response_headers(origin) {
+            response.set_header(name, value);
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(origins: &[&str], allow_credentials: bool) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| (*o).to_string()).collect(),
+            allow_credentials,
+            ..CorsConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_any_origin() {
+        let cors = CorsConfig::default();
+        assert_eq!(cors.allowed_origin(None), Some("*".to_string()));
+        assert_eq!(
+            cors.allowed_origin(Some("https://app.example.com")),
+            Some("*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_origin_allowlist() {
+        let cors = allowlist(&["https://app.example.com", "https://*.example.org"], true);
+        assert_eq!(
+            cors.allowed_origin(Some("https://app.example.com")),
+            Some("https://app.example.com".to_string())
+        );
+        assert_eq!(
+            cors.allowed_origin(Some("https://ide.example.org")),
+            Some("https://ide.example.org".to_string())
+        );
+        assert!(cors.allowed_origin(Some("https://example.org")).is_none());
+        assert!(cors
+            .allowed_origin(Some("http://ide.example.org"))
+            .is_none());
+        assert!(cors
+            .allowed_origin(Some("https://evil-example.org"))
+            .is_none());
+        assert!(cors.allowed_origin(Some("https://evil.com")).is_none());
+        assert!(cors.allowed_origin(None).is_none());
+    }
+}
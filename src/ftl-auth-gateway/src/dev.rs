@@ -0,0 +1,586 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use spin_sdk::http::{Method, Request, Response};
+use spin_sdk::key_value::Store;
+use std::fmt::Write;
+
+use crate::{
+    auth::unix_now,
+    introspection::{form_urldecode, form_urlencode},
+    providers::{AuthProvider, DiscoveryMetadata, ProviderOptions},
+    replay,
+};
+
+/// Seed of the built-in development key. It is public, so tokens signed with it prove nothing.
+const DEFAULT_SEED: &[u8; 32] = b"ftl-auth-gateway-dev-signing-key";
+
+/// Key ID of the development signing key
+const KEY_ID: &str = "ftl-dev";
+
+/// Issuer used when none is configured, matching `spin up`'s default listen address
+pub const DEFAULT_ISSUER: &str = "http://localhost:3000";
+
+/// Subject of minted tokens when none is configured or requested
+const DEFAULT_SUBJECT: &str = "dev-user";
+
+/// Lifetime of minted tokens, in seconds
+const TOKEN_TTL: i64 = 3600;
+
+/// Lifetime of authorization codes, in seconds
+const CODE_TTL: i64 = 60;
+
+/// `typ` headers telling access tokens and authorization codes apart, since both are signed
+/// with the same key (RFC 9068 names the access token type)
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+const CODE_TYPE: &str = "dev-code+jwt";
+
+/// Key-value store remembering redeemed authorization codes
+const CODE_STORE: &str = "default";
+
+/// Endpoints the development provider serves from the gateway
+const JWKS_PATH: &str = "/.well-known/jwks.json";
+const AUTHORIZE_PATH: &str = "/dev/authorize";
+const TOKEN_PATH: &str = "/dev/token";
+
+/// Claim of an authorization code holding the client's PKCE challenge
+const CODE_CHALLENGE_CLAIM: &str = "code_challenge";
+
+/// Claims of an authorization code that are not carried over to the access token
+const CODE_ONLY_CLAIMS: &[&str] = &[CODE_CHALLENGE_CLAIM, "jti", "iat", "exp"];
+
+/// The S256 challenge of a PKCE code verifier (RFC 7636, section 4.2)
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Check a code verifier against the challenge its authorization code was issued for
+fn verify_pkce(challenge: Option<&str>, verifier: Option<&str>) -> Result<(), String> {
+    let challenge =
+        challenge.ok_or_else(|| "Authorization code has no PKCE challenge".to_string())?;
+    let verifier = verifier.ok_or_else(|| "code_verifier is required".to_string())?;
+    // Verifiers are 43 to 128 unreserved characters (RFC 7636, section 4.1)
+    let well_formed = (43..=128).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    if well_formed && pkce_challenge(verifier) == challenge {
+        Ok(())
+    } else {
+        Err("code_verifier does not match the code challenge".to_string())
+    }
+}
+
+/// A random token ID
+fn new_jti() -> Result<String, String> {
+    let mut jti = [0u8; 16];
+    getrandom::getrandom(&mut jti).map_err(|e| format!("Failed to generate jti: {e}"))?;
+    Ok(URL_SAFE_NO_PAD.encode(jti))
+}
+
+/// Decode a base64url signing seed, which must be 32 bytes
+fn decode_seed(seed: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(seed.trim().trim_end_matches('='))
+        .ok()
+        .filter(|bytes| bytes.len() == 32)
+        .ok_or_else(|| "signing_seed must be 32 bytes, base64url-encoded".to_string())
+}
+
+/// Check a configured signing seed
+pub fn validate_seed(seed: &str) -> Result<(), String> {
+    decode_seed(seed).map(|_| ())
+}
+
+/// Local identity provider for development: mints `EdDSA` tokens and verifies them without
+/// any network access
+#[derive(Debug)]
+pub struct DevProvider {
+    issuer: String,
+    jwks_uri: String,
+    audience: Option<String>,
+    subject: String,
+    claims: Map<String, Value>,
+    key: Ed25519KeyPair,
+    options: ProviderOptions,
+}
+
+impl DevProvider {
+    pub fn new(
+        issuer: &str,
+        audience: Option<String>,
+        signing_seed: Option<&str>,
+    ) -> Result<Self, String> {
+        let seed = signing_seed.map_or_else(|| Ok(DEFAULT_SEED.to_vec()), decode_seed)?;
+        let key = Ed25519KeyPair::from_seed_unchecked(&seed)
+            .map_err(|e| format!("Invalid development signing key: {e}"))?;
+        let issuer = issuer.trim_end_matches('/').to_string();
+
+        Ok(Self {
+            jwks_uri: format!("{issuer}{JWKS_PATH}"),
+            issuer,
+            audience,
+            subject: DEFAULT_SUBJECT.to_string(),
+            claims: Map::new(),
+            key,
+            options: ProviderOptions::default(),
+        })
+    }
+
+    /// Set the identity minted tokens carry unless the request names another
+    pub fn with_identity(mut self, subject: Option<String>, claims: Map<String, Value>) -> Self {
+        if let Some(subject) = subject {
+            self.subject = subject;
+        }
+        self.claims = claims;
+        self
+    }
+
+    pub fn with_options(mut self, options: ProviderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Public key in JWK form
+    fn jwks(&self) -> Value {
+        serde_json::json!({
+            "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": KEY_ID,
+                "x": URL_SAFE_NO_PAD.encode(self.key.public_key().as_ref())
+            }]
+        })
+    }
+
+    /// Sign claims as a JWT of the given type
+    fn sign(&self, typ: &str, claims: Map<String, Value>) -> String {
+        let header = serde_json::json!({ "alg": "EdDSA", "typ": typ, "kid": KEY_ID });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string())
+        );
+        let signature = self.key.sign(signing_input.as_bytes());
+        format!(
+            "{signing_input}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        )
+    }
+
+    /// Mint a signed access token. Requested claims override the configured identity, but the
+    /// issuer and lifetime are always the provider's own.
+    pub fn mint(&self, requested: Map<String, Value>) -> Result<String, String> {
+        let now = unix_now();
+
+        let mut claims = Map::new();
+        claims.insert("sub".to_string(), Value::from(self.subject.clone()));
+        if let Some(audience) = self.audience.as_deref() {
+            let audiences: Vec<&str> = audience.split(',').map(str::trim).collect();
+            claims.insert("aud".to_string(), serde_json::json!(audiences));
+        }
+        claims.insert("jti".to_string(), Value::from(new_jti()?));
+        claims.extend(self.claims.clone());
+        claims.extend(requested);
+        claims.insert("iss".to_string(), Value::from(self.issuer.clone()));
+        claims.insert("iat".to_string(), Value::from(now));
+        claims.insert("exp".to_string(), Value::from(now + TOKEN_TTL));
+
+        Ok(self.sign(ACCESS_TOKEN_TYPE, claims))
+    }
+
+    /// Mint a short-lived authorization code carrying the claims of the token it is exchanged
+    /// for
+    fn mint_code(&self, mut claims: Map<String, Value>) -> Result<String, String> {
+        let now = unix_now();
+        claims.insert("jti".to_string(), Value::from(new_jti()?));
+        claims.insert("iss".to_string(), Value::from(self.issuer.clone()));
+        claims.insert("iat".to_string(), Value::from(now));
+        claims.insert("exp".to_string(), Value::from(now + CODE_TTL));
+
+        Ok(self.sign(CODE_TYPE, claims))
+    }
+
+    /// Check a token of the given type was minted by this provider and has not expired,
+    /// returning its claims
+    fn verify_own(&self, token: &str, typ: &str) -> Result<Map<String, Value>, String> {
+        let header =
+            jsonwebtoken::decode_header(token).map_err(|e| format!("Invalid token: {e}"))?;
+        if !header
+            .typ
+            .as_deref()
+            .is_some_and(|actual| actual.eq_ignore_ascii_case(typ))
+        {
+            return Err(format!("Invalid token: expected typ {typ}"));
+        }
+
+        let key = self
+            .decoding_key(KEY_ID)
+            .ok_or_else(|| "Invalid development signing key".to_string())?;
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.set_issuer(&[&self.issuer]);
+        validation.validate_aud = false;
+        decode::<Map<String, Value>>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| format!("Invalid token: {e}"))
+    }
+
+    /// Exchange an authorization code for an access token with the same identity, once the
+    /// PKCE verifier matches the code's challenge. Each code is redeemed at most once.
+    fn exchange_code(&self, code: &str, verifier: Option<&str>) -> Result<String, String> {
+        let mut claims = self.verify_own(code, CODE_TYPE)?;
+        verify_pkce(
+            claims.get(CODE_CHALLENGE_CLAIM).and_then(Value::as_str),
+            verifier,
+        )?;
+
+        let jti = claims
+            .get("jti")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "Authorization code has no jti".to_string())?;
+        let exp = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .unwrap_or_default();
+        let first_use = Store::open(CODE_STORE)
+            .and_then(|store| replay::record_once(&store, "dev-code", jti, exp, unix_now()))
+            .map_err(|e| format!("Authorization code store '{CODE_STORE}' is unavailable: {e}"))?;
+        if !first_use {
+            return Err("Authorization code has already been used".to_string());
+        }
+
+        claims.retain(|claim, _| !CODE_ONLY_CLAIMS.contains(&claim.as_str()));
+        self.mint(claims)
+    }
+
+    /// Authorization endpoint: signs in as the configured identity without prompting and
+    /// returns a signed code carrying the identity and the client's PKCE challenge
+    fn authorize(&self, req: &Request) -> Response {
+        let params = form_urldecode(req.query());
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+                .filter(|value| !value.is_empty())
+        };
+
+        let Some(redirect_uri) = param("redirect_uri")
+            .filter(|uri| uri.starts_with("http://") || uri.starts_with("https://"))
+        else {
+            return token_error(400, "invalid_request", "A valid redirect_uri is required");
+        };
+        if param("response_type") != Some("code") {
+            return token_error(400, "unsupported_response_type", "Only code is supported");
+        }
+        // PKCE is required, and only with S256 as advertised in the metadata
+        let Some(code_challenge) = param("code_challenge") else {
+            return token_error(400, "invalid_request", "code_challenge is required");
+        };
+        if param("code_challenge_method") != Some("S256") {
+            return token_error(
+                400,
+                "invalid_request",
+                "Only the S256 code_challenge_method is supported",
+            );
+        }
+
+        let mut claims = Map::new();
+        claims.insert(
+            CODE_CHALLENGE_CLAIM.to_string(),
+            Value::from(code_challenge),
+        );
+        for (claim, name) in [
+            ("scope", "scope"),
+            ("client_id", "client_id"),
+            ("aud", "resource"),
+        ] {
+            if let Some(value) = param(name) {
+                claims.insert(claim.to_string(), Value::from(value));
+            }
+        }
+        let code = match self.mint_code(claims) {
+            Ok(code) => code,
+            Err(e) => return token_error(500, "server_error", &e),
+        };
+
+        let separator = if redirect_uri.contains('?') { '&' } else { '?' };
+        let mut location = format!("{redirect_uri}{separator}code={}", form_urlencode(&code));
+        if let Some(state) = param("state") {
+            let _ = write!(location, "&state={}", form_urlencode(state));
+        }
+
+        Response::builder()
+            .status(302)
+            .header("Location", location)
+            .header("Cache-Control", "no-store")
+            .build()
+    }
+
+    /// Token endpoint: exchanges authorization codes, issues client credentials tokens, and
+    /// mints a token from a JSON object of claims
+    fn token(&self, req: &Request) -> Response {
+        if *req.method() != Method::Post {
+            return Response::builder()
+                .status(405)
+                .header("Allow", "POST")
+                .body("Method not allowed")
+                .build();
+        }
+
+        let body = String::from_utf8_lossy(req.body());
+        let minted = if body.trim_start().starts_with('{') {
+            match serde_json::from_str::<Map<String, Value>>(&body) {
+                Ok(claims) => self.mint(claims),
+                Err(e) => return token_error(400, "invalid_request", &e.to_string()),
+            }
+        } else {
+            let params = form_urldecode(&body);
+            let param = |name: &str| {
+                params
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+                    .filter(|value| !value.is_empty())
+            };
+
+            match param("grant_type").as_deref() {
+                Some("authorization_code") => {
+                    let code = param("code").unwrap_or_default();
+                    match self.exchange_code(&code, param("code_verifier").as_deref()) {
+                        Ok(token) => Ok(token),
+                        Err(e) => return token_error(400, "invalid_grant", &e),
+                    }
+                }
+                Some("client_credentials") => {
+                    let mut claims = Map::new();
+                    if let Some(client_id) = param("client_id") {
+                        claims.insert("sub".to_string(), Value::from(client_id.clone()));
+                        claims.insert("client_id".to_string(), Value::from(client_id));
+                    }
+                    if let Some(scope) = param("scope") {
+                        claims.insert("scope".to_string(), Value::from(scope));
+                    }
+                    self.mint(claims)
+                }
+                _ => {
+                    return token_error(
+                        400,
+                        "unsupported_grant_type",
+                        "Supported grant types are authorization_code and client_credentials",
+                    )
+                }
+            }
+        };
+
+        match minted {
+            Ok(token) => Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .header("Cache-Control", "no-store")
+                .body(
+                    serde_json::json!({
+                        "access_token": token,
+                        "token_type": "Bearer",
+                        "expires_in": TOKEN_TTL
+                    })
+                    .to_string(),
+                )
+                .build(),
+            Err(e) => token_error(500, "server_error", &e),
+        }
+    }
+}
+
+/// OAuth error response (RFC 6749, section 5.2)
+fn token_error(status: u16, error: &str, description: &str) -> Response {
+    let body = serde_json::json!({
+        "error": error,
+        "error_description": description
+    });
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(body.to_string())
+        .build()
+}
+
+impl AuthProvider for DevProvider {
+    fn jwks_uri(&self) -> &str {
+        &self.jwks_uri
+    }
+
+    fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    fn access_token_type(&self) -> Option<&str> {
+        Some(ACCESS_TOKEN_TYPE)
+    }
+
+    fn audience(&self) -> Option<&str> {
+        self.audience.as_deref()
+    }
+
+    fn allowed_domains(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    fn discovery_metadata(&self, _resource_url: &str) -> DiscoveryMetadata {
+        DiscoveryMetadata {
+            issuer: self.issuer.clone(),
+            authorization_endpoint: format!("{}{AUTHORIZE_PATH}", self.issuer),
+            token_endpoint: format!("{}{TOKEN_PATH}", self.issuer),
+            jwks_uri: self.jwks_uri.clone(),
+            userinfo_endpoint: None,
+            revocation_endpoint: None,
+            introspection_endpoint: None,
+            registration_endpoint: None,
+        }
+    }
+
+    fn options(&self) -> &ProviderOptions {
+        &self.options
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn decoding_key(&self, kid: &str) -> Option<DecodingKey> {
+        if kid != KEY_ID {
+            return None;
+        }
+        DecodingKey::from_ed_components(&URL_SAFE_NO_PAD.encode(self.key.public_key().as_ref()))
+            .ok()
+    }
+
+    fn handle_local_request(&self, path: &str, req: &Request) -> Option<Response> {
+        match path {
+            JWKS_PATH => Some(
+                Response::builder()
+                    .status(200)
+                    .header("Content-Type", "application/json")
+                    .body(self.jwks().to_string())
+                    .build(),
+            ),
+            AUTHORIZE_PATH => Some(self.authorize(req)),
+            TOKEN_PATH => Some(self.token(req)),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> DevProvider {
+        DevProvider::new("http://localhost:3000/", Some("mcp-api".to_string()), None)
+            .unwrap()
+            .with_identity(
+                Some("ada".to_string()),
+                serde_json::json!({ "email": "ada@example.com", "org_id": "org_1" })
+                    .as_object()
+                    .cloned()
+                    .unwrap(),
+            )
+    }
+
+    #[test]
+    fn test_mint_and_verify() {
+        let provider = provider();
+        let mut requested = Map::new();
+        requested.insert("scope".to_string(), Value::from("mcp:tools"));
+        requested.insert("iss".to_string(), Value::from("https://evil.example.com"));
+        let token = provider.mint(requested).unwrap();
+
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::EdDSA);
+        assert_eq!(header.typ.as_deref(), Some(ACCESS_TOKEN_TYPE));
+        let key = provider
+            .decoding_key(header.kid.as_deref().unwrap())
+            .unwrap();
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.set_audience(&["mcp-api"]);
+        let claims = decode::<Value>(&token, &key, &validation).unwrap().claims;
+
+        assert_eq!(claims["iss"], "http://localhost:3000");
+        assert_eq!(claims["sub"], "ada");
+        assert_eq!(claims["org_id"], "org_1");
+        assert_eq!(claims["scope"], "mcp:tools");
+        assert!(provider.verify_own(&token, ACCESS_TOKEN_TYPE).is_ok());
+        assert!(provider.decoding_key("other").is_none());
+    }
+
+    #[test]
+    fn test_signing_seed() {
+        let seed = URL_SAFE_NO_PAD.encode([7u8; 32]);
+        assert!(validate_seed(&seed).is_ok());
+        assert!(validate_seed("too-short").is_err());
+
+        // Tokens signed with another key are not accepted as authorization codes
+        let other = DevProvider::new(DEFAULT_ISSUER, None, Some(&seed)).unwrap();
+        let code = other.mint_code(Map::new()).unwrap();
+        assert!(provider().verify_own(&code, CODE_TYPE).is_err());
+    }
+
+    #[test]
+    fn test_authorization_code_pkce() {
+        let provider = provider();
+        // RFC 7636, appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(
+            pkce_challenge(verifier),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        let mut requested = Map::new();
+        requested.insert(
+            CODE_CHALLENGE_CLAIM.to_string(),
+            Value::from(pkce_challenge(verifier)),
+        );
+        requested.insert("scope".to_string(), Value::from("mcp:tools"));
+        let code = provider.mint_code(requested).unwrap();
+
+        // Codes are short-lived and are not access tokens
+        let code_claims = provider.verify_own(&code, CODE_TYPE).unwrap();
+        assert_eq!(
+            code_claims["exp"].as_i64().unwrap() - code_claims["iat"].as_i64().unwrap(),
+            CODE_TTL
+        );
+        assert!(provider.verify_own(&code, ACCESS_TOKEN_TYPE).is_err());
+
+        let other = "x".repeat(43);
+        assert!(provider.exchange_code(&code, Some(&other)).is_err());
+        assert!(provider.exchange_code(&code, None).is_err());
+
+        let token = provider.exchange_code(&code, Some(verifier)).unwrap();
+        let claims = provider.verify_own(&token, ACCESS_TOKEN_TYPE).unwrap();
+        assert_eq!(claims["sub"], "ada");
+        assert_eq!(claims["scope"], "mcp:tools");
+        assert!(!claims.contains_key(CODE_CHALLENGE_CLAIM));
+
+        // Access tokens are not authorization codes
+        assert!(provider.verify_own(&token, CODE_TYPE).is_err());
+        assert!(provider.exchange_code(&token, Some(verifier)).is_err());
+        assert!(verify_pkce(Some(&pkce_challenge("short")), Some("short")).is_err());
+    }
+
+    #[test]
+    fn test_jwks() {
+        let jwks = provider().jwks();
+        assert_eq!(jwks["keys"][0]["kid"], KEY_ID);
+        assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// `OpenID` Connect discovery document (the fields the gateway relies on)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub revocation_endpoint: Option<String>,
+    #[serde(default)]
+    pub introspection_endpoint: Option<String>,
+    #[serde(default)]
+    pub registration_endpoint: Option<String>,
+}
+
+/// Type alias for the discovery cache entry
+type DiscoveryCacheEntry = (OpenIdConfiguration, std::time::Instant);
+
+/// Type alias for the discovery cache
+type DiscoveryCache = Arc<RwLock<HashMap<String, DiscoveryCacheEntry>>>;
+
+/// Cache for discovery documents, keyed by issuer
+static DISCOVERY_CACHE: Lazy<DiscoveryCache> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Cache duration (15 minutes)
+const CACHE_DURATION: std::time::Duration = std::time::Duration::from_secs(900);
+
+/// Maximum number of issuers to cache (prevent `DoS`)
+const MAX_CACHE_SIZE: usize = 100;
+
+/// Type alias for the authorization server metadata cache entry
+type MetadataCacheEntry = (Map<String, Value>, std::time::Instant);
+
+/// Type alias for the authorization server metadata cache
+type MetadataCache = Arc<RwLock<HashMap<String, MetadataCacheEntry>>>;
+
+/// Cache for raw authorization server metadata documents, keyed by issuer
+static METADATA_CACHE: Lazy<MetadataCache> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Build the well-known discovery URL for an issuer
+pub fn discovery_url(issuer: &str) -> String {
+    format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    )
+}
+
+/// Build the RFC 8414 metadata URL for an issuer.
+///
+/// The well-known segment goes between the host and any issuer path (RFC 8414, section 3.1).
+pub fn authorization_server_metadata_url(issuer: &str) -> String {
+    let issuer = issuer.trim_end_matches('/');
+    let (scheme, rest) = issuer.split_once("://").unwrap_or(("https", issuer));
+    let (authority, path) = rest
+        .find('/')
+        .map_or((rest, ""), |index| rest.split_at(index));
+    format!("{scheme}://{authority}/.well-known/oauth-authorization-server{path}")
+}
+
+/// Compare issuers exactly, as OIDC Discovery (section 4.3) and RFC 8414 (section 3.3) require;
+/// the document's issuer becomes the one tokens are checked against
+fn issuer_matches(expected: &str, actual: &str) -> bool {
+    expected == actual
+}
+
+/// Fetch the discovery document for the given issuer with caching
+pub async fn fetch_openid_configuration(issuer: &str) -> Result<OpenIdConfiguration> {
+    // Validate issuer to prevent cache pollution
+    if issuer.is_empty() || issuer.len() > 2048 {
+        return Err(anyhow!("Invalid issuer"));
+    }
+
+    // Check cache first
+    {
+        let cache = DISCOVERY_CACHE.read().await;
+        if let Some((document, timestamp)) = cache.get(issuer) {
+            if timestamp.elapsed() < CACHE_DURATION {
+                return Ok(document.clone());
+            }
+        }
+    }
+
+    // Fetch from network
+    let url = discovery_url(issuer);
+    let request = spin_sdk::http::Request::builder()
+        .method(spin_sdk::http::Method::Get)
+        .uri(&url)
+        .header("Accept", "application/json")
+        .build();
+
+    let response: spin_sdk::http::Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch discovery document from {url}: {e}"))?;
+
+    if *response.status() != 200 {
+        let status = response.status();
+        return Err(anyhow!("Failed to fetch discovery document: HTTP {status}"));
+    }
+
+    let document: OpenIdConfiguration = serde_json::from_slice(response.body())?;
+
+    // The document must describe the issuer we asked about (OIDC Discovery 1.0, section 4.3)
+    if !issuer_matches(issuer, &document.issuer) {
+        let actual = &document.issuer;
+        return Err(anyhow!(
+            "Discovery document issuer mismatch: expected {issuer}, got {actual}"
+        ));
+    }
+
+    // Update cache
+    {
+        let mut cache = DISCOVERY_CACHE.write().await;
+
+        // If cache is at max size, remove oldest entry
+        if cache.len() >= MAX_CACHE_SIZE {
+            if let Some(oldest_key) = cache
+                .iter()
+                .min_by_key(|(_, (_, timestamp))| timestamp)
+                .map(|(key, _)| key.clone())
+            {
+                cache.remove(&oldest_key);
+            }
+        }
+
+        cache.insert(
+            issuer.to_string(),
+            (document.clone(), std::time::Instant::now()),
+        );
+    }
+
+    Ok(document)
+}
+
+/// Put a discovery document in the cache, as if it had been fetched
+#[cfg(test)]
+pub async fn cache_openid_configuration(document: OpenIdConfiguration) {
+    DISCOVERY_CACHE.write().await.insert(
+        document.issuer.clone(),
+        (document, std::time::Instant::now()),
+    );
+}
+
+/// Fetch a JSON object from a metadata URL
+async fn fetch_json_object(url: &str) -> Result<Map<String, Value>> {
+    let request = spin_sdk::http::Request::builder()
+        .method(spin_sdk::http::Method::Get)
+        .uri(url)
+        .header("Accept", "application/json")
+        .build();
+
+    let response: spin_sdk::http::Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch metadata from {url}: {e}"))?;
+
+    if *response.status() != 200 {
+        let status = response.status();
+        return Err(anyhow!(
+            "Failed to fetch metadata from {url}: HTTP {status}"
+        ));
+    }
+
+    Ok(serde_json::from_slice(response.body())?)
+}
+
+/// Fetch the issuer's authorization server metadata with caching.
+///
+/// Tries RFC 8414 first and falls back to `OpenID` Connect discovery. The document is
+/// returned as published so it can be served to clients unchanged.
+pub async fn fetch_authorization_server_metadata(issuer: &str) -> Result<Map<String, Value>> {
+    // Validate issuer to prevent cache pollution
+    if issuer.is_empty() || issuer.len() > 2048 {
+        return Err(anyhow!("Invalid issuer"));
+    }
+
+    // Check cache first
+    {
+        let cache = METADATA_CACHE.read().await;
+        if let Some((document, timestamp)) = cache.get(issuer) {
+            if timestamp.elapsed() < CACHE_DURATION {
+                return Ok(document.clone());
+            }
+        }
+    }
+
+    // Fetch from network
+    let document = match fetch_json_object(&authorization_server_metadata_url(issuer)).await {
+        Ok(document) => document,
+        Err(oauth_error) => fetch_json_object(&discovery_url(issuer))
+            .await
+            .map_err(|oidc_error| anyhow!("{oauth_error}; {oidc_error}"))?,
+    };
+
+    // The document must describe the issuer we asked about (RFC 8414, section 3.3)
+    let actual = document
+        .get("issuer")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if !issuer_matches(issuer, actual) {
+        return Err(anyhow!(
+            "Authorization server metadata issuer mismatch: expected {issuer}, got {actual}"
+        ));
+    }
+
+    // Update cache
+    {
+        let mut cache = METADATA_CACHE.write().await;
+
+        // If cache is at max size, remove oldest entry
+        if cache.len() >= MAX_CACHE_SIZE {
+            if let Some(oldest_key) = cache
+                .iter()
+                .min_by_key(|(_, (_, timestamp))| timestamp)
+                .map(|(key, _)| key.clone())
+            {
+                cache.remove(&oldest_key);
+            }
+        }
+
+        cache.insert(
+            issuer.to_string(),
+            (document.clone(), std::time::Instant::now()),
+        );
+    }
+
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_url() {
+        assert_eq!(
+            discovery_url("https://example.auth0.com/"),
+            "https://example.auth0.com/.well-known/openid-configuration"
+        );
+        assert_eq!(
+            discovery_url("https://login.example.com/realms/mcp"),
+            "https://login.example.com/realms/mcp/.well-known/openid-configuration"
+        );
+    }
+
+    #[test]
+    fn test_authorization_server_metadata_url() {
+        assert_eq!(
+            authorization_server_metadata_url("https://example.authkit.app"),
+            "https://example.authkit.app/.well-known/oauth-authorization-server"
+        );
+        assert_eq!(
+            authorization_server_metadata_url("https://login.example.com/realms/mcp/"),
+            "https://login.example.com/.well-known/oauth-authorization-server/realms/mcp"
+        );
+    }
+
+    #[test]
+    fn test_issuer_matches() {
+        assert!(issuer_matches(
+            "https://example.auth0.com/",
+            "https://example.auth0.com/"
+        ));
+        // A trailing slash makes a different issuer
+        assert!(!issuer_matches(
+            "https://example.auth0.com",
+            "https://example.auth0.com/"
+        ));
+        assert!(!issuer_matches(
+            "https://example.auth0.com",
+            "https://evil.example.com/"
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+/// Host of an absolute HTTP(S) URL, lowercased and without the port
+pub fn url_host(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    if !(scheme.eq_ignore_ascii_case("https") || scheme.eq_ignore_ascii_case("http")) {
+        return None;
+    }
+
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    // Userinfo can disguise the real host (`https://trusted.com@evil.com`)
+    if authority.contains('@') {
+        return None;
+    }
+
+    let host = if authority.starts_with('[') {
+        authority.split_inclusive(']').next().unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    };
+
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Whether a host matches an allowlist entry: an exact host, or `*.example.com` for any
+/// subdomain of `example.com`
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+
+    pattern.strip_prefix("*.").map_or_else(
+        || pattern == host,
+        |suffix| host.len() > suffix.len() + 1 && host.ends_with(&format!(".{suffix}")),
+    )
+}
+
+/// Whether a provider may fetch a URL: its host must be the issuer's own or match one of
+/// the allowed domains
+pub fn is_url_allowed(url: &str, issuer: &str, allowed_domains: &[&str]) -> bool {
+    let Some(host) = url_host(url) else {
+        return false;
+    };
+
+    url_host(issuer).is_some_and(|issuer_host| issuer_host == host)
+        || allowed_domains
+            .iter()
+            .any(|pattern| host_matches(pattern, &host))
+}
+
+/// Check an allowed domain is a bare host or a `*.` wildcard over at least two labels
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let host = pattern.strip_prefix("*.").unwrap_or(pattern);
+
+    let valid = !host.is_empty()
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && !host.contains("..")
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'))
+        && (host.len() == pattern.len() || host.contains('.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid allowed domain '{pattern}': expected a host such as 'login.example.com' \
+            or a wildcard such as '*.example.com'"
+        ))
+    }
+}
+
+/// Whether an `allowed_outbound_hosts` entry (e.g. `https://*.example.com:443`) permits
+/// HTTPS requests to every host an allowed domain can match
+pub fn outbound_host_covers(outbound: &str, pattern: &str) -> bool {
+    let Some((scheme, rest)) = outbound.trim().split_once("://") else {
+        return false;
+    };
+    if !matches!(scheme, "https" | "*") {
+        return false;
+    }
+
+    let authority = rest.split('/').next().unwrap_or_default();
+    let outbound_host = authority
+        .rsplit_once(':')
+        .filter(|(_, port)| !port.contains(']'))
+        .map_or(authority, |(host, _)| host)
+        .to_ascii_lowercase();
+    let pattern = pattern.trim().to_ascii_lowercase();
+
+    if outbound_host == "*" || outbound_host == pattern {
+        return true;
+    }
+
+    match (outbound_host.strip_prefix("*."), pattern.strip_prefix("*.")) {
+        // A wildcard is covered by the same or a broader wildcard
+        (Some(outbound_suffix), Some(suffix)) => suffix.ends_with(&format!(".{outbound_suffix}")),
+        (_, None) => host_matches(&outbound_host, &pattern),
+        (None, Some(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_host() {
+        assert_eq!(
+            url_host("https://Login.Example.com/.well-known/jwks.json").as_deref(),
+            Some("login.example.com")
+        );
+        assert_eq!(
+            url_host("https://example.com:8443?x=1").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(url_host("https://[::1]:8443/").as_deref(), Some("[::1]"));
+        assert!(url_host("https://trusted.com@evil.com/jwks").is_none());
+        assert!(url_host("ftp://example.com").is_none());
+        assert!(url_host("example.com").is_none());
+    }
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("*.authkit.app", "tenant.authkit.app"));
+        assert!(host_matches("*.authkit.app", "a.b.authkit.app"));
+        assert!(!host_matches("*.authkit.app", "authkit.app"));
+        assert!(!host_matches("*.authkit.app", "evilauthkit.app"));
+        assert!(host_matches("Login.Example.com", "login.example.com"));
+        assert!(!host_matches(
+            "login.example.com",
+            "login.example.com.evil.io"
+        ));
+    }
+
+    #[test]
+    fn test_is_url_allowed() {
+        let domains = ["*.auth0.com"];
+        let issuer = "https://login.example.com";
+        assert!(is_url_allowed(
+            "https://login.example.com/jwks",
+            issuer,
+            &domains
+        ));
+        assert!(is_url_allowed(
+            "https://tenant.auth0.com/.well-known/jwks.json",
+            issuer,
+            &domains
+        ));
+        assert!(!is_url_allowed("https://evil.com/jwks", issuer, &domains));
+        assert!(!is_url_allowed(
+            "https://login.example.com@evil.com/jwks",
+            issuer,
+            &domains
+        ));
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        assert!(validate_pattern("login.example.com").is_ok());
+        assert!(validate_pattern("*.example.com").is_ok());
+        assert!(validate_pattern("localhost").is_ok());
+        assert!(validate_pattern("*").is_err());
+        assert!(validate_pattern("*.com").is_err());
+        assert!(validate_pattern("https://example.com").is_err());
+        assert!(validate_pattern("ex*.com").is_err());
+        assert!(validate_pattern("").is_err());
+    }
+
+    #[test]
+    fn test_outbound_host_covers() {
+        assert!(outbound_host_covers(
+            "https://*.authkit.app",
+            "*.authkit.app"
+        ));
+        assert!(outbound_host_covers(
+            "https://*.authkit.app",
+            "tenant.authkit.app"
+        ));
+        assert!(outbound_host_covers(
+            "https://*.example.com",
+            "*.eu.example.com"
+        ));
+        assert!(outbound_host_covers(
+            "https://login.example.com:443",
+            "login.example.com"
+        ));
+        assert!(outbound_host_covers("*://*:*", "*.example.com"));
+        assert!(!outbound_host_covers(
+            "https://login.example.com",
+            "*.example.com"
+        ));
+        assert!(!outbound_host_covers(
+            "http://*.example.com",
+            "login.example.com"
+        ));
+        assert!(!outbound_host_covers(
+            "https://*.example.org",
+            "*.example.com"
+        ));
+    }
+}
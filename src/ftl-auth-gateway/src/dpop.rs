@@ -0,0 +1,452 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use spin_sdk::{
+    http::{Method, Request},
+    key_value::Store,
+};
+use std::collections::HashSet;
+
+use crate::{
+    auth::{unix_now, AuthError, Claims},
+    replay,
+};
+
+/// Proof signing algorithms accepted and advertised as `dpop_signing_alg_values_supported`
+pub const SIGNING_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::EdDSA,
+];
+
+/// Clock skew tolerated on a proof's `iat` in the future, in seconds
+const CLOCK_SKEW: i64 = 60;
+
+/// Default window in which a proof is accepted after its `iat`, in seconds
+const DEFAULT_MAX_PROOF_AGE: u64 = 300;
+
+/// `DPoP` (RFC 9449) settings
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DpopConfig {
+    /// Key-value store of used proof IDs; the `DPoP` scheme is only accepted when set
+    #[serde(default)]
+    pub store: Option<String>,
+    /// Reject provider tokens presented without a proof
+    #[serde(default)]
+    pub required: bool,
+    /// Seconds a proof is accepted after its `iat`
+    #[serde(default = "default_max_proof_age")]
+    pub max_proof_age: u64,
+}
+
+const fn default_max_proof_age() -> u64 {
+    DEFAULT_MAX_PROOF_AGE
+}
+
+impl Default for DpopConfig {
+    fn default() -> Self {
+        Self {
+            store: None,
+            required: false,
+            max_proof_age: DEFAULT_MAX_PROOF_AGE,
+        }
+    }
+}
+
+impl DpopConfig {
+    /// Whether DPoP-bound tokens are accepted
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Algorithm names for `dpop_signing_alg_values_supported`
+    pub fn signing_alg_values(&self) -> Vec<String> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        SIGNING_ALGORITHMS
+            .iter()
+            .map(|alg| format!("{alg:?}"))
+            .collect()
+    }
+
+    /// Check the replay store is named and proofs have a usable lifetime
+    pub fn validate(&self) -> Result<(), String> {
+        if self.store.as_deref().is_some_and(|s| s.trim().is_empty()) {
+            return Err("store must not be empty".to_string());
+        }
+        if self.required && !self.is_enabled() {
+            return Err("required needs a store for proof replay detection".to_string());
+        }
+        if self.max_proof_age == 0 {
+            return Err("max_proof_age must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What a proof must be bound to for the current request
+pub struct ProofTarget<'a> {
+    pub config: &'a DpopConfig,
+    /// Public URL of the request, without query or fragment
+    pub url: String,
+}
+
+/// A validated proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Proof {
+    jti: String,
+    /// JWK SHA-256 thumbprint (RFC 7638) of the proof key
+    jkt: String,
+    iat: i64,
+}
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    #[serde(default)]
+    ath: Option<String>,
+}
+
+/// Extract the token from a `DPoP` authorization header (the scheme is case-insensitive)
+pub fn extract_dpop_token(auth_header: &str) -> Option<&str> {
+    let auth_header = auth_header.trim();
+    let (scheme, token) = auth_header.split_once(' ').unwrap_or((auth_header, ""));
+    scheme.eq_ignore_ascii_case("dpop").then(|| token.trim())
+}
+
+/// Request method as it appears in a proof's `htm`
+fn method_name(method: &Method) -> &str {
+    match method {
+        Method::Get => "GET",
+        Method::Head => "HEAD",
+        Method::Post => "POST",
+        Method::Put => "PUT",
+        Method::Delete => "DELETE",
+        Method::Connect => "CONNECT",
+        Method::Options => "OPTIONS",
+        Method::Trace => "TRACE",
+        Method::Patch => "PATCH",
+        Method::Other(method) => method,
+    }
+}
+
+/// JWK SHA-256 thumbprint (RFC 7638) over the required members of a public key
+fn thumbprint(jwk: &Value) -> Result<String, String> {
+    let members: &[&str] = match jwk.get("kty").and_then(Value::as_str) {
+        Some("EC") => &["crv", "kty", "x", "y"],
+        Some("RSA") => &["e", "kty", "n"],
+        Some("OKP") => &["crv", "kty", "x"],
+        _ => return Err("Unsupported DPoP proof key type".to_string()),
+    };
+    // Members are already in lexicographic order, with no whitespace between them
+    let canonical = members
+        .iter()
+        .map(|member| {
+            jwk.get(*member)
+                .and_then(Value::as_str)
+                .map(|value| format!("{}:{}", Value::from(*member), Value::from(value)))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| "DPoP proof key is incomplete".to_string())?;
+
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(format!("{{{}}}", canonical.join(",")))))
+}
+
+/// Compare URLs as RFC 9449 asks: without query and fragment, scheme and host case-insensitive
+fn normalize_htu(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.find('/').map_or((rest, ""), |i| rest.split_at(i));
+            format!(
+                "{}://{}{path}",
+                scheme.to_ascii_lowercase(),
+                authority.to_ascii_lowercase()
+            )
+        }
+        None => url.to_string(),
+    }
+}
+
+/// Validate a proof JWT against the request and the access token it accompanies
+/// (RFC 9449, section 4.3)
+fn validate_proof(
+    proof: &str,
+    method: &str,
+    url: &str,
+    access_token: &str,
+    max_age: u64,
+    now: i64,
+) -> Result<Proof, String> {
+    let header = decode_header(proof).map_err(|_| "Malformed DPoP proof".to_string())?;
+    if !header
+        .typ
+        .as_deref()
+        .is_some_and(|typ| typ.eq_ignore_ascii_case("dpop+jwt"))
+    {
+        return Err("DPoP proof must have typ dpop+jwt".to_string());
+    }
+    if !SIGNING_ALGORITHMS.contains(&header.alg) {
+        return Err("Unsupported DPoP proof algorithm".to_string());
+    }
+    let jwk = header
+        .jwk
+        .ok_or_else(|| "DPoP proof has no jwk header".to_string())?;
+
+    // The thumbprint is taken over the key exactly as sent, which must be public
+    let raw_jwk = proof
+        .split('.')
+        .next()
+        .and_then(|segment| URL_SAFE_NO_PAD.decode(segment).ok())
+        .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+        .and_then(|header| header.get("jwk").cloned())
+        .ok_or_else(|| "Malformed DPoP proof".to_string())?;
+    if raw_jwk.get("d").is_some() {
+        return Err("DPoP proof key must be a public key".to_string());
+    }
+    let jkt = thumbprint(&raw_jwk)?;
+
+    let key = DecodingKey::from_jwk(&jwk).map_err(|_| "Invalid DPoP proof key".to_string())?;
+    let mut validation = Validation::new(header.alg);
+    validation.required_spec_claims = HashSet::new();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    let claims = decode::<ProofClaims>(proof, &key, &validation)
+        .map_err(|_| "Invalid DPoP proof signature or claims".to_string())?
+        .claims;
+
+    if claims.jti.is_empty() {
+        return Err("DPoP proof has no jti".to_string());
+    }
+    if claims.htm != method {
+        return Err("DPoP proof htm does not match the request method".to_string());
+    }
+    if normalize_htu(&claims.htu) != normalize_htu(url) {
+        return Err("DPoP proof htu does not match the request URL".to_string());
+    }
+    let max_age = i64::try_from(max_age).unwrap_or(i64::MAX);
+    if claims.iat > now.saturating_add(CLOCK_SKEW) || claims.iat < now.saturating_sub(max_age) {
+        return Err("DPoP proof iat is outside the accepted window".to_string());
+    }
+    let ath = URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes()));
+    if claims.ath.as_deref() != Some(ath.as_str()) {
+        return Err("DPoP proof ath does not match the access token".to_string());
+    }
+
+    Ok(Proof {
+        jti: claims.jti,
+        jkt,
+        iat: claims.iat,
+    })
+}
+
+/// Record a proof's `jti`, rejecting one already used while its proof was still acceptable.
+/// Fails closed when the store is unavailable. Two requests racing with the same proof can both
+/// pass; see [`replay::record_once`].
+fn record_jti(label: &str, proof: &Proof, max_age: u64, now: i64) -> Result<(), AuthError> {
+    let unavailable = |e: spin_sdk::key_value::Error| {
+        eprintln!("DPoP replay store '{label}' is unavailable: {e}");
+        AuthError::invalid_dpop_proof("DPoP proof could not be checked")
+    };
+    let until = proof
+        .iat
+        .saturating_add(i64::try_from(max_age).unwrap_or(i64::MAX))
+        .saturating_add(CLOCK_SKEW);
+    let store = Store::open(label).map_err(unavailable)?;
+    if replay::record_once(&store, "dpop", &proof.jti, until, now).map_err(unavailable)? {
+        Ok(())
+    } else {
+        Err(AuthError::invalid_dpop_proof(
+            "DPoP proof has already been used",
+        ))
+    }
+}
+
+/// The `cnf.jkt` thumbprint a token is bound to, if any
+fn bound_thumbprint(claims: &Claims) -> Option<&str> {
+    claims.extra.get("cnf")?.get("jkt")?.as_str()
+}
+
+/// Check a verified token is presented the way it is bound: DPoP-bound tokens with a valid,
+/// unused proof from their key, and others only as bearer tokens when `DPoP` is optional
+pub fn check_binding(
+    req: &Request,
+    access_token: &str,
+    claims: &Claims,
+    target: &ProofTarget<'_>,
+    presented_with_dpop: bool,
+) -> Result<(), AuthError> {
+    let config = target.config;
+    let bound = bound_thumbprint(claims);
+
+    let Some(label) = config.store.as_deref().filter(|_| presented_with_dpop) else {
+        if bound.is_some() {
+            return Err(AuthError::invalid_token(
+                "DPoP-bound token must be presented with a DPoP proof",
+            ));
+        }
+        if config.required {
+            return Err(AuthError::invalid_token("A DPoP-bound token is required"));
+        }
+        return Ok(());
+    };
+
+    let mut proofs = req
+        .headers()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("dpop"))
+        .filter_map(|(_, value)| value.as_str());
+    let (Some(proof), None) = (proofs.next(), proofs.next()) else {
+        return Err(AuthError::invalid_dpop_proof(
+            "Exactly one DPoP proof header is required",
+        ));
+    };
+
+    let now = unix_now();
+    let proof = validate_proof(
+        proof.trim(),
+        method_name(req.method()),
+        &target.url,
+        access_token,
+        config.max_proof_age,
+        now,
+    )
+    .map_err(AuthError::invalid_dpop_proof)?;
+
+    if bound != Some(proof.jkt.as_str()) {
+        return Err(AuthError::invalid_token(
+            "Token is not bound to the DPoP proof key",
+        ));
+    }
+
+    record_jti(label, &proof, config.max_proof_age, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const NOW: i64 = 1_700_000_000;
+    const URL: &str = "https://mcp.example.com/mcp";
+
+    fn proof(key: &Ed25519KeyPair, claims: &Value) -> String {
+        let header = serde_json::json!({
+            "typ": "dpop+jwt",
+            "alg": "EdDSA",
+            "jwk": {
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": URL_SAFE_NO_PAD.encode(key.public_key().as_ref())
+            }
+        });
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = key.sign(input.as_bytes());
+        format!("{input}.{}", URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+
+    #[test]
+    fn test_thumbprint() {
+        // RFC 8037, appendix A.3
+        let jwk = serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        });
+        assert_eq!(
+            thumbprint(&jwk).unwrap(),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+        assert!(thumbprint(&serde_json::json!({ "kty": "oct", "k": "c2VjcmV0" })).is_err());
+    }
+
+    #[test]
+    fn test_normalize_htu() {
+        assert_eq!(
+            normalize_htu("HTTPS://MCP.Example.com/mcp?x=1#frag"),
+            "https://mcp.example.com/mcp"
+        );
+        assert_ne!(
+            normalize_htu("https://mcp.example.com/MCP"),
+            normalize_htu(URL)
+        );
+    }
+
+    #[test]
+    fn test_validate_proof() {
+        let key = Ed25519KeyPair::from_seed_unchecked(&[3u8; 32]).unwrap();
+        let token = "access.token.value";
+        let ath = URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()));
+        let claims = |htm: &str, iat: i64, ath: &str| serde_json::json!({ "jti": "p-1", "htm": htm, "htu": URL, "iat": iat, "ath": ath });
+
+        let valid = validate_proof(
+            &proof(&key, &claims("POST", NOW, &ath)),
+            "POST",
+            URL,
+            token,
+            300,
+            NOW,
+        )
+        .unwrap();
+        let jwk = serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(key.public_key().as_ref())
+        });
+        assert_eq!(valid.jkt, thumbprint(&jwk).unwrap());
+        assert_eq!(valid.jti, "p-1");
+
+        let check = |claims: Value| {
+            validate_proof(&proof(&key, &claims), "POST", URL, token, 300, NOW).unwrap_err()
+        };
+        assert!(check(claims("GET", NOW, &ath)).contains("htm"));
+        assert!(check(claims("POST", NOW - 301, &ath)).contains("iat"));
+        assert!(check(claims("POST", NOW + 61, &ath)).contains("iat"));
+        assert!(check(claims("POST", NOW, "other")).contains("ath"));
+
+        // A proof signed by another key does not verify against its jwk header
+        let other = Ed25519KeyPair::from_seed_unchecked(&[4u8; 32]).unwrap();
+        let forged = proof(&key, &claims("POST", NOW, &ath));
+        let (input, _) = forged.rsplit_once('.').unwrap();
+        let forged = format!(
+            "{input}.{}",
+            URL_SAFE_NO_PAD.encode(other.sign(input.as_bytes()).as_ref())
+        );
+        assert!(validate_proof(&forged, "POST", URL, token, 300, NOW).is_err());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(DpopConfig::default().validate().is_ok());
+        assert!(DpopConfig::default().signing_alg_values().is_empty());
+
+        let required = DpopConfig {
+            required: true,
+            ..DpopConfig::default()
+        };
+        assert!(required.validate().is_err());
+
+        let enabled = DpopConfig {
+            store: Some("default".to_string()),
+            ..required
+        };
+        assert!(enabled.validate().is_ok());
+        assert!(enabled.signing_alg_values().contains(&"ES256".to_string()));
+    }
+}
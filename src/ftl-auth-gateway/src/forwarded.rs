@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use spin_sdk::http::Request;
+use std::net::{IpAddr, SocketAddr};
+
+/// An IP address range in CIDR notation (a bare address is a single-host range)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cidr> for String {
+    fn from(cidr: Cidr) -> Self {
+        format!("{}/{}", cidr.addr, cidr.prefix)
+    }
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (addr, prefix) = value
+            .split_once('/')
+            .map_or((value, None), |(addr, prefix)| (addr, Some(prefix)));
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid IP address in trusted proxy: {value}"))?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u32>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid prefix length in trusted proxy: {value}"))?,
+            None => max_prefix,
+        };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl Cidr {
+    /// Whether the address falls inside this range
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Treat IPv4-mapped IPv6 addresses as IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Host and scheme of the original client request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestOrigin {
+    pub host: Option<String>,
+    pub proto: Option<String>,
+}
+
+/// Find a header value by case-insensitive name
+fn header<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| value.as_str())
+}
+
+/// Address of the peer that connected to Spin
+fn peer_addr(req: &Request) -> Option<IpAddr> {
+    let value = header(req, "spin-client-addr")?;
+    value
+        .parse::<SocketAddr>()
+        .map(|addr| addr.ip())
+        .or_else(|_| value.parse::<IpAddr>())
+        .ok()
+}
+
+/// Whether a host value is safe to reflect into URLs and headers
+fn is_valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 255
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+}
+
+/// Whether an address belongs to a trusted proxy
+fn is_trusted(ip: IpAddr, trusted_proxies: &[Cidr]) -> bool {
+    trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// Address named by a `Forwarded` node (`192.0.2.60`, `192.0.2.60:4711`, `[2001:db8::1]:4711`).
+/// Obfuscated and `unknown` nodes have none.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(bracketed) = node.strip_prefix('[') {
+        return bracketed
+            .split_once(']')
+            .and_then(|(ip, _)| ip.parse::<IpAddr>().ok());
+    }
+    node.parse::<SocketAddr>()
+        .map(|addr| addr.ip())
+        .or_else(|_| node.parse::<IpAddr>())
+        .ok()
+}
+
+/// Parse one element of a `Forwarded` header: the address the proxy received the request from,
+/// and the host and scheme it was sent to
+fn parse_forwarded_element(element: &str) -> (Option<IpAddr>, RequestOrigin) {
+    let mut from = None;
+    let mut origin = RequestOrigin::default();
+
+    for pair in element.split(';') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        match key.trim().to_ascii_lowercase().as_str() {
+            "for" => from = parse_node(value),
+            "host" => origin.host = Some(value.to_string()),
+            "proto" => origin.proto = Some(value.to_ascii_lowercase()),
+            _ => {}
+        }
+    }
+
+    (from, origin)
+}
+
+/// Parse an RFC 7239 `Forwarded` header received from a trusted proxy.
+///
+/// Each proxy appends an element naming the address it received the request from, so elements
+/// are walked from the right: while an element names a trusted proxy, the element before it was
+/// written by that proxy too. The first element naming anyone else is the one the outermost
+/// trusted proxy wrote. Elements to its left came from the client and are ignored.
+pub fn parse_forwarded(value: &str, trusted_proxies: &[Cidr]) -> RequestOrigin {
+    let mut origin = RequestOrigin::default();
+    for (from, element) in value.split(',').rev().map(parse_forwarded_element) {
+        origin = element;
+        if !from.is_some_and(|ip| is_trusted(ip, trusted_proxies)) {
+            break;
+        }
+    }
+    origin
+}
+
+/// Determine where the client sent the request.
+///
+/// Forwarding headers are only honored when the connecting peer is a trusted proxy; otherwise
+/// the `Host` header is used as-is. `X-Forwarded-Host`, `X-Forwarded-Proto` and
+/// `X-Original-Host` do not say which proxy added each value, so only the last one, added by
+/// the peer itself, is used; chains of trusted proxies should send `Forwarded` instead.
+pub fn request_origin(req: &Request, trusted_proxies: &[Cidr]) -> RequestOrigin {
+    let trusted = peer_addr(req).is_some_and(|peer| is_trusted(peer, trusted_proxies));
+
+    let mut origin = RequestOrigin::default();
+
+    if trusted {
+        if let Some(forwarded) = header(req, "forwarded") {
+            origin = parse_forwarded(forwarded, trusted_proxies);
+        }
+
+        // De-facto headers fill in whatever `Forwarded` did not provide
+        let last_value = |name: &str| {
+            header(req, name)
+                .and_then(|value| value.rsplit(',').next())
+                .map(|value| value.trim().to_string())
+        };
+        origin.host = origin
+            .host
+            .or_else(|| last_value("x-forwarded-host"))
+            .or_else(|| last_value("x-original-host"));
+        origin.proto = origin
+            .proto
+            .or_else(|| last_value("x-forwarded-proto").map(|p| p.to_ascii_lowercase()));
+    }
+
+    origin.host = origin
+        .host
+        .or_else(|| header(req, "host").map(String::from))
+        .filter(|host| is_valid_host(host));
+    origin.proto = origin
+        .proto
+        .filter(|proto| matches!(proto.as_str(), "http" | "https"));
+
+    origin
+}
+
+/// Scheme of the URL Spin received the request on
+pub fn spin_scheme(req: &Request) -> Option<&str> {
+    header(req, "spin-full-url")
+        .and_then(|url| url.split_once("://"))
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| matches!(*scheme, "http" | "https"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr_contains() {
+        let range: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(range.contains("::ffff:10.0.0.1".parse().unwrap()));
+
+        let single: Cidr = "192.168.1.5".parse().unwrap();
+        assert!(single.contains("192.168.1.5".parse().unwrap()));
+        assert!(!single.contains("192.168.1.6".parse().unwrap()));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.0.0.1".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("not-an-ip".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_forwarded() {
+        let trusted: Vec<Cidr> = vec!["10.0.0.0/8".parse().unwrap()];
+        let origin = |host: &str, proto: &str| RequestOrigin {
+            host: Some(host.to_string()),
+            proto: Some(proto.to_string()),
+        };
+
+        assert_eq!(
+            parse_forwarded(
+                r#"for=192.0.2.60;proto=HTTPS;host="mcp.example.com""#,
+                &trusted
+            ),
+            origin("mcp.example.com", "https")
+        );
+        assert_eq!(
+            parse_forwarded("for=192.0.2.43", &trusted),
+            RequestOrigin::default()
+        );
+
+        // A chain of trusted proxies: the element written by the outermost one is used
+        assert_eq!(
+            parse_forwarded(
+                r#"for=192.0.2.60;proto=https;host=mcp.example.com, for="10.0.0.1:4711";proto=http;host=internal"#,
+                &trusted
+            ),
+            origin("mcp.example.com", "https")
+        );
+
+        // An element forged by the client, left of the trusted proxy's, is ignored
+        assert_eq!(
+            parse_forwarded(
+                "for=10.0.0.9;proto=http;host=evil.example.com, for=192.0.2.60;proto=https;host=mcp.example.com",
+                &trusted
+            ),
+            origin("mcp.example.com", "https")
+        );
+        assert_eq!(
+            parse_forwarded(
+                r#"host=evil.example.com, for="[2001:db8::1]:4711";proto=https;host=mcp.example.com"#,
+                &trusted
+            ),
+            origin("mcp.example.com", "https")
+        );
+        // Obfuscated clients are not trusted either
+        assert_eq!(
+            parse_forwarded(
+                "host=evil.example.com, for=_hidden;proto=https;host=mcp.example.com",
+                &trusted
+            ),
+            origin("mcp.example.com", "https")
+        );
+    }
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(parse_node("192.0.2.60"), "192.0.2.60".parse().ok());
+        assert_eq!(parse_node("192.0.2.60:4711"), "192.0.2.60".parse().ok());
+        assert_eq!(parse_node("[2001:db8::1]:4711"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_node("[2001:db8::1]"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+    }
+
+    #[test]
+    fn test_is_valid_host() {
+        assert!(is_valid_host("mcp.example.com"));
+        assert!(is_valid_host("127.0.0.1:3000"));
+        assert!(is_valid_host("[::1]:3000"));
+        assert!(!is_valid_host("evil.com/\"x"));
+        assert!(!is_valid_host(""));
+    }
+}
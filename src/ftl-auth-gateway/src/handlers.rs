@@ -1,17 +1,29 @@
+use std::collections::BTreeMap;
+
 use spin_sdk::http::{Method, Request, Response};
 
 use crate::{
-    auth::{self, verify_request},
+    auth::{self, verify_request, AuthError, Claims},
     config::GatewayConfig,
+    cors,
+    dpop::ProofTarget,
+    jsonrpc,
     logging::Logger,
-    metadata::handle_metadata_request,
-    proxy::forward_to_mcp_gateway,
+    metadata::{
+        determine_base_url, determine_resource_url, handle_metadata_request, resource_metadata_url,
+    },
+    origin,
+    providers::{ProviderRegistry, UserContext},
+    proxy::{forward_to_mcp_gateway, GatewayResponse},
+    registration, revocation, token_exchange,
+    trace_context::TraceContext,
 };
 
 /// Handle metadata endpoints (no auth required)
-pub fn handle_metadata_endpoints(
+pub async fn handle_metadata_endpoints(
     path: &str,
-    provider: Option<&dyn crate::providers::AuthProvider>,
+    config: &GatewayConfig,
+    registry: &ProviderRegistry,
     host: Option<&str>,
     req: &Request,
     logger: &Logger<'_>,
@@ -29,93 +41,304 @@ pub fn handle_metadata_endpoints(
         .field("host", host.unwrap_or("unknown"))
         .emit();
 
-    provider.map_or_else(
-        || {
-            logger.warn("No auth provider configured").emit();
-            Some(
-                Response::builder()
-                    .status(500)
-                    .body("No authentication provider configured")
-                    .build(),
-            )
-        },
-        |p| Some(handle_metadata_request(path, p, host, req)),
+    let Some(provider) = registry.primary() else {
+        logger.warn("No auth provider configured").emit();
+        return Some(
+            Response::builder()
+                .status(500)
+                .body("No authentication provider configured")
+                .build(),
+        );
+    };
+
+    Some(handle_metadata_request(path, config, registry, provider, host, req).await)
+}
+
+/// Handle endpoints served by a local provider, such as the development provider (no auth required)
+pub fn handle_local_provider_endpoints(
+    path: &str,
+    registry: &ProviderRegistry,
+    req: &Request,
+    logger: &Logger<'_>,
+) -> Option<Response> {
+    let response = registry
+        .providers()
+        .iter()
+        .find_map(|provider| provider.handle_local_request(path, req))?;
+
+    logger
+        .info("Local provider request")
+        .field("path", path)
+        .field("status", response.status())
+        .emit();
+
+    Some(response)
+}
+
+/// Handle dynamic client registration (no auth required)
+pub async fn handle_registration_endpoint(
+    path: &str,
+    config: &GatewayConfig,
+    registry: &ProviderRegistry,
+    req: &Request,
+    logger: &Logger<'_>,
+) -> Option<Response> {
+    if path != "/register" || !config.registration.is_enabled() {
+        return None;
+    }
+
+    if *req.method() != Method::Post {
+        return Some(
+            Response::builder()
+                .status(405)
+                .header("Allow", "POST, OPTIONS")
+                .body("Method not allowed")
+                .build(),
+        );
+    }
+
+    logger.info("Client registration request").emit();
+
+    Some(
+        registration::handle_registration(&config.registration, registry.primary(), req, logger)
+            .await,
     )
 }
 
-/// Handle OPTIONS requests (CORS preflight)
-pub fn handle_cors_preflight(method: &Method) -> Option<Response> {
-    if *method != Method::Options {
+/// Handle the revocation admin and back-channel logout endpoints
+pub async fn handle_revocation_endpoints(
+    path: &str,
+    config: &GatewayConfig,
+    registry: &ProviderRegistry,
+    req: &Request,
+    logger: &Logger<'_>,
+) -> Option<Response> {
+    let revocation = &config.revocation;
+    match path {
+        "/admin/revocations" if !revocation.admin_keys.is_empty() => {
+            Some(revocation::handle_admin_request(req, revocation, logger).await)
+        }
+        "/backchannel-logout" if revocation.backchannel_logout => {
+            Some(revocation::handle_backchannel_logout(req, revocation, registry, logger).await)
+        }
+        _ => None,
+    }
+}
+
+/// Reject browser requests from untrusted origins (DNS-rebinding protection)
+pub fn handle_origin_validation(
+    req: &Request,
+    config: &GatewayConfig,
+    logger: &Logger<'_>,
+) -> Option<Response> {
+    let origin = cors::request_origin(req);
+    let public_urls: Vec<&str> = config
+        .public_url
+        .iter()
+        .chain(config.resource_url.iter())
+        .map(String::as_str)
+        .collect();
+
+    if origin::is_origin_allowed(origin, &config.allowed_origins, &public_urls) {
         return None;
     }
 
+    logger
+        .warn("Rejected request from untrusted origin")
+        .field("origin", origin.unwrap_or_default())
+        .emit();
+
+    // The MCP transport spec calls for 403, optionally with an id-less JSON-RPC error
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": -32600,
+            "message": "Origin not allowed"
+        }
+    });
+
     Some(
         Response::builder()
-            .status(204)
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-            .header(
-                "Access-Control-Allow-Headers",
-                "Content-Type, Authorization",
-            )
-            .header("Access-Control-Max-Age", "86400")
+            .status(403)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
             .build(),
     )
 }
 
+/// Handle OPTIONS requests (CORS preflight)
+pub fn handle_cors_preflight(req: &Request, config: &GatewayConfig) -> Option<Response> {
+    if *req.method() != Method::Options {
+        return None;
+    }
+
+    Some(config.cors.preflight_response(cors::request_origin(req)))
+}
+
+/// Gateway policy applied to every verified identity: tenant binding and revocation
+fn check_verified(
+    config: &GatewayConfig,
+    (claims, user_context): (Claims, UserContext),
+) -> Result<(Claims, UserContext), AuthError> {
+    // Tenant isolation needs every request bound to a tenant
+    if config.require_tenant && user_context.tenant.is_none() {
+        return Err(AuthError::invalid_token("Token is not bound to a tenant"));
+    }
+    revocation::check_revoked(&claims, &config.revocation).map_err(AuthError::invalid_token)?;
+    Ok((claims, user_context))
+}
+
+/// Forward a request to the MCP gateway, with the caller's identity when authenticated
+async fn forward(
+    req: Request,
+    config: &GatewayConfig,
+    identity: Option<(Claims, UserContext)>,
+    tool_tokens: &BTreeMap<String, String>,
+    trace: &TraceContext,
+    logger: &Logger<'_>,
+) -> GatewayResponse {
+    let auth_config = crate::auth::AuthConfig {
+        mcp_gateway_url: config.mcp_gateway_url.clone(),
+        headers: config.headers.clone(),
+        tenant_secret: config.tenant_secret.clone(),
+    };
+
+    match forward_to_mcp_gateway(req, &auth_config, identity, tool_tokens, trace).await {
+        Ok(response) => response,
+        Err(e) => {
+            logger
+                .error("Failed to forward request to MCP gateway")
+                .field("error", &e)
+                .emit();
+            Response::builder()
+                .status(502)
+                .body(format!("Gateway error: {e}"))
+                .build()
+                .into()
+        }
+    }
+}
+
 /// Handle authenticated requests
 pub async fn handle_authenticated_request(
     req: Request,
     config: &GatewayConfig,
-    provider: Option<&dyn crate::providers::AuthProvider>,
+    registry: &ProviderRegistry,
     host: Option<&str>,
-    trace_id: &str,
+    trace: &TraceContext,
     logger: &Logger<'_>,
-) -> Response {
-    let Some(p) = provider else {
+) -> GatewayResponse {
+    let api_keys = config.api_keys.as_ref();
+    let resource_metadata = resource_metadata_url(config, host, &req);
+
+    // Challenges advertise the scopes this request needs so clients can ask for them, and
+    // token exchange needs to know which tools are called
+    let calls = if config.scope_policy.is_empty()
+        && config.anonymous.is_empty()
+        && !registry
+            .providers()
+            .iter()
+            .any(|p| p.token_exchange().is_some())
+    {
+        Vec::new()
+    } else {
+        jsonrpc::peek_calls(req.body())
+    };
+    let required_scope = config.scope_policy.required_scopes(&calls).join(" ");
+    let dpop_algs = Some(config.dpop.signing_alg_values().join(" ")).filter(|a| !a.is_empty());
+
+    if registry.providers().is_empty() && api_keys.is_none() {
         logger.warn("No authentication provider configured").emit();
         return auth::auth_error_response(
-            "No authentication provider configured",
-            host,
-            Some(trace_id),
-        );
+            &AuthError::missing_credentials("No authentication provider configured"),
+            Some(&required_scope),
+            Some(&resource_metadata),
+            dpop_algs.as_deref(),
+            Some(trace.log_id.as_str()),
+        )
+        .into();
+    }
+
+    // Tokens bound to the resource must name its canonical URL as their audience
+    let resource_audience = config
+        .resource_audience
+        .then(|| determine_resource_url(config, host, &req));
+
+    // DPoP proofs name the public URL of the request they were made for
+    let dpop = ProofTarget {
+        config: &config.dpop,
+        url: format!("{}{}", determine_base_url(config, host, &req), req.path()),
     };
 
-    match verify_request(&req, p, host, Some(trace_id)).await {
+    let verified = verify_request(
+        &req,
+        registry,
+        api_keys,
+        resource_audience.as_deref(),
+        config.token_cache_ttl,
+        &dpop,
+    )
+    .await
+    .and_then(|verified| check_verified(config, verified));
+
+    match verified {
         Ok((claims, user_context)) => {
             logger
                 .info("Authentication successful")
-                .field("provider", p.name())
+                .field("provider", &user_context.provider)
                 .field("user_id", &user_context.id)
                 .emit();
 
-            // Forward authenticated request to MCP gateway
-            let auth_config = crate::auth::AuthConfig {
-                mcp_gateway_url: config.mcp_gateway_url.clone(),
-            };
-
-            match forward_to_mcp_gateway(req, &auth_config, Some((claims, user_context)), trace_id)
-                .await
-            {
-                Ok(response) => response,
-                Err(e) => {
+            // Enforce per-method and per-tool scope requirements
+            if !config.scope_policy.is_empty() {
+                let missing = config.scope_policy.missing_scopes(&calls, &claims.scopes());
+                if !missing.is_empty() {
                     logger
-                        .error("Failed to forward request to MCP gateway")
-                        .field("error", &e)
+                        .warn("Insufficient scope")
+                        .field("user_id", &user_context.id)
+                        .field("missing", missing.join(" "))
                         .emit();
-                    Response::builder()
-                        .status(502)
-                        .body(format!("Gateway error: {e}"))
-                        .build()
+                    return auth::insufficient_scope_response(
+                        &missing,
+                        Some(&resource_metadata),
+                        Some(trace.log_id.as_str()),
+                    )
+                    .into();
                 }
             }
+
+            // Forward authenticated request to MCP gateway, with downstream tokens for its tools
+            let tool_tokens = token_exchange::tool_tokens(&req, registry, &claims, &calls).await;
+            let identity = Some((claims, user_context));
+            forward(req, config, identity, &tool_tokens, trace, logger).await
         }
-        Err(auth_error) => {
+        // Requests without credentials may still make the calls allowed anonymously
+        Err(error) if error.code.is_none() && config.anonymous.allows(&calls) => {
+            logger
+                .info("Forwarding anonymous request")
+                .field("reason", &error.description)
+                .emit();
+            forward(req, config, None, &BTreeMap::new(), trace, logger).await
+        }
+        Err(error) => {
             logger
                 .warn("Authentication failed")
-                .field("provider", p.name())
+                .field(
+                    "error",
+                    error
+                        .code
+                        .map_or("missing_credentials", |code| code.as_str()),
+                )
+                .field("reason", &error.description)
                 .emit();
-            auth_error
+            auth::auth_error_response(
+                &error,
+                Some(&required_scope),
+                Some(&resource_metadata),
+                dpop_algs.as_deref(),
+                Some(trace.log_id.as_str()),
+            )
+            .into()
         }
     }
 }
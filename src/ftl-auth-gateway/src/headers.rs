@@ -0,0 +1,339 @@
+use std::fmt::Write;
+
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+
+use crate::trace_context::{TRACEPARENT_HEADER, TRACESTATE_HEADER};
+
+/// Headers that only describe a single connection and are never forwarded
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Request headers carrying the client's credentials, which must never reach the MCP gateway
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "x-api-key"];
+
+/// Request headers the gateway sets itself, including the tenant binding
+const MANAGED_REQUEST_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "x-trace-id",
+    TRACEPARENT_HEADER,
+    TRACESTATE_HEADER,
+    TENANT_HEADER,
+    TENANT_SIGNATURE_HEADER,
+    TOOL_TOKENS_HEADER,
+];
+
+/// Header binding a forwarded request to the user's tenant
+pub const TENANT_HEADER: &str = "x-ftl-tenant";
+
+/// Header proving to the MCP gateway that the tenant header came from this gateway
+pub const TENANT_SIGNATURE_HEADER: &str = "x-ftl-tenant-signature";
+
+/// Header carrying downstream tool tokens to the MCP gateway, a JSON object keyed by tool name
+pub const TOOL_TOKENS_HEADER: &str = "x-ftl-tool-tokens";
+
+/// Response headers the gateway sets itself; `Vary` varies with the gateway's CORS policy
+const MANAGED_RESPONSE_HEADERS: &[&str] = &["x-trace-id", "vary"];
+
+/// Response header prefixes the gateway sets itself. CORS headers come from the gateway's own
+/// policy alone, so an upstream `Access-Control-Allow-Origin: *` cannot widen the allowlist.
+const MANAGED_RESPONSE_PREFIXES: &[&str] = &["access-control-"];
+
+/// Which headers are forwarded between the client and the MCP gateway
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HeaderPolicy {
+    /// Request headers forwarded to the MCP gateway: names, `X-Prefix-*` patterns, or `*`
+    #[serde(default = "default_forward_request_headers")]
+    pub forward_request: Vec<String>,
+    /// Request headers never forwarded, even when they match an allowed pattern
+    #[serde(default)]
+    pub deny_request: Vec<String>,
+    /// Response headers returned to the client: names, `X-Prefix-*` patterns, or `*`
+    #[serde(default = "default_forward_response_headers")]
+    pub forward_response: Vec<String>,
+    /// Response headers never returned, even when they match an allowed pattern
+    #[serde(default)]
+    pub deny_response: Vec<String>,
+}
+
+/// Headers the MCP Streamable HTTP transport relies on
+pub fn default_forward_request_headers() -> Vec<String> {
+    [
+        "Accept",
+        "Content-Type",
+        "Mcp-Session-Id",
+        "MCP-Protocol-Version",
+        "Last-Event-ID",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+pub fn default_forward_response_headers() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        Self {
+            forward_request: default_forward_request_headers(),
+            deny_request: Vec::new(),
+            forward_response: default_forward_response_headers(),
+            deny_response: Vec::new(),
+        }
+    }
+}
+
+/// Whether a header name matches a pattern: an exact name, `X-Prefix-*`, or `*`
+fn header_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.trim();
+    pattern.strip_suffix('*').map_or_else(
+        || pattern.eq_ignore_ascii_case(name),
+        |prefix| {
+            name.len() >= prefix.len()
+                && name
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        },
+    )
+}
+
+/// Headers named in `Connection`, which are hop-by-hop for this message only
+fn connection_options(headers: &[(String, Vec<u8>)]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("connection"))
+        .filter_map(|(_, value)| std::str::from_utf8(value).ok())
+        .flat_map(|value| value.split(','))
+        .map(|option| option.trim().to_ascii_lowercase())
+        .filter(|option| !option.is_empty())
+        .collect()
+}
+
+/// Keep the headers the allow/deny lists permit, dropping hop-by-hop and fixed headers
+fn filter_headers(
+    headers: Vec<(String, Vec<u8>)>,
+    allow: &[String],
+    deny: &[String],
+    fixed: &[&[&str]],
+    fixed_prefixes: &[&str],
+) -> Vec<(String, Vec<u8>)> {
+    let options = connection_options(&headers);
+
+    headers
+        .into_iter()
+        .filter(|(name, _)| {
+            let lower = name.to_ascii_lowercase();
+            !HOP_BY_HOP_HEADERS.contains(&lower.as_str())
+                && !options.contains(&lower)
+                && !fixed.iter().any(|list| list.contains(&lower.as_str()))
+                && !fixed_prefixes
+                    .iter()
+                    .any(|prefix| lower.starts_with(prefix))
+                && allow.iter().any(|pattern| header_matches(pattern, name))
+                && !deny.iter().any(|pattern| header_matches(pattern, name))
+        })
+        .collect()
+}
+
+impl HeaderPolicy {
+    /// Request headers to send to the MCP gateway. Credentials are always stripped.
+    pub fn request_headers(&self, headers: Vec<(String, Vec<u8>)>) -> Vec<(String, Vec<u8>)> {
+        filter_headers(
+            headers,
+            &self.forward_request,
+            &self.deny_request,
+            &[CREDENTIAL_HEADERS, MANAGED_REQUEST_HEADERS],
+            &[],
+        )
+    }
+
+    /// MCP gateway response headers to return to the client
+    pub fn response_headers(&self, headers: Vec<(String, Vec<u8>)>) -> Vec<(String, Vec<u8>)> {
+        filter_headers(
+            headers,
+            &self.forward_response,
+            &self.deny_response,
+            &[MANAGED_RESPONSE_HEADERS],
+            MANAGED_RESPONSE_PREFIXES,
+        )
+    }
+
+    /// Reject lists that name a credential header, since those are never forwarded
+    pub fn validate(&self) -> Result<(), String> {
+        self.forward_request
+            .iter()
+            .find(|pattern| {
+                CREDENTIAL_HEADERS
+                    .iter()
+                    .any(|name| pattern.trim().eq_ignore_ascii_case(name))
+            })
+            .map_or(Ok(()), |pattern| {
+                Err(format!(
+                    "{} is never forwarded to the MCP gateway",
+                    pattern.trim()
+                ))
+            })
+    }
+}
+
+/// Hex HMAC-SHA256 of the tenant ID under the secret shared with the MCP gateway
+pub fn tenant_signature(secret: &str, tenant: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::sign(&key, tenant.as_bytes()).as_ref().iter().fold(
+        String::with_capacity(64),
+        |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cors::CorsConfig;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, Vec<u8>)> {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn names(headers: &[(String, Vec<u8>)]) -> Vec<&str> {
+        headers.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_default_request_headers() {
+        let policy = HeaderPolicy::default();
+        let forwarded = policy.request_headers(headers(&[
+            ("Accept", "application/json, text/event-stream"),
+            ("content-type", "application/json"),
+            ("Mcp-Session-Id", "abc"),
+            ("mcp-protocol-version", "2025-06-18"),
+            ("Last-Event-ID", "42"),
+            ("Authorization", "Bearer secret"),
+            ("X-API-Key", "secret"),
+            ("Cookie", "session=1"),
+            ("Host", "mcp.example.com"),
+            ("X-FTL-Tenant", "spoofed"),
+        ]));
+
+        assert_eq!(
+            names(&forwarded),
+            vec![
+                "Accept",
+                "content-type",
+                "Mcp-Session-Id",
+                "mcp-protocol-version",
+                "Last-Event-ID"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_credentials_always_stripped() {
+        let policy = HeaderPolicy {
+            forward_request: vec!["*".to_string()],
+            deny_request: vec!["Cookie".to_string(), "X-Internal-*".to_string()],
+            ..HeaderPolicy::default()
+        };
+        let forwarded = policy.request_headers(headers(&[
+            ("authorization", "Bearer secret"),
+            ("Proxy-Authorization", "Basic secret"),
+            ("Cookie", "session=1"),
+            ("X-Internal-Route", "admin"),
+            ("Connection", "keep-alive, X-Hop"),
+            ("X-Hop", "1"),
+            ("User-Agent", "client/1.0"),
+        ]));
+
+        assert_eq!(names(&forwarded), vec!["User-Agent"]);
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let policy = HeaderPolicy {
+            deny_response: vec!["Server".to_string()],
+            ..HeaderPolicy::default()
+        };
+        let returned = policy.response_headers(headers(&[
+            ("Content-Type", "text/event-stream"),
+            ("Mcp-Session-Id", "abc"),
+            ("Transfer-Encoding", "chunked"),
+            ("X-Trace-Id", "upstream"),
+            ("Server", "internal"),
+        ]));
+
+        assert_eq!(names(&returned), vec!["Content-Type", "Mcp-Session-Id"]);
+    }
+
+    #[test]
+    fn test_upstream_cors_headers_dropped() {
+        let returned = HeaderPolicy::default().response_headers(headers(&[
+            ("Content-Type", "application/json"),
+            ("Access-Control-Allow-Origin", "*"),
+            ("access-control-allow-credentials", "true"),
+            ("Access-Control-Expose-Headers", "X-Internal"),
+            ("Vary", "Accept-Encoding"),
+        ]));
+        assert_eq!(names(&returned), vec!["Content-Type"]);
+
+        // A disallowed origin gets no `Access-Control-Allow-Origin` once the gateway's own
+        // CORS headers are applied to the proxied response
+        let cors = CorsConfig {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            ..CorsConfig::default()
+        };
+        let applied = cors.response_headers(Some("https://evil.example.com"));
+        assert!(returned
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .chain(applied.iter().map(|(name, _)| *name))
+            .all(|name| !name.eq_ignore_ascii_case("access-control-allow-origin")));
+    }
+
+    #[test]
+    fn test_tenant_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            tenant_signature("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(
+            tenant_signature("secret", "acme"),
+            tenant_signature("secret", "globex")
+        );
+
+        // A client's own tenant binding never reaches the MCP gateway
+        let forwarded = HeaderPolicy::default().request_headers(headers(&[
+            ("X-FTL-Tenant", "acme"),
+            ("X-FTL-Tenant-Signature", "forged"),
+        ]));
+        assert!(forwarded.is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(HeaderPolicy::default().validate().is_ok());
+
+        let policy = HeaderPolicy {
+            forward_request: vec!["Accept".to_string(), "Authorization".to_string()],
+            ..HeaderPolicy::default()
+        };
+        assert!(policy.validate().is_err());
+    }
+}
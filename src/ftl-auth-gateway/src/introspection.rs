@@ -0,0 +1,390 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{sha256_hex, unix_now, Claims},
+    providers::AuthProvider,
+};
+
+/// RFC 7662 token introspection settings for a provider
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IntrospectionConfig {
+    /// Introspection endpoint; defaults to the one advertised by the provider
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Type alias for the introspection cache entry (claims and their expiry)
+type IntrospectionCacheEntry = (Map<String, Value>, i64);
+
+/// Type alias for the introspection cache
+type IntrospectionCache = Arc<RwLock<HashMap<String, IntrospectionCacheEntry>>>;
+
+/// Cache for positive introspection results, keyed by a hash of the provider's issuer and the
+/// token
+static INTROSPECTION_CACHE: Lazy<IntrospectionCache> =
+    Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Maximum number of tokens to cache (prevent `DoS`)
+const MAX_CACHE_SIZE: usize = 1000;
+
+/// Percent-encode a value for an `application/x-www-form-urlencoded` body
+pub fn form_urlencode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(char::from(byte));
+            }
+            b' ' => out.push('+'),
+            _ => {
+                let _ = write!(out, "%{byte:02X}");
+            }
+        }
+    }
+    out
+}
+
+/// Decode `application/x-www-form-urlencoded` pairs, as in a form body or query string
+pub fn form_urldecode(input: &str) -> Vec<(String, String)> {
+    let decode = |value: &str| {
+        let bytes = value.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while let Some(&byte) = bytes.get(i) {
+            let escaped = (byte == b'%')
+                .then(|| bytes.get(i + 1..i + 3))
+                .flatten()
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (byte, escaped) {
+                (_, Some(decoded)) => {
+                    out.push(decoded);
+                    i += 3;
+                }
+                (b'+', None) => {
+                    out.push(b' ');
+                    i += 1;
+                }
+                (byte, None) => {
+                    out.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    };
+
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+/// Build an HTTP Basic `Authorization` header value for client credentials (RFC 6749, section 2.3.1)
+pub fn client_basic_auth(client_id: &str, client_secret: &str) -> String {
+    let credentials = format!(
+        "{}:{}",
+        form_urlencode(client_id),
+        form_urlencode(client_secret)
+    );
+    format!("Basic {}", STANDARD.encode(credentials))
+}
+
+/// Call the introspection endpoint and return the raw response for active tokens
+async fn fetch_introspection(
+    endpoint: &str,
+    token: &str,
+    config: &IntrospectionConfig,
+) -> Result<Map<String, Value>> {
+    let body = format!(
+        "token={}&token_type_hint=access_token",
+        form_urlencode(token)
+    );
+
+    let request = spin_sdk::http::Request::builder()
+        .method(spin_sdk::http::Method::Post)
+        .uri(endpoint)
+        .header("Accept", "application/json")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header(
+            "Authorization",
+            client_basic_auth(&config.client_id, &config.client_secret),
+        )
+        .body(body)
+        .build();
+
+    let response: spin_sdk::http::Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("Failed to call introspection endpoint {endpoint}: {e}"))?;
+
+    if *response.status() != 200 {
+        let status = response.status();
+        return Err(anyhow!("Introspection failed: HTTP {status}"));
+    }
+
+    let introspection: Map<String, Value> = serde_json::from_slice(response.body())?;
+
+    if introspection.get("active").and_then(Value::as_bool) != Some(true) {
+        return Err(anyhow!("Token is not active"));
+    }
+
+    Ok(introspection)
+}
+
+/// Map an introspection response onto `Claims`, checking it against the provider
+fn claims_from_introspection(
+    mut introspection: Map<String, Value>,
+    provider: &dyn AuthProvider,
+    audiences: &[String],
+    now: i64,
+) -> Result<Claims, String> {
+    if let Some(exp) = introspection.get("exp").and_then(Value::as_i64) {
+        if exp <= now {
+            return Err("Token has expired".to_string());
+        }
+    }
+
+    // The issuer is optional in introspection responses, but must match when present
+    match introspection.get("iss").and_then(Value::as_str) {
+        Some(iss) if iss.trim_end_matches('/') != provider.issuer().trim_end_matches('/') => {
+            eprintln!("Introspected token issuer mismatch: {iss}");
+            return Err("Token validation failed".to_string());
+        }
+        Some(_) => {}
+        None => {
+            introspection.insert("iss".to_string(), Value::from(provider.issuer()));
+        }
+    }
+
+    if !audiences.is_empty() {
+        let matches = match introspection.get("aud") {
+            Some(Value::String(value)) => audiences.contains(value),
+            Some(Value::Array(values)) => values
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|value| audiences.iter().any(|aud| aud == value)),
+            _ => false,
+        };
+        if !matches {
+            eprintln!(
+                "Introspected token audience does not include any of {}",
+                audiences.join(", ")
+            );
+            return Err("Token audience is not accepted".to_string());
+        }
+    }
+
+    // Some servers only return `username` for the resource owner
+    if !introspection.contains_key("sub") {
+        let Some(username) = introspection.get("username").cloned() else {
+            eprintln!("Introspection response has neither sub nor username");
+            return Err("Token validation failed".to_string());
+        };
+        introspection.insert("sub".to_string(), username);
+    }
+    introspection
+        .entry("iat".to_string())
+        .or_insert_with(|| Value::from(now));
+    // Without `exp` the token is treated as already expired for caching purposes
+    introspection
+        .entry("exp".to_string())
+        .or_insert_with(|| Value::from(0));
+
+    serde_json::from_value(Value::Object(introspection)).map_err(|e| {
+        eprintln!("Failed to map introspection response to claims: {e}");
+        "Token validation failed".to_string()
+    })
+}
+
+/// Cache key for a provider's token; raw tokens are never kept in memory as cache keys
+fn cache_key(provider: &dyn AuthProvider, token: &str) -> String {
+    sha256_hex(&format!("{}\n{token}", provider.issuer()))
+}
+
+/// When a positive introspection result stops being reused. Revocation is only visible at the
+/// introspection endpoint, so results are kept for at most `cache_ttl` seconds and never past
+/// the token's `exp` (RFC 7662, section 4).
+fn cache_expiry(now: i64, exp: i64, cache_ttl: u64) -> i64 {
+    let ttl = i64::try_from(cache_ttl).unwrap_or(i64::MAX);
+    now.saturating_add(ttl).min(exp)
+}
+
+/// Validate an opaque token through the provider's introspection endpoint
+pub async fn introspect_token(
+    token: &str,
+    provider: &dyn AuthProvider,
+    config: &IntrospectionConfig,
+    audiences: &[String],
+    cache_ttl: u64,
+) -> Result<Claims, String> {
+    let endpoint = config
+        .endpoint
+        .clone()
+        .or_else(|| provider.discovery_metadata("").introspection_endpoint)
+        .ok_or_else(|| {
+            eprintln!(
+                "No introspection endpoint configured for provider '{}'",
+                provider.name()
+            );
+            "Token validation failed".to_string()
+        })?;
+
+    if !provider.allows_url(&endpoint) {
+        eprintln!(
+            "Introspection endpoint {endpoint} is outside the allowed domains of provider '{}'",
+            provider.name()
+        );
+        return Err("Token validation failed".to_string());
+    }
+
+    let key = cache_key(provider, token);
+    let now = unix_now();
+
+    // Check cache first
+    {
+        let cache = INTROSPECTION_CACHE.read().await;
+        if let Some((introspection, expires_at)) = cache.get(&key) {
+            if *expires_at > now {
+                return claims_from_introspection(introspection.clone(), provider, audiences, now);
+            }
+        }
+    }
+
+    let introspection = fetch_introspection(&endpoint, token, config)
+        .await
+        .map_err(|e| {
+            eprintln!("Token introspection failed at {endpoint}: {e}");
+            "Token validation failed".to_string()
+        })?;
+
+    let claims = claims_from_introspection(introspection.clone(), provider, audiences, now)?;
+
+    // Cache positive results briefly, so a revoked token is rejected soon after
+    let expires_at = cache_expiry(now, claims.exp, cache_ttl);
+    if expires_at > now {
+        let mut cache = INTROSPECTION_CACHE.write().await;
+
+        if cache.len() >= MAX_CACHE_SIZE {
+            cache.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        if cache.len() < MAX_CACHE_SIZE {
+            cache.insert(key, (introspection, expires_at));
+        }
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::AuthKitProvider;
+
+    #[test]
+    fn test_form_urlencode() {
+        assert_eq!(form_urlencode("abc-123_~."), "abc-123_~.");
+        assert_eq!(form_urlencode("a b&c=d"), "a+b%26c%3Dd");
+        assert_eq!(form_urlencode("ü"), "%C3%BC");
+        assert_eq!(
+            form_urldecode("a=b+c&d=%C3%BC%3D&e&=f"),
+            vec![
+                ("a".to_string(), "b c".to_string()),
+                ("d".to_string(), "ü=".to_string()),
+                ("e".to_string(), String::new()),
+                (String::new(), "f".to_string()),
+            ]
+        );
+        assert_eq!(
+            form_urldecode(&form_urlencode("x&y z")),
+            vec![("x&y z".to_string(), String::new())]
+        );
+    }
+
+    fn provider() -> AuthKitProvider {
+        AuthKitProvider::new(
+            "https://example.authkit.app".to_string(),
+            None,
+            Some("mcp-api".to_string()),
+        )
+    }
+
+    fn introspection(value: Value) -> Map<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_claims_from_introspection() {
+        let provider = provider();
+        let audiences = vec!["mcp-api".to_string()];
+        let now = 1_700_000_000;
+        let claims = |value: Value| {
+            claims_from_introspection(introspection(value), &provider, &audiences, now)
+        };
+
+        // Issuer is filled in and `username` stands in for a missing subject
+        let mapped = claims(serde_json::json!({
+            "active": true,
+            "username": "alice",
+            "aud": ["other", "mcp-api"],
+            "exp": now + 600
+        }))
+        .unwrap();
+        assert_eq!(mapped.sub, "alice");
+        assert_eq!(mapped.iss, "https://example.authkit.app");
+        assert_eq!(mapped.exp, now + 600);
+
+        // Expired, foreign issuer, foreign audience, or no subject at all
+        assert!(claims(serde_json::json!({ "sub": "a", "aud": "mcp-api", "exp": now })).is_err());
+        assert!(claims(serde_json::json!({
+            "sub": "a",
+            "aud": "mcp-api",
+            "iss": "https://evil.example.com",
+            "exp": now + 600
+        }))
+        .is_err());
+        assert!(
+            claims(serde_json::json!({ "sub": "a", "aud": "other", "exp": now + 600 })).is_err()
+        );
+        assert!(claims(serde_json::json!({ "aud": "mcp-api", "exp": now + 600 })).is_err());
+
+        // Without `exp` the result is accepted once but never cached
+        let mapped = claims(serde_json::json!({ "sub": "a", "aud": "mcp-api" })).unwrap();
+        assert_eq!(mapped.exp, 0);
+        assert!(cache_expiry(now, mapped.exp, 60) <= now);
+    }
+
+    #[test]
+    fn test_cache_expiry_and_key() {
+        let now = 1_700_000_000;
+        // Capped by the cache TTL, then by the token's expiry
+        assert_eq!(cache_expiry(now, now + 3600, 60), now + 60);
+        assert_eq!(cache_expiry(now, now + 30, 60), now + 30);
+        assert_eq!(cache_expiry(now, now + 3600, 0), now);
+        assert_eq!(cache_expiry(now, now + 3600, u64::MAX), now + 3600);
+
+        let other = AuthKitProvider::new("https://other.authkit.app".to_string(), None, None);
+        assert_ne!(cache_key(&provider(), "token"), cache_key(&other, "token"));
+    }
+
+    #[test]
+    fn test_client_basic_auth() {
+        assert_eq!(
+            client_basic_auth("client", "secret"),
+            format!("Basic {}", STANDARD.encode("client:secret"))
+        );
+    }
+}
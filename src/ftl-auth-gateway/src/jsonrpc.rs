@@ -0,0 +1,89 @@
+use serde_json::Value;
+
+/// The parts of a JSON-RPC call the gateway needs for authorization decisions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcCall {
+    pub method: String,
+    /// Tool name for `tools/call` requests
+    pub tool: Option<String>,
+}
+
+impl RpcCall {
+    fn from_value(value: &Value) -> Option<Self> {
+        let method = value.get("method")?.as_str()?.to_string();
+        let tool = if method == "tools/call" {
+            value
+                .get("params")
+                .and_then(|params| params.get("name"))
+                .and_then(Value::as_str)
+                .map(String::from)
+        } else {
+            None
+        };
+        Some(Self { method, tool })
+    }
+}
+
+/// Normalize a tool name the way the MCP gateway routes it, so `delete_repo`, `delete-repo` and
+/// `Delete_Repo` are one tool to every policy
+pub fn normalize_tool_name(name: &str) -> String {
+    name.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Peek at a request body and list the JSON-RPC calls it contains.
+///
+/// Batches yield one entry per call. Bodies that are not JSON-RPC yield nothing.
+pub fn peek_calls(body: &[u8]) -> Vec<RpcCall> {
+    if body.is_empty() {
+        return Vec::new();
+    }
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(batch)) => batch.iter().filter_map(RpcCall::from_value).collect(),
+        Ok(value) => RpcCall::from_value(&value).into_iter().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tool_name() {
+        assert_eq!(normalize_tool_name("delete_repo"), "delete-repo");
+        assert_eq!(normalize_tool_name(" Delete_Repo "), "delete-repo");
+        assert_eq!(normalize_tool_name("delete-repo"), "delete-repo");
+    }
+
+    #[test]
+    fn test_peek_single_call() {
+        let body = br#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"echo"}}"#;
+        assert_eq!(
+            peek_calls(body),
+            vec![RpcCall {
+                method: "tools/call".to_string(),
+                tool: Some("echo".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_peek_batch() {
+        let body = br#"[
+            {"jsonrpc":"2.0","id":1,"method":"tools/list"},
+            {"jsonrpc":"2.0","method":"notifications/initialized"}
+        ]"#;
+        let calls = peek_calls(body);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].method, "tools/list");
+        assert!(calls[1].tool.is_none());
+    }
+
+    #[test]
+    fn test_peek_non_json() {
+        assert!(peek_calls(b"").is_empty());
+        assert!(peek_calls(b"not json").is_empty());
+        assert!(peek_calls(br#"{"result":{}}"#).is_empty());
+    }
+}
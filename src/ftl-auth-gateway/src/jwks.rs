@@ -6,6 +6,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::token_cache;
+
 /// `JWKS` response structure
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct JwksResponse {
@@ -76,8 +78,14 @@ pub async fn fetch_jwks(jwks_uri: &str) -> Result<JwksResponse> {
     let jwks: JwksResponse = serde_json::from_slice(response.body())?;
 
     // Update cache
-    {
+    let rotated = {
         let mut cache = JWKS_CACHE.write().await;
+        let rotated = cache.get(jwks_uri).is_some_and(|(previous, _)| {
+            previous
+                .keys
+                .iter()
+                .any(|key| !jwks.keys.iter().any(|k| k.kid == key.kid))
+        });
 
         // If cache is at max size, remove oldest entry
         if cache.len() >= MAX_CACHE_SIZE {
@@ -95,6 +103,13 @@ pub async fn fetch_jwks(jwks_uri: &str) -> Result<JwksResponse> {
             jwks_uri.to_string(),
             (jwks.clone(), std::time::Instant::now()),
         );
+        rotated
+    };
+
+    // Tokens signed by withdrawn keys must be verified again
+    if rotated {
+        let kids: Vec<&str> = jwks.keys.iter().filter_map(|k| k.kid.as_deref()).collect();
+        token_cache::evict_rotated_keys(jwks_uri, &kids).await;
     }
 
     Ok(jwks)
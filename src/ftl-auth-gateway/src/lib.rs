@@ -1,109 +1,174 @@
-use anyhow::Result;
-use spin_sdk::http::{IntoResponse, Request};
+use spin_sdk::http::{Request, Response, ResponseOutparam};
 
+mod api_keys;
 mod auth;
+mod claims;
 mod config;
+mod cors;
+mod dev;
+mod discovery;
+mod domains;
+mod dpop;
+mod forwarded;
 mod handlers;
+mod headers;
+mod introspection;
+mod jsonrpc;
 mod jwks;
 mod logging;
 mod metadata;
+mod origin;
+mod policy;
 mod providers;
 mod proxy;
+mod registration;
+mod replay;
+mod revocation;
+mod token_cache;
+mod token_exchange;
+mod trace_context;
+mod validation;
 
 use config::GatewayConfig;
-use handlers::{handle_authenticated_request, handle_cors_preflight, handle_metadata_endpoints};
-use logging::{get_trace_id, Logger};
+use handlers::{
+    handle_authenticated_request, handle_cors_preflight, handle_local_provider_endpoints,
+    handle_metadata_endpoints, handle_origin_validation, handle_registration_endpoint,
+    handle_revocation_endpoints,
+};
+use logging::Logger;
+use proxy::GatewayResponse;
+use std::collections::BTreeMap;
+use trace_context::TraceContext;
 
 /// Main entry point for the authentication gateway
 #[spin_sdk::http_component]
-async fn handle_request(req: Request) -> Result<impl IntoResponse> {
+async fn handle_request(req: Request, response_out: ResponseOutparam) {
     // Load gateway configuration
-    let config = GatewayConfig::from_spin_vars()?;
+    let response = match GatewayConfig::from_spin_vars() {
+        Ok(config) => {
+            // Every response carries the CORS headers for the request's origin
+            let origin = cors::request_origin(&req).map(String::from);
+            let mut response = route_request(req, &config).await;
+            for (name, value) in config.cors.response_headers(origin.as_deref()) {
+                response.set_header(name, value);
+            }
+            response
+        }
+        Err(e) => {
+            eprintln!("Invalid gateway configuration: {e:#}");
+            Response::builder()
+                .status(500)
+                .body("Invalid gateway configuration")
+                .build()
+                .into()
+        }
+    };
+
+    if let Err(e) = response.send(response_out).await {
+        eprintln!("Failed to send response: {e:#}");
+    }
+}
 
+/// Route a request to the metadata, registration or authenticated MCP handlers
+async fn route_request(req: Request, config: &GatewayConfig) -> GatewayResponse {
     // Check if authentication is enabled right at the entry point
     if !config.enabled {
         // Bypass everything and forward directly to MCP gateway
-        let trace_id = get_trace_id(&req, &config.trace_id_header);
-        let logger = Logger::new(&trace_id);
+        let trace = TraceContext::from_request(&req, &config.trace_id_header);
+        let logger = Logger::new(&trace.log_id);
 
         logger
             .info("Authentication disabled, forwarding request directly")
             .emit();
 
+        if let Some(response) = handle_origin_validation(&req, config, &logger) {
+            return response.into();
+        }
+
         let auth_config = auth::AuthConfig {
             mcp_gateway_url: config.mcp_gateway_url.clone(),
+            headers: config.headers.clone(),
+            tenant_secret: config.tenant_secret.clone(),
         };
 
-        match proxy::forward_to_mcp_gateway(req, &auth_config, None, &trace_id).await {
-            Ok(response) => return Ok(response),
+        match proxy::forward_to_mcp_gateway(req, &auth_config, None, &BTreeMap::new(), &trace).await
+        {
+            Ok(response) => return response,
             Err(e) => {
                 logger
                     .error("Failed to forward request to MCP gateway")
                     .field("error", &e)
                     .emit();
-                return Ok(spin_sdk::http::Response::builder()
+                return Response::builder()
                     .status(502)
                     .body(format!("Gateway error: {e}"))
-                    .build());
+                    .build()
+                    .into();
             }
         }
     }
 
-    // Authentication is enabled, proceed with normal auth flow
-    let registry = config.build_registry();
-    let provider = registry.providers().first();
+    // Continue or start the W3C trace; its trace ID tags every log line
+    let trace = TraceContext::from_request(&req, &config.trace_id_header);
+    let logger = Logger::new(&trace.log_id);
 
-    // Extract trace ID for structured logging
-    let trace_id = get_trace_id(&req, &config.trace_id_header);
-    let logger = Logger::new(&trace_id);
+    // Authentication is enabled, proceed with normal auth flow
+    let registry = match config.build_registry().await {
+        Ok(registry) => registry,
+        Err(e) => {
+            logger
+                .error("Failed to load authentication provider")
+                .field("error", format!("{e:#}"))
+                .emit();
+            return Response::builder()
+                .status(502)
+                .body("Authentication provider unavailable")
+                .build()
+                .into();
+        }
+    };
 
     let path = req.path();
-    let method = req.method();
-
-    // Extract host header for metadata endpoints
-    // Check multiple headers that might contain the host
-    let host = req
-        .headers()
-        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
-        .and_then(|(_, value)| value.as_str())
-        .map(String::from)
-        .or_else(|| {
-            req.headers()
-                .find(|(name, _)| name.eq_ignore_ascii_case("x-forwarded-host"))
-                .and_then(|(_, value)| value.as_str())
-                .map(String::from)
-        })
-        .or_else(|| {
-            req.headers()
-                .find(|(name, _)| name.eq_ignore_ascii_case("x-original-host"))
-                .and_then(|(_, value)| value.as_str())
-                .map(String::from)
-        });
+
+    // Determine the client-facing host, honoring forwarding headers only from trusted proxies
+    let host = forwarded::request_origin(&req, &config.trusted_proxies).host;
+
+    // Handle CORS preflight
+    if let Some(response) = handle_cors_preflight(&req, config) {
+        return response.into();
+    }
 
     // Handle metadata endpoints
-    if let Some(response) = handle_metadata_endpoints(
-        path,
-        provider.map(std::convert::AsRef::as_ref),
-        host.as_deref(),
-        &req,
-        &logger,
-    ) {
-        return Ok(response);
+    if let Some(response) =
+        handle_metadata_endpoints(path, config, &registry, host.as_deref(), &req, &logger).await
+    {
+        return response.into();
     }
 
-    // Handle CORS preflight
-    if let Some(response) = handle_cors_preflight(method) {
-        return Ok(response);
+    // Handle keys, authorization and tokens served by a local provider
+    if let Some(response) = handle_local_provider_endpoints(path, &registry, &req, &logger) {
+        return response.into();
+    }
+
+    // Handle dynamic client registration
+    if let Some(response) =
+        handle_registration_endpoint(path, config, &registry, &req, &logger).await
+    {
+        return response.into();
+    }
+
+    // Handle revocation management and back-channel logout, which carry their own credentials
+    if let Some(response) =
+        handle_revocation_endpoints(path, config, &registry, &req, &logger).await
+    {
+        return response.into();
+    }
+
+    // Reject browser requests from untrusted origins before authenticating
+    if let Some(response) = handle_origin_validation(&req, config, &logger) {
+        return response.into();
     }
 
     // All other requests require authentication
-    Ok(handle_authenticated_request(
-        req,
-        &config,
-        provider.map(std::convert::AsRef::as_ref),
-        host.as_deref(),
-        &trace_id,
-        &logger,
-    )
-    .await)
+    handle_authenticated_request(req, config, &registry, host.as_deref(), &trace, &logger).await
 }
@@ -1,27 +1,5 @@
-use spin_sdk::http::Request;
 use std::fmt;
 
-/// Generate or extract trace ID from request
-pub fn get_trace_id(req: &Request, header_name: &str) -> String {
-    req.headers()
-        .find(|(name, _)| name.eq_ignore_ascii_case(header_name))
-        .and_then(|(_, value)| value.as_str())
-        .map_or_else(
-            || {
-                // Generate a simple trace ID if not provided
-                // Note: std::process::id() is not available in WASI
-                format!(
-                    "gen-{:x}",
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map(|d| d.as_millis())
-                        .unwrap_or(0)
-                )
-            },
-            String::from,
-        )
-}
-
 /// Structured log entry
 pub struct LogEntry<'a> {
     trace_id: &'a str,
@@ -1,60 +1,97 @@
+use serde_json::{Map, Value};
 use spin_sdk::http::{Request, Response};
 
-use crate::providers::AuthProvider;
+use crate::{
+    config::GatewayConfig,
+    discovery, forwarded,
+    providers::{AuthProvider, ProviderRegistry},
+};
 
-/// Handle OAuth metadata endpoints
-pub fn handle_metadata_request(
+/// Handle OAuth metadata endpoints. Every provider is listed as an authorization server; the
+/// primary provider's metadata is served for the authorization server document.
+pub async fn handle_metadata_request(
     path: &str,
+    config: &GatewayConfig,
+    registry: &ProviderRegistry,
     provider: &dyn AuthProvider,
     host: Option<&str>,
     req: &Request,
 ) -> Response {
-    // Determine resource URL first
-    let resource_url = determine_resource_url(host, req);
+    // Determine base and resource URLs first
+    let base_url = determine_base_url(config, host, req);
+    let resource_url = determine_resource_url(config, host, req);
+    eprintln!("Returning resource URL: {resource_url}");
 
     match path {
         "/.well-known/oauth-protected-resource" => {
-            let metadata = serde_json::json!({
+            let mut metadata = serde_json::json!({
                 "resource": resource_url,
-                "authorization_servers": [provider.issuer()],
+                "authorization_servers": registry.issuers(),
                 "bearer_methods_supported": ["header"]
             });
 
+            // Advertise DPoP support and whether bearer tokens are still accepted (RFC 9449)
+            let dpop_algs = config.dpop.signing_alg_values();
+            if !dpop_algs.is_empty() {
+                if let Some(obj) = metadata.as_object_mut() {
+                    obj.insert(
+                        "dpop_signing_alg_values_supported".to_string(),
+                        serde_json::json!(dpop_algs),
+                    );
+                    obj.insert(
+                        "dpop_bound_access_tokens_required".to_string(),
+                        Value::from(config.dpop.required),
+                    );
+                }
+            }
+
+            // Advertise the scopes the gateway enforces
+            let scopes_supported = config.scope_policy.scopes_supported();
+            if !scopes_supported.is_empty() {
+                if let Some(obj) = metadata.as_object_mut() {
+                    obj.insert(
+                        "scopes_supported".to_string(),
+                        serde_json::json!(scopes_supported),
+                    );
+                }
+            }
+
             Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
                 .body(metadata.to_string())
                 .build()
         }
         "/.well-known/oauth-authorization-server" => {
-            // Return provider-specific metadata
-            let discovery = provider.discovery_metadata(&resource_url);
-            let metadata = serde_json::json!({
-                "issuer": discovery.issuer,
-                "authorization_endpoint": discovery.authorization_endpoint,
-                "token_endpoint": discovery.token_endpoint,
-                "jwks_uri": discovery.jwks_uri,
-                "userinfo_endpoint": discovery.userinfo_endpoint,
-                "revocation_endpoint": discovery.revocation_endpoint,
-                "introspection_endpoint": discovery.introspection_endpoint,
-                "response_types_supported": ["code"],
-                "response_modes_supported": ["query"],
-                "grant_types_supported": ["authorization_code", "refresh_token"],
-                "code_challenge_methods_supported": ["S256"],
-                "token_endpoint_auth_methods_supported": [
-                    "none",
-                    "client_secret_post",
-                    "client_secret_basic"
-                ],
-                "scopes_supported": ["email", "offline_access", "openid", "profile"]
-            });
+            // Serve the provider's own metadata; the static document is only a fallback
+            let mut metadata = if provider.is_local() {
+                fallback_authorization_server_metadata(provider, &resource_url)
+            } else {
+                match discovery::fetch_authorization_server_metadata(provider.issuer()).await {
+                    Ok(document) => document,
+                    Err(e) => {
+                        eprintln!(
+                            "Failed to fetch authorization server metadata, using fallback: {e}"
+                        );
+                        fallback_authorization_server_metadata(provider, &resource_url)
+                    }
+                }
+            };
+
+            // Clients register through the gateway when registration is enabled
+            if config.registration.is_enabled() {
+                metadata.insert(
+                    "registration_endpoint".to_string(),
+                    Value::from(format!("{base_url}/register")),
+                );
+            }
+
+            apply_overrides(&mut metadata, &config.metadata_overrides);
 
             Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
-                .body(metadata.to_string())
+                .body(Value::Object(metadata).to_string())
                 .build()
         }
         _ => Response::builder()
@@ -64,39 +101,129 @@ pub fn handle_metadata_request(
     }
 }
 
-/// Determine the resource URL based on request headers
-fn determine_resource_url(host: Option<&str>, req: &Request) -> String {
-    host.map_or_else(
-        || {
-            eprintln!("No host header found, using default");
-            "http://127.0.0.1:3000/mcp".to_string() // Default fallback
-        },
-        |h| {
-            // Check X-Forwarded-Proto header for protocol
-            let forwarded_proto = req
-                .headers()
-                .find(|(name, _)| name.eq_ignore_ascii_case("x-forwarded-proto"))
-                .and_then(|(_, value)| value.as_str());
-
-            // Determine protocol
-            let protocol = forwarded_proto.unwrap_or_else(|| {
-                if h.contains(":443") || h.contains(".fermyon.tech") || h.contains(".fermyon.cloud")
-                {
-                    "https"
-                } else if h.contains(":80")
-                    || h.starts_with("localhost")
-                    || h.starts_with("127.0.0.1")
-                {
-                    "http"
-                } else {
-                    // Default to https for production domains
-                    "https"
-                }
-            });
+/// Metadata derived from the provider configuration, used when the issuer's document is unavailable
+fn fallback_authorization_server_metadata(
+    provider: &dyn AuthProvider,
+    resource_url: &str,
+) -> Map<String, Value> {
+    let discovery = provider.discovery_metadata(resource_url);
+    let metadata = serde_json::json!({
+        "issuer": discovery.issuer,
+        "authorization_endpoint": discovery.authorization_endpoint,
+        "token_endpoint": discovery.token_endpoint,
+        "jwks_uri": discovery.jwks_uri,
+        "userinfo_endpoint": discovery.userinfo_endpoint,
+        "revocation_endpoint": discovery.revocation_endpoint,
+        "introspection_endpoint": discovery.introspection_endpoint,
+        "response_types_supported": ["code"],
+        "response_modes_supported": ["query"],
+        "grant_types_supported": ["authorization_code", "refresh_token"],
+        "code_challenge_methods_supported": ["S256"],
+        "token_endpoint_auth_methods_supported": [
+            "none",
+            "client_secret_post",
+            "client_secret_basic"
+        ],
+        "scopes_supported": ["email", "offline_access", "openid", "profile"]
+    });
+
+    match metadata {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
 
-            let url = format!("{protocol}://{h}/mcp");
-            eprintln!("Returning resource URL: {url}");
-            url
-        },
+/// Apply configured overrides on top of a metadata document; `null` removes a field
+fn apply_overrides(metadata: &mut Map<String, Value>, overrides: &Map<String, Value>) {
+    for (key, value) in overrides {
+        if value.is_null() {
+            metadata.remove(key);
+        } else {
+            metadata.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// Determine the canonical resource URL of the MCP endpoint (RFC 8707).
+///
+/// A configured canonical URL wins over one derived from request headers, so tokens can be
+/// bound to a stable audience.
+pub fn determine_resource_url(config: &GatewayConfig, host: Option<&str>, req: &Request) -> String {
+    config.resource_url.clone().unwrap_or_else(|| {
+        format!(
+            "{}{}",
+            determine_base_url(config, host, req),
+            config.mcp_path
+        )
+    })
+}
+
+/// URL of the protected resource metadata document, for `WWW-Authenticate` challenges
+pub fn resource_metadata_url(config: &GatewayConfig, host: Option<&str>, req: &Request) -> String {
+    format!(
+        "{}/.well-known/oauth-protected-resource",
+        determine_base_url(config, host, req)
     )
 }
+
+/// Determine the public base URL of the gateway.
+///
+/// A configured public URL is used as-is. Otherwise the scheme comes from a trusted proxy,
+/// then from the URL Spin received the request on.
+pub fn determine_base_url(config: &GatewayConfig, host: Option<&str>, req: &Request) -> String {
+    if let Some(public_url) = &config.public_url {
+        return public_url.clone();
+    }
+
+    let Some(host) = host else {
+        eprintln!("No host header found, using default");
+        return "http://127.0.0.1:3000".to_string(); // Default fallback
+    };
+
+    let protocol = forwarded::request_origin(req, &config.trusted_proxies)
+        .proto
+        .or_else(|| forwarded::spin_scheme(req).map(String::from))
+        .unwrap_or_else(|| {
+            if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
+                "http".to_string()
+            } else {
+                // Default to https for production domains
+                "https".to_string()
+            }
+        });
+
+    format!("{protocol}://{host}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut metadata = serde_json::json!({
+            "issuer": "https://example.authkit.app",
+            "scopes_supported": ["openid"],
+            "userinfo_endpoint": "https://example.authkit.app/oauth2/userinfo"
+        })
+        .as_object()
+        .cloned()
+        .unwrap();
+        let overrides = serde_json::json!({
+            "scopes_supported": ["openid", "mcp:tools"],
+            "userinfo_endpoint": null
+        })
+        .as_object()
+        .cloned()
+        .unwrap();
+
+        apply_overrides(&mut metadata, &overrides);
+
+        assert_eq!(
+            metadata.get("scopes_supported"),
+            Some(&serde_json::json!(["openid", "mcp:tools"]))
+        );
+        assert!(!metadata.contains_key("userinfo_endpoint"));
+        assert!(metadata.contains_key("issuer"));
+    }
+}
@@ -0,0 +1,108 @@
+use ftl_origin::{is_loopback_origin, origin_matches};
+
+/// Scheme and authority of a URL (`https://host:port`), lowercased
+fn url_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    Some(format!("{scheme}://{authority}").to_ascii_lowercase())
+}
+
+/// Validate a request's `Origin` header against DNS-rebinding attacks.
+///
+/// Requests without an `Origin` (non-browser clients) are allowed. Otherwise the origin must
+/// match the allowlist (`*` disables the check) or one of the gateway's configured public
+/// URLs. With no allowlist configured, loopback origins are accepted so local development
+/// under `spin up` works out of the box. The `Host` header is never trusted here, since a
+/// rebound domain controls it.
+pub fn is_origin_allowed(
+    origin: Option<&str>,
+    allowed_origins: &[String],
+    public_urls: &[&str],
+) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+
+    if allowed_origins
+        .iter()
+        .any(|pattern| origin_matches(pattern, origin))
+    {
+        return true;
+    }
+
+    let origin_lower = origin.to_ascii_lowercase();
+    if public_urls
+        .iter()
+        .filter_map(|url| url_origin(url))
+        .any(|public| public == origin_lower)
+    {
+        return true;
+    }
+
+    allowed_origins.is_empty() && is_loopback_origin(origin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_origin_is_allowed() {
+        assert!(is_origin_allowed(None, &[], &[]));
+    }
+
+    #[test]
+    fn test_loopback_defaults() {
+        assert!(is_origin_allowed(Some("http://localhost:6274"), &[], &[]));
+        assert!(is_origin_allowed(Some("http://127.0.0.1:3000"), &[], &[]));
+        assert!(is_origin_allowed(Some("http://[::1]:3000"), &[], &[]));
+        assert!(!is_origin_allowed(
+            Some("http://evil.example.com:3000"),
+            &[],
+            &[]
+        ));
+        assert!(!is_origin_allowed(
+            Some("http://localhost.evil.com"),
+            &[],
+            &[]
+        ));
+
+        // An explicit allowlist replaces the loopback defaults
+        let allowed = vec!["https://app.example.com".to_string()];
+        assert!(!is_origin_allowed(
+            Some("http://localhost:6274"),
+            &allowed,
+            &[]
+        ));
+        assert!(is_origin_allowed(
+            Some("https://app.example.com"),
+            &allowed,
+            &[]
+        ));
+    }
+
+    #[test]
+    fn test_public_url_origin() {
+        let public_urls = ["https://MCP.example.com/mcp"];
+        assert!(is_origin_allowed(
+            Some("https://mcp.example.com"),
+            &[],
+            &public_urls
+        ));
+        assert!(!is_origin_allowed(
+            Some("http://mcp.example.com"),
+            &[],
+            &public_urls
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_disables_check() {
+        let allowed = vec!["*".to_string()];
+        assert!(is_origin_allowed(
+            Some("https://anything.example"),
+            &allowed,
+            &[]
+        ));
+    }
+}
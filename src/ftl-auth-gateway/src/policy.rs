@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::jsonrpc::{normalize_tool_name, RpcCall};
+
+/// Scopes required per MCP method and per tool name
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScopePolicy {
+    /// Required scopes keyed by JSON-RPC method (e.g. `tools/call`)
+    #[serde(default)]
+    pub methods: HashMap<String, Vec<String>>,
+    /// Required scopes keyed by tool name, checked for `tools/call` under any spelling the MCP
+    /// gateway routes to the same tool
+    #[serde(default)]
+    pub tools: HashMap<String, Vec<String>>,
+}
+
+impl ScopePolicy {
+    /// Whether any scope requirement is configured
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty() && self.tools.is_empty()
+    }
+
+    /// All scopes required by the given calls, sorted and deduplicated
+    pub fn required_scopes(&self, calls: &[RpcCall]) -> Vec<String> {
+        let mut required = BTreeSet::new();
+        for call in calls {
+            if let Some(scopes) = self.methods.get(&call.method) {
+                required.extend(scopes.iter().cloned());
+            }
+            if let Some(tool) = call.tool.as_deref().map(normalize_tool_name) {
+                required.extend(
+                    self.tools
+                        .iter()
+                        .filter(|(name, _)| normalize_tool_name(name) == tool)
+                        .flat_map(|(_, scopes)| scopes.iter().cloned()),
+                );
+            }
+        }
+        required.into_iter().collect()
+    }
+
+    /// Required scopes the token was not granted
+    pub fn missing_scopes(&self, calls: &[RpcCall], granted: &[String]) -> Vec<String> {
+        self.required_scopes(calls)
+            .into_iter()
+            .filter(|scope| !granted.contains(scope))
+            .collect()
+    }
+
+    /// Every scope mentioned by the policy, for `scopes_supported` metadata
+    pub fn scopes_supported(&self) -> Vec<String> {
+        self.methods
+            .values()
+            .chain(self.tools.values())
+            .flatten()
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Calls clients may make without credentials
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnonymousPolicy {
+    /// JSON-RPC methods allowed anonymously; a trailing `*` matches a prefix
+    /// (`notifications/*`)
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Tools that may be called anonymously through `tools/call`, under any spelling the MCP
+    /// gateway routes to them
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+impl AnonymousPolicy {
+    /// Whether any anonymous access is configured
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty() && self.tools.is_empty()
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|pattern| {
+            pattern
+                .strip_suffix('*')
+                .map_or(pattern == method, |prefix| method.starts_with(prefix))
+        })
+    }
+
+    fn allows_call(&self, call: &RpcCall) -> bool {
+        // `tools/call` is governed by the tool list alone
+        call.tool.as_deref().map_or_else(
+            || call.method != "tools/call" && self.allows_method(&call.method),
+            |tool| {
+                let tool = normalize_tool_name(tool);
+                self.tools
+                    .iter()
+                    .any(|allowed| normalize_tool_name(allowed) == tool)
+            },
+        )
+    }
+
+    /// Whether every call may be made anonymously. Requests that are not JSON-RPC calls, such
+    /// as the SSE stream, always need credentials.
+    pub fn allows(&self, calls: &[RpcCall]) -> bool {
+        !calls.is_empty() && calls.iter().all(|call| self.allows_call(call))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ScopePolicy {
+        serde_json::from_value(serde_json::json!({
+            "methods": { "tools/call": ["mcp:call"], "tools/list": ["mcp:read"] },
+            "tools": { "delete_repo": ["repo:admin", "mcp:call"] }
+        }))
+        .unwrap()
+    }
+
+    fn call(method: &str, tool: Option<&str>) -> RpcCall {
+        RpcCall {
+            method: method.to_string(),
+            tool: tool.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_required_scopes() {
+        let policy = policy();
+        assert_eq!(
+            policy.required_scopes(&[call("tools/call", Some("delete_repo"))]),
+            vec!["mcp:call", "repo:admin"]
+        );
+        assert_eq!(
+            policy.required_scopes(&[call("tools/call", Some("echo"))]),
+            vec!["mcp:call"]
+        );
+        assert!(policy.required_scopes(&[call("ping", None)]).is_empty());
+    }
+
+    #[test]
+    fn test_required_scopes_tool_aliases() {
+        // The MCP gateway routes every spelling to the same component
+        let policy = policy();
+        for alias in ["delete-repo", "Delete_Repo", " DELETE-REPO "] {
+            assert_eq!(
+                policy.required_scopes(&[call("tools/call", Some(alias))]),
+                vec!["mcp:call", "repo:admin"],
+                "{alias}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_scopes() {
+        let policy = policy();
+        let granted = vec!["mcp:call".to_string()];
+        assert_eq!(
+            policy.missing_scopes(&[call("tools/call", Some("delete_repo"))], &granted),
+            vec!["repo:admin"]
+        );
+        assert!(policy
+            .missing_scopes(&[call("tools/call", Some("echo"))], &granted)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_scopes_supported() {
+        assert_eq!(
+            policy().scopes_supported(),
+            vec!["mcp:call", "mcp:read", "repo:admin"]
+        );
+    }
+
+    #[test]
+    fn test_anonymous_policy() {
+        let policy: AnonymousPolicy = serde_json::from_value(serde_json::json!({
+            "methods": ["initialize", "tools/list", "notifications/*"],
+            "tools": ["echo"]
+        }))
+        .unwrap();
+
+        assert!(policy.allows(&[call("initialize", None)]));
+        assert!(policy.allows(&[
+            call("notifications/initialized", None),
+            call("tools/list", None)
+        ]));
+        assert!(policy.allows(&[call("tools/call", Some("echo"))]));
+        assert!(policy.allows(&[call("tools/call", Some(" ECHO "))]));
+
+        assert!(!policy.allows(&[call("tools/call", Some("delete_repo"))]));
+        assert!(!policy.allows(&[call("tools/call", None)]));
+        assert!(!policy.allows(&[call("tools/list", None), call("resources/read", None)]));
+        assert!(!policy.allows(&[]));
+        assert!(!AnonymousPolicy::default().allows(&[call("initialize", None)]));
+    }
+}
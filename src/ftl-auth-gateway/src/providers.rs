@@ -1,4 +1,12 @@
+use jsonwebtoken::DecodingKey;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use spin_sdk::http::{Request, Response};
+
+use crate::{
+    claims::ClaimMappings, domains, introspection::IntrospectionConfig,
+    token_exchange::TokenExchangeConfig, validation::ValidationPolicy,
+};
 
 /// Trait for authentication providers
 pub trait AuthProvider: Send + Sync {
@@ -11,32 +19,115 @@ pub trait AuthProvider: Send + Sync {
     /// Get the audience for this provider (optional)
     fn audience(&self) -> Option<&str>;
 
-    /// Get allowed domains for JWKS fetching
-    #[allow(dead_code)]
+    /// Get the accepted audiences (the configured audience is a comma-separated list)
+    fn audiences(&self) -> Vec<&str> {
+        self.audience()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|aud| !aud.is_empty())
+            .collect()
+    }
+
+    /// Get allowed domains for JWKS, discovery and introspection fetches
     fn allowed_domains(&self) -> Vec<&str>;
 
+    /// Whether an outbound URL may be fetched for this provider
+    fn allows_url(&self, url: &str) -> bool {
+        domains::is_url_allowed(url, self.issuer(), &self.allowed_domains())
+    }
+
     /// Get discovery metadata for OAuth 2.0
     fn discovery_metadata(&self, resource_url: &str) -> DiscoveryMetadata;
 
-    /// Extract the provider-specific user context from claims
+    /// Get the settings shared by all provider types
+    fn options(&self) -> &ProviderOptions;
+
+    /// Get the introspection settings used for opaque tokens (optional)
+    fn introspection(&self) -> Option<&IntrospectionConfig> {
+        self.options().introspection.as_ref()
+    }
+
+    /// Get the token exchange settings for downstream tool tokens (optional)
+    fn token_exchange(&self) -> Option<&TokenExchangeConfig> {
+        self.options().token_exchange.as_ref()
+    }
+
+    /// Extract the user context from claims, applying the provider's claim mappings
     fn extract_user_context(&self, claims: &crate::auth::Claims) -> UserContext {
-        UserContext {
+        let mut user_context = UserContext {
             id: claims.sub.clone(),
             email: claims.email.clone(),
             provider: self.name().to_string(),
+            ..UserContext::default()
+        };
+        if let Ok(claims) = serde_json::to_value(claims) {
+            self.options()
+                .claim_mappings
+                .apply(&claims, &mut user_context);
         }
+        user_context
+    }
+
+    /// `typ` header its access tokens carry, for a provider that signs other JWTs with the same
+    /// key; tokens of any other type are rejected
+    fn access_token_type(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the provider runs inside the gateway, with nothing to fetch over the network
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Get a verification key held by the provider itself, instead of fetching its JWKS
+    fn decoding_key(&self, _kid: &str) -> Option<DecodingKey> {
+        None
+    }
+
+    /// Handle an endpoint the provider serves from the gateway
+    fn handle_local_request(&self, _path: &str, _req: &Request) -> Option<Response> {
+        None
     }
 
     /// Get the provider name
     fn name(&self) -> &str;
 }
 
+/// Settings shared by every provider type
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProviderOptions {
+    /// Validate opaque access tokens through RFC 7662 introspection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub introspection: Option<IntrospectionConfig>,
+    /// Where roles, groups, tenant and other attributes are read from in token claims
+    #[serde(default)]
+    pub claim_mappings: ClaimMappings,
+    /// Leeway, required claims, maximum age and authorized clients for the provider's tokens
+    #[serde(default)]
+    pub validation: ValidationPolicy,
+    /// Exchange the client's token for downstream tokens scoped to each tool (RFC 8693)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_exchange: Option<TokenExchangeConfig>,
+}
+
 /// User context extracted from JWT claims
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserContext {
     pub id: String,
     pub email: Option<String>,
     pub provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Values of the configured attribute mappings
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub attributes: Map<String, Value>,
 }
 
 /// OAuth 2.0 discovery metadata
@@ -49,6 +140,7 @@ pub struct DiscoveryMetadata {
     pub userinfo_endpoint: Option<String>,
     pub revocation_endpoint: Option<String>,
     pub introspection_endpoint: Option<String>,
+    pub registration_endpoint: Option<String>,
 }
 
 /// Generic OIDC provider configuration
@@ -61,18 +153,33 @@ pub struct OidcProviderConfig {
     pub authorization_endpoint: String,
     pub token_endpoint: String,
     pub userinfo_endpoint: Option<String>,
-    #[allow(dead_code)]
+    #[serde(default)]
+    pub revocation_endpoint: Option<String>,
+    #[serde(default)]
+    pub introspection_endpoint: Option<String>,
+    #[serde(default)]
+    pub registration_endpoint: Option<String>,
     pub allowed_domains: Vec<String>,
 }
 
 /// Generic OIDC provider implementation
 pub struct OidcProvider {
     config: OidcProviderConfig,
+    options: ProviderOptions,
 }
 
 impl OidcProvider {
     pub fn new(config: OidcProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            options: ProviderOptions::default(),
+        }
+    }
+
+    /// Attach the shared provider settings
+    pub fn with_options(mut self, options: ProviderOptions) -> Self {
+        self.options = options;
+        self
     }
 }
 
@@ -104,11 +211,16 @@ impl AuthProvider for OidcProvider {
             token_endpoint: self.config.token_endpoint.clone(),
             jwks_uri: self.config.jwks_uri.clone(),
             userinfo_endpoint: self.config.userinfo_endpoint.clone(),
-            revocation_endpoint: None,
-            introspection_endpoint: None,
+            revocation_endpoint: self.config.revocation_endpoint.clone(),
+            introspection_endpoint: self.config.introspection_endpoint.clone(),
+            registration_endpoint: self.config.registration_endpoint.clone(),
         }
     }
 
+    fn options(&self) -> &ProviderOptions {
+        &self.options
+    }
+
     fn name(&self) -> &str {
         &self.config.name
     }
@@ -119,17 +231,28 @@ pub struct AuthKitProvider {
     issuer: String,
     jwks_uri: String,
     audience: Option<String>,
+    options: ProviderOptions,
 }
 
 impl AuthKitProvider {
+    /// Hosts every `AuthKit` tenant is served from
+    pub const ALLOWED_DOMAINS: &'static [&'static str] = &["*.authkit.app"];
+
     pub fn new(issuer: String, jwks_uri: Option<String>, audience: Option<String>) -> Self {
         let jwks_uri = jwks_uri.unwrap_or_else(|| format!("{issuer}/oauth2/jwks"));
         Self {
             issuer,
             jwks_uri,
             audience,
+            options: ProviderOptions::default(),
         }
     }
+
+    /// Attach the shared provider settings
+    pub fn with_options(mut self, options: ProviderOptions) -> Self {
+        self.options = options;
+        self
+    }
 }
 
 impl AuthProvider for AuthKitProvider {
@@ -146,7 +269,7 @@ impl AuthProvider for AuthKitProvider {
     }
 
     fn allowed_domains(&self) -> Vec<&str> {
-        vec!["*.authkit.app"]
+        Self::ALLOWED_DOMAINS.to_vec()
     }
 
     fn discovery_metadata(&self, _resource_url: &str) -> DiscoveryMetadata {
@@ -158,9 +281,14 @@ impl AuthProvider for AuthKitProvider {
             userinfo_endpoint: Some(format!("{}/oauth2/userinfo", self.issuer)),
             revocation_endpoint: Some(format!("{}/oauth2/revoke", self.issuer)),
             introspection_endpoint: Some(format!("{}/oauth2/introspect", self.issuer)),
+            registration_endpoint: Some(format!("{}/oauth2/register", self.issuer)),
         }
     }
 
+    fn options(&self) -> &ProviderOptions {
+        &self.options
+    }
+
     fn name(&self) -> &'static str {
         "authkit"
     }
@@ -183,7 +311,6 @@ impl ProviderRegistry {
     }
 
     /// Find a provider by issuer
-    #[allow(dead_code)]
     pub fn find_by_issuer(&self, issuer: &str) -> Option<&dyn AuthProvider> {
         self.providers
             .iter()
@@ -196,13 +323,15 @@ impl ProviderRegistry {
         &self.providers
     }
 
-    /// Get all allowed domains across all providers
-    #[allow(dead_code)]
-    pub fn all_allowed_domains(&self) -> Vec<&str> {
-        self.providers
-            .iter()
-            .flat_map(|p| p.allowed_domains())
-            .collect()
+    /// The first configured provider, which serves authorization server metadata and
+    /// client registration
+    pub fn primary(&self) -> Option<&dyn AuthProvider> {
+        self.providers.first().map(std::convert::AsRef::as_ref)
+    }
+
+    /// Issuers of all providers
+    pub fn issuers(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.issuer()).collect()
     }
 }
 
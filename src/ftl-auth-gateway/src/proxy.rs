@@ -1,149 +1,294 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
 use serde_json::Value;
-use spin_sdk::http::{Request, Response};
+use spin_sdk::http::{
+    Fields, IncomingResponse, OutgoingResponse, Request, Response, ResponseOutparam,
+};
+use std::collections::BTreeMap;
 
 use crate::{
     auth::{AuthConfig, Claims},
+    headers::{self, TENANT_HEADER, TENANT_SIGNATURE_HEADER, TOOL_TOKENS_HEADER},
     providers::UserContext,
+    trace_context::{TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER},
 };
 
-/// Forward authenticated requests to the MCP gateway
-#[allow(clippy::too_many_lines)]
-pub async fn forward_to_mcp_gateway(
-    req: Request,
-    config: &AuthConfig,
-    auth_context: Option<(Claims, UserContext)>,
-    trace_id: &str,
-) -> Result<Response> {
-    // Parse the request body to potentially inject user info
-    let body = req.body();
-    let mut request_data: Value = if body.is_empty() {
-        // If there's no body, we shouldn't forward an empty object
-        // Let's just forward the request as-is
-        eprintln!("Warning: Empty request body received");
-        serde_json::json!(null)
-    } else {
-        match serde_json::from_slice(body) {
-            Ok(data) => data,
-            Err(e) => {
-                eprintln!("Failed to parse request body as JSON: {e}");
-                let body_str = String::from_utf8_lossy(body);
-                eprintln!("Request body: {body_str:?}");
-                return Err(anyhow::anyhow!("Invalid JSON in request body: {e}"));
-            }
+/// Body of a response sent to the client
+enum ResponseBody {
+    /// A body held in memory
+    Full(Vec<u8>),
+    /// An MCP gateway response body, streamed through chunk by chunk
+    Stream(IncomingResponse),
+}
+
+/// A response to send to the client, either built by the gateway or streamed from the MCP
+/// gateway
+pub struct GatewayResponse {
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    body: ResponseBody,
+}
+
+impl From<Response> for GatewayResponse {
+    fn from(response: Response) -> Self {
+        let headers = response
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect();
+        Self {
+            status: *response.status(),
+            headers,
+            body: ResponseBody::Full(response.into_body()),
         }
-    };
+    }
+}
+
+impl GatewayResponse {
+    /// Set a header, replacing any existing values
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers
+            .push((name.to_string(), value.into().into_bytes()));
+    }
+
+    /// Write the response to the client, streaming the body when it comes from the MCP gateway
+    pub async fn send(self, response_out: ResponseOutparam) -> Result<()> {
+        let fields = Fields::from_list(&self.headers)
+            .map_err(|e| anyhow!("Invalid response headers: {e:?}"))?;
+        let response = OutgoingResponse::new(fields);
+        response
+            .set_status_code(self.status)
+            .map_err(|()| anyhow!("Invalid response status: {}", self.status))?;
+
+        let mut body = response.take_body();
+        response_out.set(response);
 
-    // If this is an initialize request and we have auth context, inject user info
-    if let Some((ref _claims, ref user_context)) = auth_context {
-        if let Some(obj) = request_data.as_object_mut() {
-            if let Some(method) = obj.get("method").and_then(|m| m.as_str()) {
-                if method == "initialize" {
-                    // Add user context to the request
-                    if let Some(params) = obj.get_mut("params").and_then(|p| p.as_object_mut()) {
-                        params.insert(
-                            "_authContext".to_string(),
-                            serde_json::json!({
-                                "authenticated_user": user_context.id,
-                                "email": user_context.email,
-                                "provider": user_context.provider,
-                            }),
-                        );
-                    }
+        match self.body {
+            ResponseBody::Full(bytes) => {
+                if !bytes.is_empty() {
+                    body.send(bytes)
+                        .await
+                        .map_err(|e| anyhow!("Failed to write response body: {e:?}"))?;
+                }
+            }
+            ResponseBody::Stream(upstream) => {
+                let mut stream = upstream.take_body_stream();
+                while let Some(chunk) = stream.next().await {
+                    let chunk =
+                        chunk.map_err(|e| anyhow!("Failed to read MCP gateway response: {e:?}"))?;
+                    body.send(chunk)
+                        .await
+                        .map_err(|e| anyhow!("Failed to write response body: {e:?}"))?;
                 }
             }
         }
+
+        Ok(())
     }
+}
+
+/// The MCP gateway URL with the client's query string
+fn forward_url(mcp_url: &str, query: &str) -> String {
+    if query.is_empty() {
+        mcp_url.to_string()
+    } else if mcp_url.contains('?') {
+        format!("{mcp_url}&{query}")
+    } else {
+        format!("{mcp_url}?{query}")
+    }
+}
+
+/// The user information added to the `initialize` exchange
+fn auth_info(user_context: &UserContext) -> Value {
+    let mut info = serde_json::json!({
+        "authenticated_user": user_context.id,
+        "email": user_context.email,
+        "provider": user_context.provider,
+    });
+    // Mapped claims are only included when the token carried them
+    if let (Some(info), Ok(Value::Object(mapped))) =
+        (info.as_object_mut(), serde_json::to_value(user_context))
+    {
+        for key in ["name", "tenant", "roles", "groups", "attributes"] {
+            if let Some(value) = mapped.get(key) {
+                info.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+    info
+}
+
+/// Add the user's context to the params of an `initialize` request. Returns `None` for any
+/// other message, which is then forwarded untouched.
+fn inject_auth_context(body: &[u8], user_context: &UserContext) -> Option<Vec<u8>> {
+    let mut message: Value = serde_json::from_slice(body).ok()?;
+    let request = message.as_object_mut()?;
+    if request.get("method").and_then(Value::as_str) != Some("initialize") {
+        return None;
+    }
+
+    request
+        .get_mut("params")?
+        .as_object_mut()?
+        .insert("_authContext".to_string(), auth_info(user_context));
+    serde_json::to_vec(&message).ok()
+}
 
-    // Build the request to forward to MCP gateway
-    let mcp_url = &config.mcp_gateway_url;
+/// Add the user's information to the `serverInfo` of an `initialize` result
+fn inject_auth_info(body: &[u8], user_context: &UserContext) -> Option<Vec<u8>> {
+    let mut message: Value = serde_json::from_slice(body).ok()?;
+    message
+        .get_mut("result")?
+        .get_mut("serverInfo")?
+        .as_object_mut()?
+        .insert("authInfo".to_string(), auth_info(user_context));
+    serde_json::to_vec(&message).ok()
+}
+
+/// Forward a request to the MCP gateway.
+///
+/// Only the `initialize` exchange of an authenticated request is rewritten. Every other body,
+/// including `text/event-stream` responses, streams through untouched with the original status.
+/// Headers in both directions pass through the configured [`HeaderPolicy`](crate::headers::HeaderPolicy).
+pub async fn forward_to_mcp_gateway(
+    req: Request,
+    config: &AuthConfig,
+    auth_context: Option<(Claims, UserContext)>,
+    tool_tokens: &BTreeMap<String, String>,
+    trace: &TraceContext,
+) -> Result<GatewayResponse> {
+    let user_context = auth_context.map(|(_, user_context)| user_context);
+    let initialize = user_context
+        .as_ref()
+        .and_then(|user| inject_auth_context(req.body(), user));
+
+    let mcp_url = forward_url(&config.mcp_gateway_url, req.query());
     eprintln!("Forwarding request to: {mcp_url}");
 
-    // Determine the body to forward
-    let forward_body = if body.is_empty() {
-        // Forward empty body as-is
-        eprintln!("Forwarding empty request body");
-        body.to_vec()
-    } else if request_data == serde_json::json!(null) {
-        // If we couldn't parse, forward original body
-        body.to_vec()
-    } else {
-        // Forward modified JSON
-        eprintln!(
-            "Request data: {}",
-            serde_json::to_string_pretty(&request_data)?
-        );
-        serde_json::to_vec(&request_data)?
-    };
+    let request_headers = config.headers.request_headers(
+        req.headers()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect(),
+    );
 
-    let forward_req = Request::builder()
-        .method(req.method().clone())
-        .uri(&config.mcp_gateway_url)
-        .header("Content-Type", "application/json")
-        .header("X-Trace-Id", trace_id)
-        .body(forward_body)
+    let mut builder = Request::builder();
+    builder.method(req.method().clone()).uri(mcp_url);
+    for (name, value) in request_headers {
+        if let Ok(value) = String::from_utf8(value) {
+            builder.header(name, value);
+        }
+    }
+    // The MCP gateway passes the trace on to tool components
+    builder.header("X-Trace-Id", &trace.log_id);
+    builder.header(TRACEPARENT_HEADER, trace.traceparent());
+    if let Some(state) = &trace.state {
+        builder.header(TRACESTATE_HEADER, state);
+    }
+    if let Some(tenant) = user_context
+        .as_ref()
+        .and_then(|user| user.tenant.as_deref())
+    {
+        builder.header(TENANT_HEADER, tenant);
+        // The MCP gateway only trusts a tenant signed with the secret it shares with this gateway
+        if let Some(secret) = &config.tenant_secret {
+            builder.header(
+                TENANT_SIGNATURE_HEADER,
+                headers::tenant_signature(secret, tenant),
+            );
+        }
+    }
+    // The MCP gateway hands each downstream token only to the tool it was exchanged for
+    if !tool_tokens.is_empty() {
+        if let Ok(tokens) = serde_json::to_string(tool_tokens) {
+            builder.header(TOOL_TOKENS_HEADER, tokens);
+        }
+    }
+    let rewritten = initialize.is_some();
+    let forward_req = builder
+        .body(initialize.unwrap_or_else(|| req.into_body()))
         .build();
 
-    // Forward the request
-    let resp: spin_sdk::http::Response = spin_sdk::http::send(forward_req).await?;
+    let response: IncomingResponse = spin_sdk::http::send(forward_req).await?;
+    let status = response.status();
 
-    // Parse the response to potentially inject auth info
-    let resp_body = resp.body();
-    let mut response_data: Value = if resp_body.is_empty() {
-        serde_json::json!({})
-    } else {
-        match serde_json::from_slice(resp_body) {
-            Ok(data) => data,
-            Err(e) => {
-                eprintln!("Failed to parse MCP gateway response as JSON: {e}");
-                let status = resp.status();
-                eprintln!("Response status: {status}");
-                let body_str = String::from_utf8_lossy(resp_body);
-                eprintln!("Response body: {body_str:?}");
-                return Err(anyhow::anyhow!(
-                    "Invalid JSON response from MCP gateway: {e}"
-                ));
-            }
-        }
+    let mut headers = config
+        .headers
+        .response_headers(response.headers().entries());
+    headers.push(("X-Trace-Id".to_string(), trace.log_id.as_bytes().to_vec()));
+
+    let is_json = headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("content-type") && value.starts_with(b"application/json")
+    });
+
+    // The `initialize` result is buffered to add the user's information; anything else,
+    // including an event stream, is passed through
+    let Some(user_context) = user_context.filter(|_| rewritten && is_json) else {
+        return Ok(GatewayResponse {
+            status,
+            headers,
+            body: ResponseBody::Stream(response),
+        });
     };
 
-    // If this is an initialize response and we have auth context, inject auth info into serverInfo
-    if let Some((ref _claims, ref user_context)) = auth_context {
-        if let Some(result) = response_data
-            .as_object_mut()
-            .and_then(|obj| obj.get_mut("result"))
-            .and_then(|r| r.as_object_mut())
-        {
-            if let Some(server_info) = result
-                .get_mut("serverInfo")
-                .and_then(|si| si.as_object_mut())
-            {
-                server_info.insert(
-                    "authInfo".to_string(),
-                    serde_json::json!({
-                        "authenticated_user": user_context.id,
-                        "email": user_context.email,
-                        "provider": user_context.provider,
-                    }),
-                );
-            }
+    let body = response
+        .into_body()
+        .await
+        .map_err(|e| anyhow!("Failed to read MCP gateway response: {e:?}"))?;
+    let body = inject_auth_info(&body, &user_context).unwrap_or(body);
+    headers.retain(|(name, _)| !name.eq_ignore_ascii_case("content-length"));
+
+    Ok(GatewayResponse {
+        status,
+        headers,
+        body: ResponseBody::Full(body),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> UserContext {
+        UserContext {
+            id: "user_123".to_string(),
+            email: Some("user@example.com".to_string()),
+            provider: "authkit".to_string(),
+            roles: vec!["admin".to_string()],
+            ..UserContext::default()
         }
     }
 
-    // Build the response to return
-    if response_data == serde_json::json!(null) || resp_body.is_empty() {
-        // Return the original response as-is
-        Ok(Response::builder()
-            .status(*resp.status())
-            .body(resp_body.to_vec())
-            .build())
-    } else {
-        // Return the modified JSON response
-        Ok(Response::builder()
-            .status(*resp.status())
-            .header("Content-Type", "application/json")
-            .header("X-Trace-Id", trace_id)
-            .body(serde_json::to_string(&response_data)?)
-            .build())
+    #[test]
+    fn test_inject_auth_context() {
+        let initialize = br#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let body = inject_auth_context(initialize, &user()).unwrap();
+        let message: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            message["params"]["_authContext"]["authenticated_user"],
+            "user_123"
+        );
+        assert_eq!(message["params"]["_authContext"]["roles"][0], "admin");
+        assert!(message["params"]["_authContext"].get("tenant").is_none());
+
+        // Other messages and non-JSON bodies are left alone
+        let call = br#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{}}"#;
+        assert!(inject_auth_context(call, &user()).is_none());
+        assert!(inject_auth_context(b"not json", &user()).is_none());
+        assert!(inject_auth_context(b"", &user()).is_none());
+    }
+
+    #[test]
+    fn test_inject_auth_info() {
+        let result = br#"{"jsonrpc":"2.0","id":1,"result":{"serverInfo":{"name":"ftl"}}}"#;
+        let body = inject_auth_info(result, &user()).unwrap();
+        let message: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            message["result"]["serverInfo"]["authInfo"]["provider"],
+            "authkit"
+        );
+
+        let error = br#"{"jsonrpc":"2.0","id":1,"error":{"code":-32600,"message":"x"}}"#;
+        assert!(inject_auth_info(error, &user()).is_none());
     }
 }
@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use spin_sdk::http::{Method, Request, Response};
+
+use crate::{logging::Logger, providers::AuthProvider};
+
+/// Largest registration request forwarded; client metadata is a few hundred bytes
+const MAX_BODY_SIZE: usize = 16 * 1024;
+
+/// Most redirect URIs and additional metadata fields accepted in one registration
+const MAX_REDIRECT_URIS: usize = 16;
+const MAX_EXTRA_FIELDS: usize = 32;
+
+/// Dynamic client registration (RFC 7591) settings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RegistrationConfig {
+    /// No registration endpoint is served
+    #[default]
+    Disabled,
+    /// Forward registrations to the identity provider, which issues and validates the clients
+    Proxy {
+        /// Upstream registration endpoint; defaults to the one advertised by the provider
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+}
+
+impl RegistrationConfig {
+    /// Whether the gateway serves a registration endpoint
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+}
+
+/// Client metadata accepted in registration requests (RFC 7591, section 2)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClientMetadata {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default = "default_auth_method")]
+    pub token_endpoint_auth_method: String,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<String>,
+    #[serde(default = "default_response_types")]
+    pub response_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Remaining metadata (`client_uri`, `logo_uri`, `contacts`, ...) is kept as-is
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn default_auth_method() -> String {
+    "client_secret_basic".to_string()
+}
+
+fn default_grant_types() -> Vec<String> {
+    vec!["authorization_code".to_string()]
+}
+
+fn default_response_types() -> Vec<String> {
+    vec!["code".to_string()]
+}
+
+/// Registration error response (RFC 7591, section 3.2.2)
+fn registration_error(status: u16, error: &str, description: &str) -> Response {
+    let body = serde_json::json!({
+        "error": error,
+        "error_description": description
+    });
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(body.to_string())
+        .build()
+}
+
+/// Check a redirect URI: HTTPS, loopback HTTP or a private-use scheme, and no fragment
+fn validate_redirect_uri(uri: &str) -> Result<(), String> {
+    if uri.contains('#') {
+        return Err(format!("Redirect URI must not contain a fragment: {uri}"));
+    }
+
+    let Some((scheme, rest)) = uri.split_once(':') else {
+        return Err(format!("Redirect URI must be absolute: {uri}"));
+    };
+
+    match scheme.to_ascii_lowercase().as_str() {
+        "http" => {
+            let authority = rest
+                .trim_start_matches("//")
+                .split(['/', '?'])
+                .next()
+                .unwrap_or_default();
+            let host = authority
+                .rsplit_once(':')
+                .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+                .map_or(authority, |(host, _)| host);
+            if matches!(host, "localhost" | "127.0.0.1" | "[::1]") {
+                Ok(())
+            } else {
+                Err(format!(
+                    "HTTP redirect URIs must use a loopback host: {uri}"
+                ))
+            }
+        }
+        "javascript" | "data" | "file" => Err(format!("Redirect URI scheme not allowed: {uri}")),
+        // HTTPS and private-use URI schemes for native apps (RFC 8252, section 7.1)
+        _ => Ok(()),
+    }
+}
+
+/// Validate client metadata before it reaches the identity provider
+fn validate_metadata(metadata: &ClientMetadata) -> Result<(), (&'static str, String)> {
+    if metadata.redirect_uris.len() > MAX_REDIRECT_URIS {
+        return Err((
+            "invalid_redirect_uri",
+            format!("At most {MAX_REDIRECT_URIS} redirect URIs may be registered"),
+        ));
+    }
+
+    if metadata.extra.len() > MAX_EXTRA_FIELDS {
+        return Err((
+            "invalid_client_metadata",
+            format!("At most {MAX_EXTRA_FIELDS} additional metadata fields are accepted"),
+        ));
+    }
+
+    if metadata
+        .grant_types
+        .iter()
+        .any(|grant_type| grant_type == "authorization_code")
+        && metadata.redirect_uris.is_empty()
+    {
+        return Err((
+            "invalid_redirect_uri",
+            "redirect_uris is required for the authorization_code grant".to_string(),
+        ));
+    }
+
+    for uri in &metadata.redirect_uris {
+        validate_redirect_uri(uri).map_err(|e| ("invalid_redirect_uri", e))?;
+    }
+
+    Ok(())
+}
+
+/// Forward a registration request to the identity provider
+async fn proxy_registration(endpoint: &str, req: &Request) -> Result<Response> {
+    let mut builder = Request::builder();
+    builder
+        .method(Method::Post)
+        .uri(endpoint)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json");
+
+    // An initial access token (RFC 7591, section 3) is meant for the identity provider
+    if let Some(auth) = req
+        .headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.as_str())
+    {
+        builder.header("Authorization", auth);
+    }
+
+    let request = builder.body(req.body().to_vec()).build();
+
+    let response: Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("Failed to reach registration endpoint {endpoint}: {e}"))?;
+
+    let content_type = response
+        .header("content-type")
+        .and_then(|value| value.as_str())
+        .unwrap_or("application/json")
+        .to_string();
+
+    Ok(Response::builder()
+        .status(*response.status())
+        .header("Content-Type", content_type)
+        .header("Cache-Control", "no-store")
+        .body(response.body().to_vec())
+        .build())
+}
+
+/// Forward a registration request to the upstream authorization server
+async fn handle_proxy_registration(
+    endpoint: Option<&str>,
+    provider: Option<&dyn AuthProvider>,
+    req: &Request,
+    logger: &Logger<'_>,
+) -> Response {
+    let endpoint = endpoint
+        .map(String::from)
+        .or_else(|| provider.and_then(|p| p.discovery_metadata("").registration_endpoint));
+    let Some(endpoint) = endpoint else {
+        logger
+            .error("No upstream registration endpoint available")
+            .emit();
+        return registration_error(
+            502,
+            "temporarily_unavailable",
+            "No upstream registration endpoint available",
+        );
+    };
+
+    if provider.is_some_and(|p| !p.allows_url(&endpoint)) {
+        logger
+            .error("Registration endpoint is outside the provider's allowed domains")
+            .field("endpoint", &endpoint)
+            .emit();
+        return registration_error(
+            502,
+            "temporarily_unavailable",
+            "Registration endpoint unavailable",
+        );
+    }
+
+    match proxy_registration(&endpoint, req).await {
+        Ok(response) => {
+            logger
+                .info("Proxied client registration")
+                .field("status", response.status())
+                .emit();
+            response
+        }
+        Err(e) => {
+            logger
+                .error("Client registration proxy failed")
+                .field("error", &e)
+                .emit();
+            registration_error(
+                502,
+                "temporarily_unavailable",
+                "Registration endpoint unavailable",
+            )
+        }
+    }
+}
+
+/// Handle a dynamic client registration request. The metadata is checked before it is
+/// forwarded, so the identity provider only sees bounded, well-formed registrations.
+pub async fn handle_registration(
+    config: &RegistrationConfig,
+    provider: Option<&dyn AuthProvider>,
+    req: &Request,
+    logger: &Logger<'_>,
+) -> Response {
+    let RegistrationConfig::Proxy { endpoint } = config else {
+        return registration_error(
+            404,
+            "invalid_request",
+            "Dynamic client registration is not enabled",
+        );
+    };
+
+    if req.body().len() > MAX_BODY_SIZE {
+        logger
+            .warn("Rejected client registration")
+            .field("reason", "request body too large")
+            .emit();
+        return registration_error(
+            413,
+            "invalid_client_metadata",
+            &format!("Client metadata must not exceed {MAX_BODY_SIZE} bytes"),
+        );
+    }
+
+    let metadata: ClientMetadata = match serde_json::from_slice(req.body()) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return registration_error(
+                400,
+                "invalid_client_metadata",
+                &format!("Invalid client metadata: {e}"),
+            );
+        }
+    };
+
+    if let Err((error, description)) = validate_metadata(&metadata) {
+        logger
+            .warn("Rejected client registration")
+            .field("error", error)
+            .field("reason", &description)
+            .emit();
+        return registration_error(400, error, &description);
+    }
+
+    handle_proxy_registration(endpoint.as_deref(), provider, req, logger).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(value: Value) -> ClientMetadata {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_uri_validation() {
+        assert!(validate_redirect_uri("https://app.example.com/callback").is_ok());
+        assert!(validate_redirect_uri("http://localhost:8123/callback").is_ok());
+        assert!(validate_redirect_uri("http://127.0.0.1/callback").is_ok());
+        assert!(validate_redirect_uri("com.example.app:/oauth2redirect").is_ok());
+        assert!(validate_redirect_uri("http://evil.example.com/callback").is_err());
+        assert!(validate_redirect_uri("http://localhost.evil.com/callback").is_err());
+        assert!(validate_redirect_uri("https://app.example.com/cb#frag").is_err());
+        assert!(validate_redirect_uri("javascript:alert(1)").is_err());
+        assert!(validate_redirect_uri("/relative").is_err());
+    }
+
+    #[test]
+    fn test_metadata_defaults_and_validation() {
+        let public = metadata(serde_json::json!({
+            "client_name": "IDE agent",
+            "redirect_uris": ["http://localhost:3334/callback"],
+            "token_endpoint_auth_method": "none",
+            "logo_uri": "https://example.com/logo.png"
+        }));
+        assert!(validate_metadata(&public).is_ok());
+        assert_eq!(public.grant_types, vec!["authorization_code"]);
+        assert!(public.extra.contains_key("logo_uri"));
+
+        let missing_redirect = metadata(serde_json::json!({}));
+        assert_eq!(
+            validate_metadata(&missing_redirect).unwrap_err().0,
+            "invalid_redirect_uri"
+        );
+
+        // The identity provider decides which authentication methods it supports
+        let private_key_jwt = metadata(serde_json::json!({
+            "redirect_uris": ["https://app.example.com/cb"],
+            "token_endpoint_auth_method": "private_key_jwt"
+        }));
+        assert!(validate_metadata(&private_key_jwt).is_ok());
+
+        let extra: Map<String, Value> = (0..=MAX_EXTRA_FIELDS)
+            .map(|i| (format!("field_{i}"), Value::from(i)))
+            .collect();
+        let mut bloated = private_key_jwt.clone();
+        bloated.extra = extra;
+        assert_eq!(
+            validate_metadata(&bloated).unwrap_err().0,
+            "invalid_client_metadata"
+        );
+
+        let mut many_uris = private_key_jwt;
+        many_uris.redirect_uris =
+            vec!["https://app.example.com/cb".to_string(); MAX_REDIRECT_URIS + 1];
+        assert_eq!(
+            validate_metadata(&many_uris).unwrap_err().0,
+            "invalid_redirect_uri"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use spin_sdk::key_value::{Error, Store};
+
+use crate::auth::sha256_hex;
+
+/// Record that a one-time identifier (a `jti`) is used up until `until`, a Unix time. Returns
+/// `false` when it was already recorded and that record has not lapsed.
+///
+/// Identifiers are hashed into keys under `kind`, so different kinds never collide. Spin's
+/// key-value API has no compare-and-set, so the check and the write are separate: two requests
+/// racing with the same identifier can both pass. Reuse after the first request completes is
+/// always caught.
+pub fn record_once(
+    store: &Store,
+    kind: &str,
+    id: &str,
+    until: i64,
+    now: i64,
+) -> Result<bool, Error> {
+    let key = format!("{kind}:jti:{}", sha256_hex(id));
+
+    let used_until = store
+        .get(&key)?
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|s| s.parse::<i64>().ok());
+    if used_until.is_some_and(|until| until > now) {
+        return Ok(false);
+    }
+
+    store.set(&key, until.to_string().as_bytes())?;
+    Ok(true)
+}
@@ -0,0 +1,595 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use spin_sdk::http::{Method, Request, Response};
+use spin_sdk::key_value::Store;
+
+use crate::{
+    api_keys::{constant_time_eq, hash_api_key},
+    auth::{self, unix_now, Claims},
+    logging::Logger,
+    providers::ProviderRegistry,
+    replay, token_cache,
+};
+
+/// Prefix for revocations in the key-value store
+const STORE_KEY_PREFIX: &str = "revoked:";
+
+/// Event a back-channel logout token must carry (OIDC Back-Channel Logout 1.0)
+const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// Claims of an OIDC back-channel logout token (section 2.4). It names a subject, a session, or
+/// both.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogoutClaims {
+    pub iss: String,
+    #[serde(default)]
+    pub sub: Option<String>,
+    #[serde(default)]
+    pub sid: Option<String>,
+    pub jti: String,
+    pub exp: i64,
+    #[serde(default)]
+    pub events: Value,
+    #[serde(default)]
+    pub nonce: Option<Value>,
+}
+
+/// Token revocation settings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RevocationConfig {
+    /// Key-value store holding revocations; tokens are not checked when unset
+    #[serde(default)]
+    pub store: Option<String>,
+    /// Hex-encoded SHA-256 hashes of the keys allowed to manage revocations
+    #[serde(default)]
+    pub admin_keys: Vec<String>,
+    /// Accept OIDC back-channel logout tokens from configured providers
+    #[serde(default)]
+    pub backchannel_logout: bool,
+}
+
+impl RevocationConfig {
+    /// Whether tokens are checked against a revocation store
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Check admin keys are stored as SHA-256 hashes and that the endpoints have a store
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .admin_keys
+            .iter()
+            .any(|hash| hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            return Err("admin keys must be stored as hex-encoded SHA-256 hashes".to_string());
+        }
+        if !self.is_enabled() && (!self.admin_keys.is_empty() || self.backchannel_logout) {
+            return Err("admin keys and back-channel logout require a store".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The claim a revocation matches tokens on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RevocationKind {
+    /// A single token, by `jti`
+    Token,
+    /// Every token of a subject issued before a point in time, by `sub`
+    Subject,
+    /// Every token of an identity provider session, by `sid`
+    Session,
+}
+
+impl RevocationKind {
+    const fn claim(self) -> &'static str {
+        match self {
+            Self::Token => "jti",
+            Self::Subject => "sub",
+            Self::Session => "sid",
+        }
+    }
+
+    /// Claims are only unique per issuer, so the issuer is part of the key. Issuers are URLs
+    /// without whitespace, which keeps the key unambiguous.
+    fn store_key(self, iss: &str, value: &str) -> String {
+        format!("{STORE_KEY_PREFIX}{}:{iss} {value}", self.claim())
+    }
+}
+
+/// A revocation as persisted in the key-value store
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct RevocationEntry {
+    revoked_at: i64,
+    /// Tokens issued at or after this time are unaffected (subject revocations)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_before: Option<i64>,
+}
+
+impl RevocationEntry {
+    fn revokes(&self, issued_at: i64) -> bool {
+        self.not_before
+            .map_or(true, |not_before| issued_at < not_before)
+    }
+}
+
+/// Body of an admin revocation request: the issuer and exactly one of `jti`, `sub` or `sid`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RevocationRequest {
+    /// Issuer of the tokens to revoke; `api_key` for API keys
+    iss: String,
+    #[serde(default)]
+    jti: Option<String>,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    sid: Option<String>,
+    /// For `sub`: tokens issued before this time are revoked (defaults to now)
+    #[serde(default)]
+    not_before: Option<i64>,
+}
+
+impl RevocationRequest {
+    fn target(&self) -> Result<(RevocationKind, &str), String> {
+        if self.iss.trim().is_empty() {
+            return Err("iss is required".to_string());
+        }
+
+        let targets: Vec<(RevocationKind, &str)> = [
+            (RevocationKind::Token, &self.jti),
+            (RevocationKind::Subject, &self.sub),
+            (RevocationKind::Session, &self.sid),
+        ]
+        .into_iter()
+        .filter_map(|(kind, value)| Some((kind, value.as_deref()?.trim())))
+        .collect();
+
+        match targets.as_slice() {
+            [(kind, value)] if !value.is_empty() => {
+                if self.not_before.is_some() && *kind != RevocationKind::Subject {
+                    return Err("not_before only applies to sub revocations".to_string());
+                }
+                Ok((*kind, value))
+            }
+            _ => Err("Exactly one of jti, sub or sid is required".to_string()),
+        }
+    }
+}
+
+/// The revocation keys a token can be matched on
+fn token_targets(claims: &Claims) -> Vec<(RevocationKind, &str)> {
+    let mut targets = vec![(RevocationKind::Subject, claims.sub.as_str())];
+    for kind in [RevocationKind::Token, RevocationKind::Session] {
+        if let Some(value) = claims.extra.get(kind.claim()).and_then(Value::as_str) {
+            targets.push((kind, value));
+        }
+    }
+    targets
+}
+
+fn open_store(label: &str) -> Result<Store, String> {
+    Store::open(label).map_err(|e| {
+        eprintln!("Failed to open revocation store '{label}': {e}");
+        "Token validation failed".to_string()
+    })
+}
+
+/// Reject a verified token that has been revoked. Fails closed when the store is unavailable.
+pub fn check_revoked(claims: &Claims, config: &RevocationConfig) -> Result<(), String> {
+    let Some(label) = config.store.as_deref() else {
+        return Ok(());
+    };
+    let store = open_store(label)?;
+
+    for (kind, value) in token_targets(claims) {
+        let entry = store
+            .get(&kind.store_key(&claims.iss, value))
+            .map_err(|e| {
+                eprintln!("Failed to read revocation store '{label}': {e}");
+                "Token validation failed".to_string()
+            })?;
+        let Some(entry) = entry else {
+            continue;
+        };
+
+        let revoked = serde_json::from_slice::<RevocationEntry>(&entry)
+            .map_or(true, |entry| entry.revokes(claims.iat));
+        if revoked {
+            eprintln!("Rejected token revoked by {}", kind.claim());
+            return Err("Token has been revoked".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Persist a revocation and drop the tokens it covers from the verified-token cache
+async fn revoke(
+    store: &Store,
+    iss: &str,
+    kind: RevocationKind,
+    value: &str,
+    not_before: Option<i64>,
+) -> Result<RevocationEntry, String> {
+    let now = unix_now();
+    let entry = RevocationEntry {
+        revoked_at: now,
+        not_before: (kind == RevocationKind::Subject).then(|| not_before.unwrap_or(now)),
+    };
+    let bytes = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+    store
+        .set(&kind.store_key(iss, value), &bytes)
+        .map_err(|e| format!("Failed to store revocation: {e}"))?;
+
+    token_cache::evict(|claims| {
+        claims.iss == iss
+            && token_targets(claims).contains(&(kind, value))
+            && entry.revokes(claims.iat)
+    })
+    .await;
+
+    Ok(entry)
+}
+
+/// JSON error response in the OAuth style
+fn error_response(status: u16, error: &str, description: &str) -> Response {
+    let body = serde_json::json!({
+        "error": error,
+        "error_description": description
+    });
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(body.to_string())
+        .build()
+}
+
+/// Whether the request carries one of the configured admin keys as a bearer token
+fn is_admin(req: &Request, config: &RevocationConfig) -> bool {
+    let Some(key) = req
+        .headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.as_str())
+        .and_then(|value| value.trim().split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, key)| key.trim())
+    else {
+        return false;
+    };
+
+    let hash = hash_api_key(key);
+    config
+        .admin_keys
+        .iter()
+        .any(|admin| constant_time_eq(admin.to_lowercase().as_bytes(), hash.as_bytes()))
+}
+
+/// Manage revocations: `POST` adds one and `DELETE` removes it
+pub async fn handle_admin_request(
+    req: &Request,
+    config: &RevocationConfig,
+    logger: &Logger<'_>,
+) -> Response {
+    if !is_admin(req, config) {
+        logger.warn("Rejected revocation admin request").emit();
+        return Response::builder()
+            .status(401)
+            .header("WWW-Authenticate", "Bearer")
+            .body("Unauthorized")
+            .build();
+    }
+
+    let Some(label) = config.store.as_deref() else {
+        return error_response(500, "server_error", "No revocation store configured");
+    };
+
+    let request = match serde_json::from_slice::<RevocationRequest>(req.body()) {
+        Ok(request) => request,
+        Err(e) => return error_response(400, "invalid_request", &e.to_string()),
+    };
+    let (kind, value) = match request.target() {
+        Ok(target) => target,
+        Err(e) => return error_response(400, "invalid_request", &e),
+    };
+    let iss = request.iss.trim();
+
+    let store = match open_store(label) {
+        Ok(store) => store,
+        Err(e) => return error_response(500, "server_error", &e),
+    };
+
+    match req.method() {
+        Method::Post => match revoke(&store, iss, kind, value, request.not_before).await {
+            Ok(entry) => {
+                logger
+                    .info("Token revocation added")
+                    .field("claim", kind.claim())
+                    .emit();
+                let mut body = serde_json::json!({ "iss": iss, kind.claim(): value });
+                if let (Some(body), Ok(Value::Object(entry))) =
+                    (body.as_object_mut(), serde_json::to_value(entry))
+                {
+                    body.extend(entry);
+                }
+                Response::builder()
+                    .status(201)
+                    .header("Content-Type", "application/json")
+                    .body(body.to_string())
+                    .build()
+            }
+            Err(e) => error_response(500, "server_error", &e),
+        },
+        Method::Delete => match store.delete(&kind.store_key(iss, value)) {
+            Ok(()) => {
+                logger
+                    .info("Token revocation removed")
+                    .field("claim", kind.claim())
+                    .emit();
+                Response::builder().status(204).build()
+            }
+            Err(e) => error_response(500, "server_error", &format!("{e}")),
+        },
+        _ => Response::builder()
+            .status(405)
+            .header("Allow", "POST, DELETE")
+            .body("Method not allowed")
+            .build(),
+    }
+}
+
+/// The `logout_token` parameter of a form-encoded body
+fn logout_token(body: &[u8]) -> Option<&str> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("logout_token="))
+        .filter(|token| !token.is_empty())
+}
+
+/// Check the claims of a verified logout token (OIDC Back-Channel Logout, section 2.6)
+fn validate_logout_claims(claims: &LogoutClaims) -> Result<(RevocationKind, &str), String> {
+    if claims.events.get(BACKCHANNEL_LOGOUT_EVENT).is_none() {
+        return Err("Logout token lacks the back-channel logout event".to_string());
+    }
+    if claims.nonce.is_some() {
+        return Err("Logout token must not contain a nonce".to_string());
+    }
+    if claims.jti.is_empty() {
+        return Err("Logout token must have a jti".to_string());
+    }
+
+    // A session ID logs out that session; otherwise every earlier token of the subject
+    let sid = claims.sid.as_deref().filter(|sid| !sid.is_empty());
+    let sub = claims.sub.as_deref().filter(|sub| !sub.is_empty());
+    match (sid, sub) {
+        (Some(sid), _) => Ok((RevocationKind::Session, sid)),
+        (None, Some(sub)) => Ok((RevocationKind::Subject, sub)),
+        (None, None) => Err("Logout token must contain a sub or a sid".to_string()),
+    }
+}
+
+/// Revoke the session or subject named by an OIDC back-channel logout token
+pub async fn handle_backchannel_logout(
+    req: &Request,
+    config: &RevocationConfig,
+    registry: &ProviderRegistry,
+    logger: &Logger<'_>,
+) -> Response {
+    if *req.method() != Method::Post {
+        return Response::builder()
+            .status(405)
+            .header("Allow", "POST")
+            .body("Method not allowed")
+            .build();
+    }
+
+    let Some(label) = config.store.as_deref() else {
+        return error_response(500, "server_error", "No revocation store configured");
+    };
+    let Some(token) = logout_token(req.body()) else {
+        return error_response(400, "invalid_request", "Missing logout_token");
+    };
+
+    let claims = match auth::verify_logout_token(token, registry).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            logger
+                .warn("Rejected back-channel logout token")
+                .field("reason", &e)
+                .emit();
+            return error_response(400, "invalid_request", &e);
+        }
+    };
+    let (kind, value) = match validate_logout_claims(&claims) {
+        Ok(target) => target,
+        Err(e) => return error_response(400, "invalid_request", &e),
+    };
+
+    let store = match open_store(label) {
+        Ok(store) => store,
+        Err(e) => return error_response(500, "server_error", &e),
+    };
+    // Each logout token is accepted once, for as long as it is valid
+    match replay::record_once(&store, "logout", &claims.jti, claims.exp, unix_now()) {
+        Ok(true) => {}
+        Ok(false) => {
+            return error_response(400, "invalid_request", "Logout token has already been used")
+        }
+        Err(e) => return error_response(500, "server_error", &e.to_string()),
+    }
+
+    // Tokens issued before the logout are revoked, later sign-ins are unaffected
+    match revoke(&store, &claims.iss, kind, value, None).await {
+        Ok(_) => {
+            logger
+                .info("Back-channel logout")
+                .field("claim", kind.claim())
+                .field("issuer", &claims.iss)
+                .emit();
+            Response::builder()
+                .status(200)
+                .header("Cache-Control", "no-store")
+                .build()
+        }
+        Err(e) => error_response(500, "server_error", &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(extra: Value) -> Claims {
+        Claims {
+            sub: "user_123".to_string(),
+            iss: "https://example.authkit.app".to_string(),
+            aud: None,
+            exp: 2_000_000_000,
+            iat: 1_700_000_000,
+            email: None,
+            extra,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RevocationConfig::default().validate().is_ok());
+
+        let without_store = RevocationConfig {
+            backchannel_logout: true,
+            ..RevocationConfig::default()
+        };
+        assert!(without_store.validate().is_err());
+
+        let plain_key = RevocationConfig {
+            store: Some("default".to_string()),
+            admin_keys: vec!["secret".to_string()],
+            ..RevocationConfig::default()
+        };
+        assert!(plain_key.validate().is_err());
+    }
+
+    #[test]
+    fn test_entry_revokes() {
+        let token = RevocationEntry {
+            revoked_at: 1_700_000_100,
+            not_before: None,
+        };
+        assert!(token.revokes(1_800_000_000));
+
+        let subject = RevocationEntry {
+            revoked_at: 1_700_000_100,
+            not_before: Some(1_700_000_100),
+        };
+        assert!(subject.revokes(1_700_000_000));
+        assert!(!subject.revokes(1_700_000_100));
+    }
+
+    #[test]
+    fn test_request_target() {
+        let request = |body: Value| serde_json::from_value::<RevocationRequest>(body).unwrap();
+
+        let iss = "https://example.authkit.app";
+
+        assert_eq!(
+            request(serde_json::json!({ "iss": iss, "jti": "abc" })).target(),
+            Ok((RevocationKind::Token, "abc"))
+        );
+        assert_eq!(
+            request(serde_json::json!({ "iss": iss, "sub": "user_123", "not_before": 1 })).target(),
+            Ok((RevocationKind::Subject, "user_123"))
+        );
+        assert!(request(serde_json::json!({ "iss": iss })).target().is_err());
+        assert!(
+            request(serde_json::json!({ "iss": iss, "jti": "a", "sid": "b" }))
+                .target()
+                .is_err()
+        );
+        assert!(
+            request(serde_json::json!({ "iss": iss, "sid": "b", "not_before": 1 }))
+                .target()
+                .is_err()
+        );
+        assert!(request(serde_json::json!({ "iss": iss, "jti": " " }))
+            .target()
+            .is_err());
+        assert!(request(serde_json::json!({ "iss": " ", "jti": "abc" }))
+            .target()
+            .is_err());
+        // Without an issuer a revocation would reach every provider's tokens
+        assert!(
+            serde_json::from_value::<RevocationRequest>(serde_json::json!({ "jti": "abc" }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_token_targets() {
+        let claims = claims(serde_json::json!({ "jti": "abc", "sid": "s1" }));
+        assert_eq!(
+            token_targets(&claims),
+            vec![
+                (RevocationKind::Subject, "user_123"),
+                (RevocationKind::Token, "abc"),
+                (RevocationKind::Session, "s1"),
+            ]
+        );
+        assert_eq!(
+            RevocationKind::Token.store_key(&claims.iss, "abc"),
+            "revoked:jti:https://example.authkit.app abc"
+        );
+        assert_ne!(
+            RevocationKind::Subject.store_key("https://one.example.com", "user_123"),
+            RevocationKind::Subject.store_key("https://two.example.com", "user_123")
+        );
+    }
+
+    #[test]
+    fn test_logout_claims() {
+        let logout = |extra: Value| -> LogoutClaims {
+            let mut value = serde_json::json!({
+                "iss": "https://example.authkit.app",
+                "jti": "logout_1",
+                "exp": 2_000_000_000,
+                "events": { BACKCHANNEL_LOGOUT_EVENT: {} },
+            });
+            if let (Some(object), Value::Object(extra)) = (value.as_object_mut(), extra) {
+                object.extend(extra);
+            }
+            serde_json::from_value(value).unwrap()
+        };
+
+        let both = logout(serde_json::json!({ "sub": "user_123", "sid": "s1" }));
+        assert_eq!(
+            validate_logout_claims(&both),
+            Ok((RevocationKind::Session, "s1"))
+        );
+
+        let session = logout(serde_json::json!({ "sid": "s1" }));
+        assert_eq!(
+            validate_logout_claims(&session),
+            Ok((RevocationKind::Session, "s1"))
+        );
+
+        let subject = logout(serde_json::json!({ "sub": "user_123" }));
+        assert_eq!(
+            validate_logout_claims(&subject),
+            Ok((RevocationKind::Subject, "user_123"))
+        );
+
+        assert!(validate_logout_claims(&logout(serde_json::json!({}))).is_err());
+        let no_event = logout(serde_json::json!({ "sub": "user_123", "events": {} }));
+        assert!(validate_logout_claims(&no_event).is_err());
+        let nonce = logout(serde_json::json!({ "sub": "user_123", "nonce": "n" }));
+        assert!(validate_logout_claims(&nonce).is_err());
+        let no_jti = logout(serde_json::json!({ "sub": "user_123", "jti": "" }));
+        assert!(validate_logout_claims(&no_jti).is_err());
+
+        assert_eq!(logout_token(b"logout_token=a.b.c&x=1"), Some("a.b.c"));
+        assert!(logout_token(b"token=a.b.c").is_none());
+    }
+}
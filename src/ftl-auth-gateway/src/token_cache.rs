@@ -0,0 +1,235 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{sha256_hex, unix_now, Claims},
+    providers::UserContext,
+};
+
+/// A verified token: its claims and user context, and the key that signed it
+#[derive(Debug, Clone)]
+struct VerifiedToken {
+    claims: Claims,
+    user_context: UserContext,
+    jwks_uri: String,
+    kid: String,
+    /// Unix time the entry stops being served: the end of its TTL or the token's `exp`
+    expires_at: i64,
+}
+
+/// Type alias for the verified-token cache
+type TokenCache = Arc<RwLock<HashMap<String, VerifiedToken>>>;
+
+/// Cache for verified tokens, keyed by a hash of the token and the audiences it was checked
+/// against. It lives as long as the component instance, so it only hits when the host reuses
+/// instances.
+static TOKEN_CACHE: Lazy<TokenCache> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Maximum number of tokens to cache (prevent `DoS`)
+const MAX_CACHE_SIZE: usize = 1000;
+
+/// Cache key for a token; the raw token is never held as a key
+fn cache_key(token: &str, audiences: &[String]) -> String {
+    sha256_hex(&format!("{token}\n{}", audiences.join(" ")))
+}
+
+/// Claims and user context of a token verified earlier for the same audiences
+pub async fn get(token: &str, audiences: &[String]) -> Option<(Claims, UserContext)> {
+    let key = cache_key(token, audiences);
+    let now = unix_now();
+
+    {
+        let cache = TOKEN_CACHE.read().await;
+        match cache.get(&key) {
+            Some(entry) if entry.expires_at > now => {
+                return Some((entry.claims.clone(), entry.user_context.clone()));
+            }
+            Some(_) => {}
+            None => return None,
+        }
+    }
+
+    // Drop the expired entry
+    TOKEN_CACHE.write().await.remove(&key);
+    None
+}
+
+/// Remember a verified token for `ttl` seconds, but never past its `exp`
+pub async fn insert(
+    token: &str,
+    audiences: &[String],
+    jwks_uri: &str,
+    kid: &str,
+    ttl: u64,
+    claims: &Claims,
+    user_context: &UserContext,
+) {
+    let ttl = i64::try_from(ttl).unwrap_or(i64::MAX);
+    let expires_at = unix_now().saturating_add(ttl).min(claims.exp);
+    if expires_at <= unix_now() {
+        return;
+    }
+
+    let mut cache = TOKEN_CACHE.write().await;
+
+    // Expired entries go first; if the cache is still full, remove the entry expiring soonest
+    if cache.len() >= MAX_CACHE_SIZE {
+        let now = unix_now();
+        cache.retain(|_, entry| entry.expires_at > now);
+    }
+    if cache.len() >= MAX_CACHE_SIZE {
+        if let Some(oldest_key) = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.expires_at)
+            .map(|(key, _)| key.clone())
+        {
+            cache.remove(&oldest_key);
+        }
+    }
+
+    cache.insert(
+        cache_key(token, audiences),
+        VerifiedToken {
+            claims: claims.clone(),
+            user_context: user_context.clone(),
+            jwks_uri: jwks_uri.to_string(),
+            kid: kid.to_string(),
+            expires_at,
+        },
+    );
+}
+
+/// Drop tokens signed by keys that are no longer published at a JWKS URI
+pub async fn evict_rotated_keys(jwks_uri: &str, kids: &[&str]) {
+    TOKEN_CACHE
+        .write()
+        .await
+        .retain(|_, entry| entry.jwks_uri != jwks_uri || kids.contains(&entry.kid.as_str()));
+}
+
+/// Drop tokens whose claims match, e.g. because they have been revoked
+pub async fn evict(matches: impl Fn(&Claims) -> bool) {
+    TOKEN_CACHE
+        .write()
+        .await
+        .retain(|_, entry| !matches(&entry.claims));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn claims(sub: &str, exp: i64) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            iss: "https://example.authkit.app".to_string(),
+            aud: None,
+            exp,
+            iat: unix_now(),
+            email: None,
+            extra: serde_json::Value::Null,
+        }
+    }
+
+    async fn cached(token: &str) -> Option<i64> {
+        TOKEN_CACHE
+            .read()
+            .await
+            .get(&cache_key(token, &[]))
+            .map(|entry| entry.expires_at)
+    }
+
+    // One test, since every case shares the process-wide cache
+    #[test]
+    fn test_cache_lifecycle() {
+        block_on(async {
+            TOKEN_CACHE.write().await.clear();
+            let user = UserContext::default();
+            let now = unix_now();
+            let insert = |token: &'static str, kid: &'static str, ttl: u64, exp: i64| {
+                let claims = claims(token, exp);
+                let user = user.clone();
+                async move {
+                    insert(
+                        token,
+                        &[],
+                        "https://keys.example.com",
+                        kid,
+                        ttl,
+                        &claims,
+                        &user,
+                    )
+                    .await;
+                }
+            };
+
+            // Served for the TTL, but never past the token's exp
+            insert("ttl", "k1", 60, now + 3600).await;
+            assert!(cached("ttl")
+                .await
+                .is_some_and(|until| (now + 60..=now + 61).contains(&until)));
+            assert_eq!(
+                get("ttl", &[]).await.map(|(c, _)| c.sub).as_deref(),
+                Some("ttl")
+            );
+            insert("exp", "k1", 3600, now + 5).await;
+            assert_eq!(cached("exp").await, Some(now + 5));
+
+            // Expired tokens are not cached, and expired entries are dropped when read
+            insert("expired", "k1", 60, now).await;
+            assert!(cached("expired").await.is_none());
+            if let Some(entry) = TOKEN_CACHE.write().await.get_mut(&cache_key("ttl", &[])) {
+                entry.expires_at = now - 1;
+            }
+            assert!(get("ttl", &[]).await.is_none());
+            assert!(cached("ttl").await.is_none());
+
+            // Rotated keys and revoked claims evict their tokens
+            insert("rotated", "k2", 60, now + 3600).await;
+            evict_rotated_keys("https://other.example.com", &[]).await;
+            assert!(cached("rotated").await.is_some());
+            evict_rotated_keys("https://keys.example.com", &["k1"]).await;
+            assert!(cached("rotated").await.is_none());
+            assert!(cached("exp").await.is_some());
+            evict(|claims| claims.sub == "exp").await;
+            assert!(cached("exp").await.is_none());
+
+            // A full cache makes room by dropping the entry expiring soonest
+            insert("soonest", "k1", 10, now + 3600).await;
+            let filler = TOKEN_CACHE
+                .read()
+                .await
+                .get(&cache_key("soonest", &[]))
+                .cloned()
+                .map(|entry| VerifiedToken {
+                    expires_at: now + 1000,
+                    ..entry
+                });
+            TOKEN_CACHE.write().await.extend(
+                (1..MAX_CACHE_SIZE).filter_map(|i| Some((format!("filler-{i}"), filler.clone()?))),
+            );
+            assert_eq!(TOKEN_CACHE.read().await.len(), MAX_CACHE_SIZE);
+            insert("newest", "k1", 60, now + 3600).await;
+            assert_eq!(TOKEN_CACHE.read().await.len(), MAX_CACHE_SIZE);
+            assert!(cached("soonest").await.is_none());
+            assert!(cached("newest").await.is_some());
+
+            TOKEN_CACHE.write().await.clear();
+        });
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let audiences = vec!["https://mcp.example.com".to_string()];
+        let key = cache_key("a.b.c", &audiences);
+
+        assert_eq!(key.len(), 64);
+        assert!(!key.contains("a.b.c"));
+        assert_eq!(key, cache_key("a.b.c", &audiences));
+        assert_ne!(key, cache_key("a.b.c", &[]));
+        assert_ne!(key, cache_key("a.b.d", &audiences));
+    }
+}
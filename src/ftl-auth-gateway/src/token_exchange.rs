@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{
+    auth::{self, sha256_hex, unix_now, Claims},
+    introspection::{client_basic_auth, form_urlencode},
+    jsonrpc::{normalize_tool_name, RpcCall},
+    providers::{AuthProvider, ProviderRegistry},
+};
+
+/// RFC 8693 grant type and token type identifiers
+const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Lifetime assumed when the token endpoint does not return `expires_in`, in seconds
+const DEFAULT_EXPIRES_IN: i64 = 300;
+
+/// Downstream tokens stop being served this many seconds before they expire
+const EXPIRY_MARGIN: i64 = 30;
+
+/// RFC 8693 token exchange settings for a provider: the downstream token each tool receives
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenExchangeConfig {
+    /// Token endpoint; defaults to the provider's
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Downstream audience of each tool, keyed by tool name
+    pub tools: BTreeMap<String, ToolAudience>,
+}
+
+/// The audience, and optionally the scope, of a tool's downstream token
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolAudience {
+    pub audience: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl TokenExchangeConfig {
+    /// The downstream audience for a tool, if it gets a token
+    pub fn target(&self, tool: &str) -> Option<&ToolAudience> {
+        let tool = normalize_tool_name(tool);
+        self.tools
+            .iter()
+            .find(|(name, _)| normalize_tool_name(name) == tool)
+            .map(|(_, target)| target)
+    }
+
+    /// Check client credentials are set and every tool names an audience
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_id.trim().is_empty() || self.client_secret.is_empty() {
+            return Err("client_id and client_secret are required".to_string());
+        }
+        if self.tools.is_empty() {
+            return Err("tools must map at least one tool to an audience".to_string());
+        }
+        if let Some(tool) = self
+            .tools
+            .iter()
+            .find(|(_, target)| target.audience.trim().is_empty())
+            .map(|(tool, _)| tool)
+        {
+            return Err(format!("tool '{tool}' has an empty audience"));
+        }
+        Ok(())
+    }
+}
+
+/// Type alias for the exchange cache entry (downstream token and when it stops being served)
+type ExchangeCacheEntry = (String, i64);
+
+/// Type alias for the exchange cache
+type ExchangeCache = Arc<RwLock<HashMap<String, ExchangeCacheEntry>>>;
+
+/// Cache for downstream tokens, keyed by a hash of the subject token and the target
+static EXCHANGE_CACHE: Lazy<ExchangeCache> = Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Maximum number of downstream tokens to cache (prevent `DoS`)
+const MAX_CACHE_SIZE: usize = 1000;
+
+/// Cache key for a subject token and target; the raw token is never held as a key
+fn cache_key(subject_token: &str, target: &ToolAudience) -> String {
+    sha256_hex(&format!(
+        "{subject_token}\n{}\n{}",
+        target.audience,
+        target.scope.as_deref().unwrap_or_default()
+    ))
+}
+
+/// Form body of a token exchange request (RFC 8693, section 2.1)
+fn exchange_request_body(subject_token: &str, target: &ToolAudience) -> String {
+    let mut body = format!(
+        "grant_type={}&subject_token={}&subject_token_type={}&requested_token_type={}&audience={}",
+        form_urlencode(TOKEN_EXCHANGE_GRANT),
+        form_urlencode(subject_token),
+        form_urlencode(ACCESS_TOKEN_TYPE),
+        form_urlencode(ACCESS_TOKEN_TYPE),
+        form_urlencode(&target.audience)
+    );
+    if let Some(scope) = target.scope.as_deref() {
+        body.push_str("&scope=");
+        body.push_str(&form_urlencode(scope));
+    }
+    body
+}
+
+/// Successful token exchange response (RFC 8693, section 2.2.1)
+#[derive(Debug, Deserialize)]
+struct ExchangeResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+/// Call the token endpoint for a downstream token
+async fn fetch_exchange(
+    endpoint: &str,
+    config: &TokenExchangeConfig,
+    subject_token: &str,
+    target: &ToolAudience,
+) -> Result<ExchangeResponse> {
+    let request = spin_sdk::http::Request::builder()
+        .method(spin_sdk::http::Method::Post)
+        .uri(endpoint)
+        .header("Accept", "application/json")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header(
+            "Authorization",
+            client_basic_auth(&config.client_id, &config.client_secret),
+        )
+        .body(exchange_request_body(subject_token, target))
+        .build();
+
+    let response: spin_sdk::http::Response = spin_sdk::http::send(request)
+        .await
+        .map_err(|e| anyhow!("Failed to call token endpoint {endpoint}: {e}"))?;
+
+    if *response.status() != 200 {
+        let status = response.status();
+        return Err(anyhow!("Token exchange failed: HTTP {status}"));
+    }
+
+    Ok(serde_json::from_slice(response.body())?)
+}
+
+/// Exchange the client's token for a downstream token addressed to a tool's audience
+async fn exchange_token(
+    provider: &dyn AuthProvider,
+    config: &TokenExchangeConfig,
+    subject_token: &str,
+    subject_exp: i64,
+    target: &ToolAudience,
+) -> Result<String, String> {
+    let endpoint = config
+        .endpoint
+        .clone()
+        .unwrap_or_else(|| provider.discovery_metadata("").token_endpoint);
+
+    if !provider.allows_url(&endpoint) {
+        return Err(format!(
+            "Token endpoint {endpoint} is outside the allowed domains of provider '{}'",
+            provider.name()
+        ));
+    }
+
+    let key = cache_key(subject_token, target);
+    let now = unix_now();
+
+    // Check cache first
+    {
+        let cache = EXCHANGE_CACHE.read().await;
+        if let Some((token, expires_at)) = cache.get(&key) {
+            if *expires_at > now {
+                return Ok(token.clone());
+            }
+        }
+    }
+
+    let response = fetch_exchange(&endpoint, config, subject_token, target)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Downstream tokens are never served past the client's own token
+    let expires_at = now
+        .saturating_add(response.expires_in.unwrap_or(DEFAULT_EXPIRES_IN))
+        .saturating_sub(EXPIRY_MARGIN)
+        .min(subject_exp);
+    if expires_at > now {
+        let mut cache = EXCHANGE_CACHE.write().await;
+
+        if cache.len() >= MAX_CACHE_SIZE {
+            cache.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        if cache.len() < MAX_CACHE_SIZE {
+            cache.insert(key, (response.access_token.clone(), expires_at));
+        }
+    }
+
+    Ok(response.access_token)
+}
+
+/// Downstream tokens for the tools a request calls, keyed by tool name. A failed exchange is
+/// logged and the tool is called without a token.
+pub async fn tool_tokens(
+    req: &spin_sdk::http::Request,
+    registry: &ProviderRegistry,
+    claims: &Claims,
+    calls: &[RpcCall],
+) -> BTreeMap<String, String> {
+    let mut tokens = BTreeMap::new();
+
+    // API keys have no provider, so only provider tokens are exchanged
+    let Some((provider, config)) = registry
+        .find_by_issuer(&claims.iss)
+        .and_then(|provider| provider.token_exchange().map(|config| (provider, config)))
+    else {
+        return tokens;
+    };
+    let Some(subject_token) = auth::presented_token(req) else {
+        return tokens;
+    };
+
+    for tool in calls.iter().filter_map(|call| call.tool.as_deref()) {
+        let Some(target) = config.target(tool) else {
+            continue;
+        };
+        if tokens.contains_key(tool) {
+            continue;
+        }
+        match exchange_token(provider, config, subject_token, claims.exp, target).await {
+            Ok(token) => {
+                tokens.insert(tool.to_string(), token);
+            }
+            Err(e) => eprintln!("Token exchange for tool '{tool}' failed: {e}"),
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::introspection::form_urldecode;
+
+    fn config() -> TokenExchangeConfig {
+        serde_json::from_value(serde_json::json!({
+            "client_id": "gateway",
+            "client_secret": "secret",
+            "tools": {
+                "weather_rs": { "audience": "https://api.weather.example.com", "scope": "forecast" }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_target() {
+        let config = config();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config
+                .target("weather-rs")
+                .map(|target| target.audience.as_str()),
+            Some("https://api.weather.example.com")
+        );
+        assert!(config.target("echo").is_none());
+
+        let empty = TokenExchangeConfig {
+            tools: BTreeMap::new(),
+            ..config
+        };
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn test_exchange_request_body() {
+        let config = config();
+        let target = config.target("weather_rs").unwrap();
+        let body = form_urldecode(&exchange_request_body("a.b.c", target));
+        let param = |name: &str| {
+            body.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        assert_eq!(param("grant_type"), Some(TOKEN_EXCHANGE_GRANT));
+        assert_eq!(param("subject_token"), Some("a.b.c"));
+        assert_eq!(param("subject_token_type"), Some(ACCESS_TOKEN_TYPE));
+        assert_eq!(param("audience"), Some("https://api.weather.example.com"));
+        assert_eq!(param("scope"), Some("forecast"));
+        assert_ne!(
+            cache_key("a.b.c", target),
+            cache_key(
+                "a.b.c",
+                &ToolAudience {
+                    audience: target.audience.clone(),
+                    scope: None,
+                }
+            )
+        );
+    }
+}
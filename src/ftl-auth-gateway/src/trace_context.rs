@@ -0,0 +1,197 @@
+use spin_sdk::http::Request;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::auth::sha256_hex;
+
+/// W3C Trace Context headers
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// Longest `tracestate` passed on; 32 list members of up to 256 characters fit well within it
+const MAX_TRACESTATE_LEN: usize = 8192;
+
+/// Longest client trace ID kept for logs and the response
+const MAX_LOG_ID_LEN: usize = 256;
+
+/// Flags of a trace started by the gateway: `sampled`
+const DEFAULT_FLAGS: u8 = 0x01;
+
+/// Counter keeping generated IDs unique when no random source is available
+static FALLBACK_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// W3C Trace Context of a request (<https://www.w3.org/TR/trace-context/>).
+///
+/// The trace ID and flags are inherited from a valid incoming `traceparent`; otherwise the
+/// gateway starts a new trace. Either way the gateway is a span of its own, so the parent ID it
+/// passes on is freshly generated. A trace ID the client sends in its own header keeps tagging
+/// the logs and the response, whatever its format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits identifying the whole trace
+    pub trace_id: String,
+    /// 16 lowercase hex digits identifying the gateway's span
+    pub span_id: String,
+    /// Trace flags; bit 0 is `sampled`
+    pub flags: u8,
+    /// Vendor-specific trace state, passed on unchanged
+    pub state: Option<String>,
+    /// ID tagging logs and returned in `X-Trace-Id`: the client's trace ID header as sent, or
+    /// the trace ID
+    pub log_id: String,
+}
+
+impl TraceContext {
+    /// Continue the request's trace, or start one. A valid trace ID in `trace_id_header` starts
+    /// the trace when there is no `traceparent`.
+    pub fn from_request(req: &Request, trace_id_header: &str) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .and_then(|(_, value)| value.as_str())
+        };
+
+        let client_id = header(trace_id_header)
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_LOG_ID_LEN);
+
+        let (trace_id, flags, state) = match header(TRACEPARENT_HEADER).and_then(parse_traceparent)
+        {
+            Some((trace_id, flags)) => (
+                trace_id,
+                flags,
+                header(TRACESTATE_HEADER)
+                    .map(str::trim)
+                    .filter(|state| !state.is_empty() && state.len() <= MAX_TRACESTATE_LEN)
+                    .map(String::from),
+            ),
+            None => (
+                client_id
+                    .map(str::to_ascii_lowercase)
+                    .filter(|id| is_valid_id(id, 32))
+                    .unwrap_or_else(|| random_hex(16)),
+                DEFAULT_FLAGS,
+                None,
+            ),
+        };
+
+        Self {
+            log_id: client_id.map_or_else(|| trace_id.clone(), String::from),
+            trace_id,
+            span_id: random_hex(8),
+            flags,
+            state,
+        }
+    }
+
+    /// The `traceparent` header naming the gateway's span as the parent
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+/// Whether `id` is `len` lowercase hex digits and not all zeros, as trace and parent IDs must be
+fn is_valid_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && id.bytes().any(|b| b != b'0')
+}
+
+/// Parse a `traceparent` header into its trace ID and flags.
+///
+/// Version `00` has exactly four fields. Later versions may append fields, which are ignored;
+/// version `ff` is invalid.
+fn parse_traceparent(value: &str) -> Option<(String, u8)> {
+    let mut fields = value.trim().split('-');
+    let version = fields.next()?;
+    let trace_id = fields.next()?;
+    let parent_id = fields.next()?;
+    let flags = fields.next()?;
+
+    let valid_version = version.len() == 2
+        && version != "ff"
+        && version
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    if !valid_version || (version == "00" && fields.next().is_some()) {
+        return None;
+    }
+    if !is_valid_id(trace_id, 32) || !is_valid_id(parent_id, 16) || flags.len() != 2 {
+        return None;
+    }
+
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    Some((trace_id.to_string(), flags))
+}
+
+/// Random lowercase hex from the given number of bytes
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    if getrandom::getrandom(&mut bytes).is_err() || bytes.iter().all(|b| *b == 0) {
+        // Still unique without a random source: the time and a per-instance counter
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let count = FALLBACK_COUNTER.fetch_add(1, Ordering::Relaxed);
+        return sha256_hex(&format!("{nanos}:{count}"))
+            .chars()
+            .take(len * 2)
+            .collect();
+    }
+    bytes
+        .iter()
+        .fold(String::with_capacity(len * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Some(("4bf92f3577b34da6a3ce929d0e0e4736".to_string(), 1))
+        );
+        // Later versions may carry extra fields
+        assert_eq!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"),
+            Some(("4bf92f3577b34da6a3ce929d0e0e4736".to_string(), 0))
+        );
+
+        for invalid in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "gen-19806d27e75",
+        ] {
+            assert!(parse_traceparent(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_generated_ids() {
+        let first = random_hex(16);
+        assert!(is_valid_id(&first, 32));
+        assert_ne!(first, random_hex(16));
+
+        let context = TraceContext {
+            log_id: first.clone(),
+            trace_id: first,
+            span_id: random_hex(8),
+            flags: DEFAULT_FLAGS,
+            state: None,
+        };
+        let traceparent = context.traceparent();
+        assert_eq!(
+            parse_traceparent(&traceparent),
+            Some((context.trace_id, DEFAULT_FLAGS))
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    auth::{unix_now, Claims},
+    claims,
+};
+
+/// Clock skew tolerated on `exp`, `nbf` and `iat`, matching the `jsonwebtoken` default
+const DEFAULT_LEEWAY: u64 = 60;
+
+/// Checks applied to a provider's tokens on top of signature, issuer, audience and expiry
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidationPolicy {
+    /// Clock skew tolerated on time-based claims, in seconds
+    #[serde(default = "default_leeway")]
+    pub leeway: u64,
+    /// Claims that must be present with the given value, keyed by claim path. A claim holding
+    /// an array passes when it contains the value.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub required_claims: BTreeMap<String, Value>,
+    /// Oldest token accepted, in seconds since its `iat`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
+    /// Client IDs tokens may be issued to, read from `azp` or `client_id`; any when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorized_clients: Vec<String>,
+}
+
+const fn default_leeway() -> u64 {
+    DEFAULT_LEEWAY
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            leeway: DEFAULT_LEEWAY,
+            required_claims: BTreeMap::new(),
+            max_age: None,
+            authorized_clients: Vec::new(),
+        }
+    }
+}
+
+/// Why a verified token failed the validation policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    MissingClaim(String),
+    ClaimMismatch(String),
+    TooOld { age: i64, max_age: u64 },
+    IssuedInFuture { iat: i64 },
+    MissingClient,
+    UnauthorizedClient(String),
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingClaim(claim) => write!(f, "required claim '{claim}' is missing"),
+            Self::ClaimMismatch(claim) => {
+                write!(f, "required claim '{claim}' has an unexpected value")
+            }
+            Self::TooOld { age, max_age } => {
+                write!(f, "token issued {age}s ago exceeds max age of {max_age}s")
+            }
+            Self::IssuedInFuture { iat } => write!(f, "token iat {iat} is in the future"),
+            Self::MissingClient => write!(f, "token names no azp or client_id"),
+            Self::UnauthorizedClient(client) => {
+                write!(f, "client '{client}' is not authorized")
+            }
+        }
+    }
+}
+
+impl PolicyViolation {
+    /// Description returned to the client, which leaves out configured values
+    pub const fn description(&self) -> &'static str {
+        match self {
+            Self::MissingClaim(_) | Self::ClaimMismatch(_) => {
+                "Token does not carry the required claims"
+            }
+            Self::TooOld { .. } => "Token is too old",
+            Self::IssuedInFuture { .. } => "Token is not yet valid",
+            Self::MissingClient | Self::UnauthorizedClient(_) => {
+                "Token was issued to an unauthorized client"
+            }
+        }
+    }
+}
+
+/// Whether a claim holds the expected value, or contains it when the claim is an array
+fn claim_matches(actual: &Value, expected: &Value) -> bool {
+    actual == expected
+        || matches!(actual, Value::Array(values) if !expected.is_array() && values.contains(expected))
+}
+
+impl ValidationPolicy {
+    /// Leeway as a signed number of seconds
+    fn leeway_secs(&self) -> i64 {
+        i64::try_from(self.leeway).unwrap_or(i64::MAX)
+    }
+
+    /// Check a verified token's claims against the policy
+    pub fn check(&self, claims: &Claims) -> Result<(), PolicyViolation> {
+        self.check_at(claims, unix_now())
+    }
+
+    fn check_at(&self, claims: &Claims, now: i64) -> Result<(), PolicyViolation> {
+        if !self.required_claims.is_empty() {
+            let value = serde_json::to_value(claims).unwrap_or_default();
+            for (path, expected) in &self.required_claims {
+                let actual = claims::lookup(&value, path)
+                    .ok_or_else(|| PolicyViolation::MissingClaim(path.clone()))?;
+                if !claim_matches(actual, expected) {
+                    return Err(PolicyViolation::ClaimMismatch(path.clone()));
+                }
+            }
+        }
+
+        if let Some(max_age) = self.max_age {
+            let leeway = self.leeway_secs();
+            if claims.iat > now.saturating_add(leeway) {
+                return Err(PolicyViolation::IssuedInFuture { iat: claims.iat });
+            }
+            let age = now.saturating_sub(claims.iat);
+            if age
+                > i64::try_from(max_age)
+                    .unwrap_or(i64::MAX)
+                    .saturating_add(leeway)
+            {
+                return Err(PolicyViolation::TooOld { age, max_age });
+            }
+        }
+
+        if !self.authorized_clients.is_empty() {
+            let client = ["azp", "client_id"]
+                .iter()
+                .find_map(|claim| claims.extra.get(*claim).and_then(Value::as_str))
+                .ok_or(PolicyViolation::MissingClient)?;
+            if !self
+                .authorized_clients
+                .iter()
+                .any(|allowed| allowed == client)
+            {
+                return Err(PolicyViolation::UnauthorizedClient(client.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check every required claim and authorized client is named
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .required_claims
+            .keys()
+            .any(|path| path.trim().is_empty())
+        {
+            return Err("required claim paths must not be empty".to_string());
+        }
+        if self
+            .authorized_clients
+            .iter()
+            .any(|client| client.trim().is_empty())
+        {
+            return Err("authorized client IDs must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn claims(extra: Value) -> Claims {
+        Claims {
+            sub: "user_123".to_string(),
+            iss: "https://example.authkit.app".to_string(),
+            aud: None,
+            exp: NOW + 3600,
+            iat: NOW - 120,
+            email: Some("ada@example.com".to_string()),
+            extra,
+        }
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = ValidationPolicy::default();
+        assert_eq!(policy.leeway, 60);
+        assert!(policy.check_at(&claims(Value::Null), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_required_claims() {
+        let policy: ValidationPolicy = serde_json::from_value(serde_json::json!({
+            "required_claims": { "email_verified": true, "realm_access.roles": "mcp" }
+        }))
+        .unwrap();
+
+        let verified = claims(serde_json::json!({
+            "email_verified": true,
+            "realm_access": { "roles": ["mcp", "admin"] }
+        }));
+        assert!(policy.check_at(&verified, NOW).is_ok());
+
+        let unverified = claims(serde_json::json!({
+            "email_verified": false,
+            "realm_access": { "roles": ["mcp"] }
+        }));
+        assert_eq!(
+            policy.check_at(&unverified, NOW),
+            Err(PolicyViolation::ClaimMismatch("email_verified".to_string()))
+        );
+
+        let missing = claims(serde_json::json!({ "email_verified": true }));
+        assert_eq!(
+            policy.check_at(&missing, NOW),
+            Err(PolicyViolation::MissingClaim(
+                "realm_access.roles".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_max_age() {
+        let policy = ValidationPolicy {
+            leeway: 10,
+            max_age: Some(100),
+            ..ValidationPolicy::default()
+        };
+        let token = claims(Value::Null);
+
+        assert!(policy.check_at(&token, NOW - 15).is_ok());
+        assert!(policy.check_at(&token, NOW - 10).is_ok());
+        assert_eq!(
+            policy.check_at(&token, NOW),
+            Err(PolicyViolation::TooOld {
+                age: 120,
+                max_age: 100
+            })
+        );
+        assert!(matches!(
+            policy.check_at(&token, NOW - 200),
+            Err(PolicyViolation::IssuedInFuture { .. })
+        ));
+    }
+
+    #[test]
+    fn test_authorized_clients() {
+        let policy = ValidationPolicy {
+            authorized_clients: vec!["mcp-client".to_string()],
+            ..ValidationPolicy::default()
+        };
+
+        let azp = claims(serde_json::json!({ "azp": "mcp-client" }));
+        assert!(policy.check_at(&azp, NOW).is_ok());
+        let client_id = claims(serde_json::json!({ "client_id": "other" }));
+        assert_eq!(
+            policy.check_at(&client_id, NOW),
+            Err(PolicyViolation::UnauthorizedClient("other".to_string()))
+        );
+        assert_eq!(
+            policy.check_at(&claims(Value::Null), NOW),
+            Err(PolicyViolation::MissingClient)
+        );
+    }
+}
@@ -125,12 +125,12 @@ fn trace_id_header() {
     // Should return 401
     assert_eq!(response.status(), 401);
 
-    // Check for trace ID in response
+    // The client's trace ID is returned as sent, even though it is not a W3C trace ID
     let response_headers = response.headers();
     let has_trace = response_headers
         .entries()
         .iter()
-        .any(|(name, _)| name == "x-trace-id");
+        .any(|(name, value)| name == "x-trace-id" && value.as_slice() == b"test-trace-123");
     assert!(has_trace);
 }
 
@@ -178,6 +178,59 @@ fn metadata_endpoint_with_provider() {
     assert!(has_content_type);
 }
 
+/// POST a JSON-RPC request to `/mcp` without credentials
+fn anonymous_rpc(body: &str) -> http::types::IncomingResponse {
+    let headers = http::types::Headers::new();
+    headers.append("content-type", b"application/json").unwrap();
+
+    let request = http::types::OutgoingRequest::new(headers);
+    request.set_method(&http::types::Method::Post).unwrap();
+    request.set_path_with_query(Some("/mcp")).unwrap();
+    request.body().unwrap().write_bytes(body.as_bytes());
+    spin_test_sdk::perform_request(request)
+}
+
+/// Allow anonymous `tools/list` and `echo`, and answer for the MCP gateway
+fn anonymous_access_config() {
+    spin_test_virt::variables::set("auth_anonymous_methods", "initialize,tools/list");
+    spin_test_virt::variables::set("auth_anonymous_tools", "echo");
+
+    let response = http::types::OutgoingResponse::new(http::types::Headers::new());
+    response.write_body(br#"{"jsonrpc":"2.0","id":1,"result":{"tools":[]}}"#);
+    spin_test_virt::http_handler::set_response(
+        "http://ftl-mcp-gateway.spin.internal/mcp-internal",
+        spin_test_virt::http_handler::ResponseHandler::Response(response),
+    );
+}
+
+#[spin_test]
+fn anonymous_tools_list_is_forwarded() {
+    anonymous_access_config();
+
+    let response = anonymous_rpc(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#);
+
+    // Forwarded to the MCP gateway without credentials
+    assert_eq!(response.status(), 200);
+}
+
+#[spin_test]
+fn anonymous_call_of_non_public_tool_requires_token() {
+    anonymous_access_config();
+
+    let response = anonymous_rpc(
+        r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"delete_repo"}}"#,
+    );
+
+    // Only the listed tools may be called anonymously
+    assert_eq!(response.status(), 401);
+    let has_challenge = response
+        .headers()
+        .entries()
+        .iter()
+        .any(|(name, _)| name == "www-authenticate");
+    assert!(has_challenge);
+}
+
 #[spin_test]
 fn https_enforcement_rejects_http() {
     // Test that HTTP URLs are rejected for security
@@ -255,3 +308,44 @@ fn https_enforcement_oidc_urls() {
     // Should get an internal error because the component failed to initialize
     assert_eq!(response.status(), 500);
 }
+
+#[spin_test]
+fn structured_config_rejects_http_issuer() {
+    // A structured auth_config replaces the flat variables and is held to the same rules
+    spin_test_virt::variables::set(
+        "auth_config",
+        r#"{"providers": [{"type": "authkit", "issuer": "http://example.authkit.app"}]}"#,
+    );
+
+    let request = http::types::OutgoingRequest::new(http::types::Headers::new());
+    request.set_path_with_query(Some("/mcp")).unwrap();
+    let response = spin_test_sdk::perform_request(request);
+
+    // Should get an internal error because the configuration is invalid
+    assert_eq!(response.status(), 500);
+}
+
+#[spin_test]
+fn structured_config_lists_all_issuers() {
+    spin_test_virt::variables::set(
+        "auth_config",
+        r#"{"providers": [
+            {"type": "authkit", "issuer": "https://one.authkit.app"},
+            {"type": "authkit", "issuer": "https://two.authkit.app"}
+        ]}"#,
+    );
+
+    let request = http::types::OutgoingRequest::new(http::types::Headers::new());
+    request
+        .set_path_with_query(Some("/.well-known/oauth-protected-resource"))
+        .unwrap();
+    let response = spin_test_sdk::perform_request(request);
+    assert_eq!(response.status(), 200);
+
+    let body: serde_json::Value =
+        serde_json::from_str(&response.body_as_string().unwrap()).unwrap();
+    assert_eq!(
+        body["authorization_servers"],
+        serde_json::json!(["https://one.authkit.app", "https://two.authkit.app"])
+    );
+}
@@ -0,0 +1,206 @@
+use ftl_origin::origin_matches;
+use serde::{Deserialize, Serialize};
+use spin_sdk::http::{Request, Response};
+use spin_sdk::variables;
+
+/// Cross-origin resource sharing policy
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CorsConfig {
+    /// Allowed origins: exact origins, `https://*.example.com` patterns, or `*` for any
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+    /// Whether browsers may send credentials (requires explicit origins)
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Request headers browsers may send
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Response headers browser scripts may read
+    #[serde(default = "default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    /// How long browsers may cache preflight results, in seconds
+    #[serde(default = "default_max_age")]
+    pub max_age: u32,
+}
+
+fn default_allowed_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+/// Headers MCP clients send, including the Streamable HTTP session and version headers
+fn default_allowed_headers() -> Vec<String> {
+    [
+        "Content-Type",
+        "Authorization",
+        "Mcp-Session-Id",
+        "MCP-Protocol-Version",
+        "Last-Event-ID",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Headers MCP clients need to read from responses
+fn default_exposed_headers() -> Vec<String> {
+    ["Mcp-Session-Id", "MCP-Protocol-Version", "X-Trace-Id"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+const fn default_max_age() -> u32 {
+    86400
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: default_allowed_origins(),
+            allow_credentials: false,
+            allowed_headers: default_allowed_headers(),
+            exposed_headers: default_exposed_headers(),
+            max_age: default_max_age(),
+        }
+    }
+}
+
+/// The request's `Origin` header
+pub fn request_origin(req: &Request) -> Option<&str> {
+    req.headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("origin"))
+        .and_then(|(_, value)| value.as_str())
+}
+
+impl CorsConfig {
+    /// Load the CORS policy from Spin variables
+    pub fn from_spin_vars() -> anyhow::Result<Self> {
+        let list = |name: &str| -> Option<Vec<String>> {
+            variables::get(name)
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| {
+                    s.split(',')
+                        .map(|item| item.trim().to_string())
+                        .filter(|item| !item.is_empty())
+                        .collect()
+                })
+        };
+
+        let config = Self {
+            allowed_origins: list("cors_allowed_origins").unwrap_or_else(default_allowed_origins),
+            allow_credentials: variables::get("cors_allow_credentials")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse::<bool>()
+                .unwrap_or(false),
+            allowed_headers: list("cors_allowed_headers").unwrap_or_else(default_allowed_headers),
+            exposed_headers: list("cors_exposed_headers").unwrap_or_else(default_exposed_headers),
+            ..Self::default()
+        };
+
+        if config.allow_credentials && config.allowed_origins.iter().any(|o| o == "*") {
+            anyhow::bail!(
+                "cors_allow_credentials requires explicit origins in cors_allowed_origins"
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Whether any origin is allowed without reflecting it
+    fn allows_any_origin(&self) -> bool {
+        !self.allow_credentials && self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Value for `Access-Control-Allow-Origin`, if the origin is allowed
+    pub fn allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+        if self.allows_any_origin() {
+            return Some("*".to_string());
+        }
+
+        let origin = origin?;
+        self.allowed_origins
+            .iter()
+            .any(|pattern| origin_matches(pattern, origin))
+            .then(|| origin.to_string())
+    }
+
+    /// Add CORS headers to a response
+    pub fn apply(&self, response: &mut Response, origin: Option<&str>) {
+        // Responses differ per origin unless every origin gets `*`
+        if !self.allows_any_origin() {
+            response.set_header("Vary", "Origin");
+        }
+
+        let Some(allowed) = self.allowed_origin(origin) else {
+            return;
+        };
+
+        response.set_header("Access-Control-Allow-Origin", allowed);
+        if self.allow_credentials {
+            response.set_header("Access-Control-Allow-Credentials", "true");
+        }
+        if !self.exposed_headers.is_empty() {
+            response.set_header(
+                "Access-Control-Expose-Headers",
+                self.exposed_headers.join(", "),
+            );
+        }
+    }
+
+    /// Build the response to a CORS preflight request
+    pub fn preflight_response(&self, origin: Option<&str>) -> Response {
+        let mut response = Response::builder().status(204).build();
+
+        if self.allowed_origin(origin).is_some() {
+            response.set_header("Access-Control-Allow-Methods", "POST, OPTIONS");
+            response.set_header(
+                "Access-Control-Allow-Headers",
+                self.allowed_headers.join(", "),
+            );
+            response.set_header("Access-Control-Max-Age", self.max_age.to_string());
+        }
+        self.apply(&mut response, origin);
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(origins: &[&str], allow_credentials: bool) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| (*o).to_string()).collect(),
+            allow_credentials,
+            ..CorsConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_any_origin() {
+        let cors = CorsConfig::default();
+        assert_eq!(cors.allowed_origin(None), Some("*".to_string()));
+        assert_eq!(
+            cors.allowed_origin(Some("https://app.example.com")),
+            Some("*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_origin_allowlist() {
+        let cors = allowlist(&["https://app.example.com", "https://*.example.org"], true);
+        assert_eq!(
+            cors.allowed_origin(Some("https://app.example.com")),
+            Some("https://app.example.com".to_string())
+        );
+        assert_eq!(
+            cors.allowed_origin(Some("https://ide.example.org")),
+            Some("https://ide.example.org".to_string())
+        );
+        assert!(cors.allowed_origin(Some("https://example.org")).is_none());
+        assert!(cors.allowed_origin(Some("https://evil.com")).is_none());
+        assert!(cors.allowed_origin(None).is_none());
+    }
+}
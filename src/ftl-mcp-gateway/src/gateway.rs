@@ -1,12 +1,19 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use spin_sdk::http::{Method, Request, Response};
 use spin_sdk::variables;
 
+use crate::cors::{self, CorsConfig};
 use crate::mcp_types::{
     CallToolRequest, ErrorCode, InitializeRequest, InitializeResponse, JsonRpcRequest,
     JsonRpcResponse, ListToolsResponse, McpProtocolVersion, ServerCapabilities, ServerInfo,
     ToolContent, ToolMetadata, ToolResponse,
 };
+use crate::origin;
+use crate::tenants::{self, Tenant};
+use crate::tool_tokens::{self, TOOL_TOKEN_HEADER};
+use crate::trace_context;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GatewayConfig {
@@ -21,11 +28,77 @@ fn default_validate_arguments() -> bool {
 
 pub struct McpGateway {
     config: GatewayConfig,
+    tenant: Option<Tenant>,
+    /// Downstream tokens keyed by tool name, each passed only to its tool
+    tool_tokens: BTreeMap<String, String>,
+    /// Trace headers passed on to every tool request
+    trace_headers: Vec<(&'static str, String)>,
 }
 
 impl McpGateway {
     pub fn new(config: GatewayConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            tenant: None,
+            tool_tokens: BTreeMap::new(),
+            trace_headers: Vec::new(),
+        }
+    }
+
+    /// Bind the gateway to the request's tenant
+    pub fn with_tenant(mut self, tenant: Option<Tenant>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// Pass the downstream tokens the auth gateway exchanged for the request's tool calls
+    pub fn with_tool_tokens(mut self, tool_tokens: BTreeMap<String, String>) -> Self {
+        self.tool_tokens = tool_tokens;
+        self
+    }
+
+    /// Carry the request's trace to tool components
+    pub fn with_trace_headers(mut self, trace_headers: Vec<(&'static str, String)>) -> Self {
+        self.trace_headers = trace_headers;
+        self
+    }
+
+    /// Whether the request's tenant may use a tool
+    fn allows_tool(&self, tool_name: &str) -> bool {
+        self.tenant
+            .as_ref()
+            .map_or(true, |tenant| tenant.allows_tool(tool_name))
+    }
+
+    /// Build a request to a tool component, carrying the request's trace, identifying the
+    /// tenant when there is one and carrying the downstream token exchanged for the tool
+    fn tool_request(
+        &self,
+        method: Method,
+        tool_url: &str,
+        body: Option<Vec<u8>>,
+        tool_token: Option<&str>,
+    ) -> Request {
+        let mut builder = Request::builder();
+        builder.method(method).uri(tool_url);
+        for (name, value) in &self.trace_headers {
+            builder.header(*name, value);
+        }
+        if let Some(tenant) = &self.tenant {
+            for (name, value) in tenant.tool_headers() {
+                builder.header(name, value);
+            }
+        }
+        if let Some(token) = tool_token {
+            builder.header(TOOL_TOKEN_HEADER, token);
+        }
+        match body {
+            Some(body) => builder
+                .header("Content-Type", "application/json")
+                .body(body)
+                .build(),
+            None => builder.build(),
+        }
     }
 
     /// Convert `snake_case` to kebab-case for component names
@@ -38,10 +111,7 @@ impl McpGateway {
         let component_name = Self::snake_to_kebab(tool_name);
         let tool_url = format!("http://{component_name}.spin.internal/");
 
-        let req = Request::builder()
-            .method(Method::Get)
-            .uri(&tool_url)
-            .build();
+        let req = self.tool_request(Method::Get, &tool_url, None, None);
 
         match spin_sdk::http::send::<_, spin_sdk::http::Response>(req).await {
             Ok(resp) => {
@@ -188,8 +258,12 @@ impl McpGateway {
             }
         };
 
-        // Parse the comma-separated list of tool names
-        let tool_names: Vec<&str> = tool_components.split(',').map(str::trim).collect();
+        // Parse the comma-separated list of tool names, keeping the tenant's tools
+        let tool_names: Vec<&str> = tool_components
+            .split(',')
+            .map(str::trim)
+            .filter(|tool_name| self.allows_tool(tool_name))
+            .collect();
 
         // Create futures for fetching metadata from all tools in parallel
         let metadata_futures: Vec<_> = tool_names
@@ -214,6 +288,26 @@ impl McpGateway {
         }
     }
 
+    /// Validate arguments against the input schema in the tool's metadata
+    async fn check_tool_arguments(
+        &self,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<(), String> {
+        // Fetch tool metadata for validation
+        if let Some(tool_metadata) = self.fetch_tool_metadata(tool_name).await {
+            // Validate arguments against the tool's input schema
+            Self::validate_arguments(tool_name, &tool_metadata.input_schema, arguments)
+        } else {
+            // Tool metadata not available - log but continue
+            // This allows the tool itself to handle validation
+            eprintln!(
+                "Warning: Could not fetch metadata for tool '{tool_name}', skipping validation"
+            );
+            Ok(())
+        }
+    }
+
     async fn handle_call_tool(&self, request: JsonRpcRequest) -> JsonRpcResponse {
         let params: CallToolRequest = match request.params {
             Some(p) => match serde_json::from_value(p) {
@@ -235,30 +329,27 @@ impl McpGateway {
             }
         };
 
+        // Tools outside the tenant's set are rejected as if they did not exist
+        if !self.allows_tool(&params.name) {
+            return JsonRpcResponse::error(
+                request.id,
+                ErrorCode::INVALID_PARAMS.0,
+                &format!("Tool '{}' not found", params.name),
+            );
+        }
+
         // Validate arguments if validation is enabled
         let tool_arguments = params.arguments.unwrap_or_else(|| serde_json::json!({}));
 
         if self.config.validate_arguments {
-            // Fetch tool metadata for validation
-            if let Some(tool_metadata) = self.fetch_tool_metadata(&params.name).await {
-                // Validate arguments against the tool's input schema
-                if let Err(validation_error) = Self::validate_arguments(
-                    &params.name,
-                    &tool_metadata.input_schema,
-                    &tool_arguments,
-                ) {
-                    return JsonRpcResponse::error(
-                        request.id,
-                        ErrorCode::INVALID_PARAMS.0,
-                        &validation_error,
-                    );
-                }
-            } else {
-                // Tool metadata not available - log but continue
-                // This allows the tool itself to handle validation
-                eprintln!(
-                    "Warning: Could not fetch metadata for tool '{}', skipping validation",
-                    params.name
+            if let Err(validation_error) = self
+                .check_tool_arguments(&params.name, &tool_arguments)
+                .await
+            {
+                return JsonRpcResponse::error(
+                    request.id,
+                    ErrorCode::INVALID_PARAMS.0,
+                    &validation_error,
                 );
             }
         }
@@ -271,15 +362,15 @@ impl McpGateway {
         // Prepare the request body with just the arguments
         let tool_request_body = tool_arguments;
 
-        let req = Request::builder()
-            .method(Method::Post)
-            .uri(&tool_url)
-            .header("Content-Type", "application/json")
-            .body(
+        let req = self.tool_request(
+            Method::Post,
+            &tool_url,
+            Some(
                 serde_json::to_vec(&tool_request_body)
                     .unwrap_or_else(|_| br#"{"error":"Failed to serialize request"}"#.to_vec()),
-            )
-            .build();
+            ),
+            self.tool_tokens.get(&params.name).map(String::as_str),
+        );
 
         match spin_sdk::http::send::<_, spin_sdk::http::Response>(req).await {
             Ok(resp) => {
@@ -337,14 +428,30 @@ impl McpGateway {
     }
 }
 
-pub async fn handle_mcp_request(req: Request) -> Response {
+pub async fn handle_mcp_request(req: Request, cors: &CorsConfig) -> Response {
     // Handle CORS preflight
     if *req.method() == Method::Options {
+        return cors.preflight_response(cors::request_origin(&req));
+    }
+
+    // Reject browser requests from untrusted origins (DNS-rebinding protection)
+    let origin = cors::request_origin(&req);
+    if !origin::is_origin_allowed(origin, &origin::allowed_origins_from_spin_vars()) {
+        eprintln!(
+            "Rejected request from untrusted origin: {}",
+            origin.unwrap_or_default()
+        );
+        let error_response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": ErrorCode::INVALID_REQUEST.0,
+                "message": "Origin not allowed"
+            }
+        });
         return Response::builder()
-            .status(200)
-            .header("Access-Control-Allow-Origin", "*")
-            .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-            .header("Access-Control-Allow-Headers", "Content-Type")
+            .status(403)
+            .header("Content-Type", "application/json")
+            .body(error_response.to_string())
             .build();
     }
 
@@ -369,7 +476,23 @@ pub async fn handle_mcp_request(req: Request) -> Response {
             return Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
+                .body(serde_json::to_vec(&error_response).unwrap_or_else(|_| {
+                    br#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal serialization error"}}"#.to_vec()
+                }))
+                .build();
+        }
+    };
+
+    // Bind the request to its tenant when tenancy is configured
+    let tenant = match tenants::tenant_from_spin_vars(&req) {
+        Ok(tenant) => tenant,
+        Err(e) => {
+            eprintln!("Rejected request: {e}");
+            let error_response =
+                JsonRpcResponse::error(request.id, ErrorCode::INVALID_REQUEST.0, &e);
+            return Response::builder()
+                .status(403)
+                .header("Content-Type", "application/json")
                 .body(serde_json::to_vec(&error_response).unwrap_or_else(|_| {
                     br#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal serialization error"}}"#.to_vec()
                 }))
@@ -390,7 +513,10 @@ pub async fn handle_mcp_request(req: Request) -> Response {
         },
         validate_arguments,
     };
-    let gateway = McpGateway::new(config);
+    let gateway = McpGateway::new(config)
+        .with_tenant(tenant)
+        .with_tool_tokens(tool_tokens::tool_tokens_from_request(&req))
+        .with_trace_headers(trace_context::trace_headers(&req));
 
     // Handle the request
     gateway.handle_request(request).await.map_or_else(
@@ -399,7 +525,6 @@ pub async fn handle_mcp_request(req: Request) -> Response {
             Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
                 .body(Vec::new())
                 .build()
         },
@@ -407,7 +532,6 @@ pub async fn handle_mcp_request(req: Request) -> Response {
             Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .header("Access-Control-Allow-Origin", "*")
                 .body(serde_json::to_vec(&response).unwrap_or_else(|_| {
                     br#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal serialization error"}}"#.to_vec()
                 }))
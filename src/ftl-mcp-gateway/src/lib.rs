@@ -1,10 +1,22 @@
+mod cors;
 mod gateway;
 mod mcp_types;
+mod origin;
+mod tenants;
+mod tool_tokens;
+mod trace_context;
 
 use spin_sdk::http::{IntoResponse, Request};
 use spin_sdk::http_component;
 
 #[http_component]
 async fn handle_mcp_gateway(req: Request) -> anyhow::Result<impl IntoResponse> {
-    Ok(gateway::handle_mcp_request(req).await)
+    let cors = cors::CorsConfig::from_spin_vars()?;
+
+    // Every response carries the CORS headers for the request's origin
+    let origin = cors::request_origin(&req).map(String::from);
+    let mut response = gateway::handle_mcp_request(req, &cors).await;
+    cors.apply(&mut response, origin.as_deref());
+
+    Ok(response)
 }
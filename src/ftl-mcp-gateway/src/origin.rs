@@ -0,0 +1,79 @@
+use ftl_origin::{is_loopback_origin, origin_matches};
+use spin_sdk::variables;
+
+/// Validate a request's `Origin` header against DNS-rebinding attacks.
+///
+/// Requests without an `Origin` (non-browser clients, or the auth gateway forwarding
+/// internally) are allowed. Otherwise the origin must match the allowlist (`*` disables the
+/// check). With no allowlist configured, loopback origins are accepted so local development
+/// under `spin up` works out of the box.
+pub fn is_origin_allowed(origin: Option<&str>, allowed_origins: &[String]) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+
+    if allowed_origins
+        .iter()
+        .any(|pattern| origin_matches(pattern, origin))
+    {
+        return true;
+    }
+
+    allowed_origins.is_empty() && is_loopback_origin(origin)
+}
+
+/// Load the Origin allowlist from Spin variables
+pub fn allowed_origins_from_spin_vars() -> Vec<String> {
+    variables::get("allowed_origins")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_origin_is_allowed() {
+        assert!(is_origin_allowed(None, &[]));
+    }
+
+    #[test]
+    fn test_loopback_defaults() {
+        assert!(is_origin_allowed(Some("http://localhost:6274"), &[]));
+        assert!(is_origin_allowed(Some("https://LOCALHOST"), &[]));
+        assert!(is_origin_allowed(Some("http://127.0.0.1:3000"), &[]));
+        assert!(is_origin_allowed(Some("http://[::1]:3000"), &[]));
+        assert!(!is_origin_allowed(
+            Some("http://evil.example.com:3000"),
+            &[]
+        ));
+        assert!(!is_origin_allowed(Some("http://localhost.evil.com"), &[]));
+        assert!(!is_origin_allowed(Some("http://127.0.0.1.evil.com"), &[]));
+        assert!(!is_origin_allowed(Some("file://localhost"), &[]));
+        assert!(!is_origin_allowed(Some("localhost"), &[]));
+        assert!(!is_origin_allowed(Some("null"), &[]));
+
+        // An explicit allowlist replaces the loopback defaults
+        let allowed = vec!["https://app.example.com".to_string()];
+        assert!(!is_origin_allowed(Some("http://localhost:6274"), &allowed));
+        assert!(is_origin_allowed(Some("https://app.example.com"), &allowed));
+        assert!(!is_origin_allowed(
+            Some("https://evil.example.com"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_disables_check() {
+        let allowed = vec!["*".to_string()];
+        assert!(is_origin_allowed(
+            Some("https://anything.example"),
+            &allowed
+        ));
+        assert!(is_origin_allowed(Some("null"), &allowed));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use spin_sdk::http::Request;
+use spin_sdk::variables;
+
+/// Header the auth gateway binds a request to a tenant with
+const TENANT_HEADER: &str = "x-ftl-tenant";
+
+/// Header carrying the auth gateway's HMAC-SHA256 of the tenant ID, keyed with `tenant_secret`
+const TENANT_SIGNATURE_HEADER: &str = "x-ftl-tenant-signature";
+
+/// Headers passing the tenant to tool components
+const TOOL_TENANT_HEADER: &str = "X-FTL-Tenant";
+const TOOL_NAMESPACE_HEADER: &str = "X-FTL-Tenant-Namespace";
+const TOOL_VARIABLES_HEADER: &str = "X-FTL-Tenant-Variables";
+
+/// Per-tenant settings from the `tenants` variable
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Tools the tenant may list and call; every configured tool when unset
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    /// Variable overrides passed to the tenant's tool components
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Prefix for the tenant's key-value keys; the tenant ID when unset
+    #[serde(default)]
+    pub kv_namespace: Option<String>,
+}
+
+/// The tenant a request is bound to
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub id: String,
+    config: TenantConfig,
+}
+
+/// Tool names are matched regardless of `snake_case` or kebab-case
+fn normalize_tool_name(name: &str) -> String {
+    name.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Tenant IDs and namespaces end up in headers and storage keys, so they are kept simple
+fn is_valid_identifier(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 128
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+impl Tenant {
+    /// Whether the tenant may list and call a tool
+    pub fn allows_tool(&self, tool_name: &str) -> bool {
+        let tool_name = normalize_tool_name(tool_name);
+        self.config.tools.as_ref().map_or(true, |tools| {
+            tools
+                .iter()
+                .any(|allowed| normalize_tool_name(allowed) == tool_name)
+        })
+    }
+
+    /// Prefix for the tenant's key-value keys
+    pub fn namespace(&self) -> &str {
+        self.config.kv_namespace.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Headers identifying the tenant to tool components
+    pub fn tool_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            (TOOL_TENANT_HEADER, self.id.clone()),
+            (TOOL_NAMESPACE_HEADER, self.namespace().to_string()),
+        ];
+        if !self.config.variables.is_empty() {
+            if let Ok(variables) = serde_json::to_string(&self.config.variables) {
+                headers.push((TOOL_VARIABLES_HEADER, variables));
+            }
+        }
+        headers
+    }
+}
+
+/// Tenant configuration from the `tenants` variable, a JSON object keyed by tenant ID. An
+/// empty map leaves tenancy off.
+fn tenants_from_spin_vars() -> anyhow::Result<BTreeMap<String, TenantConfig>> {
+    let Some(raw) = variables::get("tenants")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+    else {
+        return Ok(BTreeMap::new());
+    };
+
+    let tenants: BTreeMap<String, TenantConfig> =
+        serde_json::from_str(&raw).context("tenants must be a JSON object keyed by tenant ID")?;
+
+    for (id, config) in &tenants {
+        if !is_valid_identifier(id) {
+            anyhow::bail!("Invalid tenant ID '{id}': use letters, digits, '-', '_' or '.'");
+        }
+        if let Some(namespace) = &config.kv_namespace {
+            if !is_valid_identifier(namespace) {
+                anyhow::bail!("Invalid kv_namespace '{namespace}' for tenant '{id}'");
+            }
+        }
+    }
+
+    Ok(tenants)
+}
+
+/// Bind a request to its tenant with the `tenants` and `tenant_secret` variables
+pub fn tenant_from_spin_vars(req: &Request) -> Result<Option<Tenant>, String> {
+    let tenants =
+        tenants_from_spin_vars().map_err(|e| format!("Invalid tenant configuration: {e:#}"))?;
+    let secret = variables::get("tenant_secret")
+        .ok()
+        .filter(|s| !s.trim().is_empty());
+    resolve_tenant(req, &tenants, secret.as_deref())
+}
+
+/// Bind a request to its tenant.
+///
+/// With tenancy off any tenant header is ignored. With tenancy on the request must name a
+/// configured tenant, and the header must be signed with the secret shared with the auth gateway:
+/// the gateway may be reachable without passing through the auth gateway, so the header alone
+/// proves nothing.
+fn resolve_tenant(
+    req: &Request,
+    tenants: &BTreeMap<String, TenantConfig>,
+    secret: Option<&str>,
+) -> Result<Option<Tenant>, String> {
+    if tenants.is_empty() {
+        return Ok(None);
+    }
+
+    let header = |wanted: &str| {
+        req.headers()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .and_then(|(_, value)| value.as_str())
+    };
+    let id = header(TENANT_HEADER);
+    if let Some(id) = id {
+        verify_signature(id, header(TENANT_SIGNATURE_HEADER), secret)?;
+    }
+    tenant_by_id(id, tenants)
+}
+
+/// Check the auth gateway's signature of a tenant header value, in constant time
+fn verify_signature(id: &str, signature: Option<&str>, secret: Option<&str>) -> Result<(), String> {
+    let secret = secret.ok_or_else(|| {
+        "Tenant headers cannot be verified: tenant_secret is not configured".to_string()
+    })?;
+    let signature = signature
+        .and_then(decode_hex)
+        .ok_or_else(|| "Tenant header is not signed by the auth gateway".to_string())?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|_| "Invalid tenant_secret".to_string())?;
+    mac.update(id.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| "Tenant header is not signed by the auth gateway".to_string())
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            value
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect()
+}
+
+/// Look up the tenant named by the tenant header, if tenancy is on
+fn tenant_by_id(
+    id: Option<&str>,
+    tenants: &BTreeMap<String, TenantConfig>,
+) -> Result<Option<Tenant>, String> {
+    if tenants.is_empty() {
+        return Ok(None);
+    }
+
+    let id = id
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| "Request is not bound to a tenant".to_string())?;
+
+    tenants.get(id).map_or_else(
+        || Err(format!("Unknown tenant '{id}'")),
+        |config| {
+            Ok(Some(Tenant {
+                id: id.to_string(),
+                config: config.clone(),
+            }))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenants() -> BTreeMap<String, TenantConfig> {
+        serde_json::from_value(serde_json::json!({
+            "acme": { "tools": ["weather_rs", " Echo "], "variables": { "region": "eu" } },
+            "globex": { "kv_namespace": "globex-prod" }
+        }))
+        .unwrap_or_default()
+    }
+
+    fn tenant(id: &str, tenants: &BTreeMap<String, TenantConfig>) -> Option<Tenant> {
+        tenant_by_id(Some(id), tenants).ok().flatten()
+    }
+
+    #[test]
+    fn test_tenancy_off() {
+        let tenants = BTreeMap::new();
+        assert!(matches!(tenant_by_id(None, &tenants), Ok(None)));
+        assert!(matches!(tenant_by_id(Some("acme"), &tenants), Ok(None)));
+    }
+
+    #[test]
+    fn test_missing_or_unknown_tenant() {
+        let tenants = tenants();
+        assert!(tenant_by_id(None, &tenants).is_err());
+        assert!(tenant_by_id(Some("  "), &tenants).is_err());
+        assert_eq!(
+            tenant_by_id(Some("initech"), &tenants).err().as_deref(),
+            Some("Unknown tenant 'initech'")
+        );
+        // Tenant IDs are matched exactly
+        assert!(tenant_by_id(Some("ACME"), &tenants).is_err());
+
+        let acme = tenant(" acme ", &tenants);
+        assert_eq!(acme.as_ref().map(|t| t.id.as_str()), Some("acme"));
+        assert_eq!(acme.as_ref().map(Tenant::namespace), Some("acme"));
+        assert!(acme.is_some_and(|t| t
+            .tool_headers()
+            .contains(&(TOOL_VARIABLES_HEADER, r#"{"region":"eu"}"#.to_string()))));
+
+        let globex = tenant("globex", &tenants);
+        assert_eq!(globex.as_ref().map(Tenant::namespace), Some("globex-prod"));
+        assert_eq!(globex.map(|t| t.tool_headers().len()), Some(2));
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        // RFC 4231 test case 2, as the auth gateway computes it
+        let signature = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        let id = "what do ya want for nothing?";
+        assert!(verify_signature(id, Some(signature), Some("Jefe")).is_ok());
+        assert!(verify_signature(id, Some(&signature.to_uppercase()), Some("Jefe")).is_ok());
+
+        // Without a secret, a signature or with a signature of another tenant, tenancy is refused
+        assert!(verify_signature(id, Some(signature), None).is_err());
+        assert!(verify_signature(id, None, Some("Jefe")).is_err());
+        assert!(verify_signature(id, Some(signature), Some("other")).is_err());
+        assert!(verify_signature("acme", Some(signature), Some("Jefe")).is_err());
+        assert!(verify_signature(id, Some("5bdc"), Some("Jefe")).is_err());
+        assert!(verify_signature(id, Some("not hex"), Some("Jefe")).is_err());
+    }
+
+    #[test]
+    fn test_allows_tool() {
+        let tenants = tenants();
+        let allows =
+            |id: &str, tool: &str| tenant(id, &tenants).is_some_and(|t| t.allows_tool(tool));
+        assert!(allows("acme", "weather-rs"));
+        assert!(allows("acme", "WEATHER_RS"));
+        assert!(allows("acme", "echo"));
+        assert!(!allows("acme", "weather"));
+        assert!(!allows("acme", "calculator"));
+
+        // Without a tool list every tool is allowed
+        assert!(allows("globex", "calculator"));
+    }
+
+    #[test]
+    fn test_is_valid_identifier() {
+        assert!(is_valid_identifier("acme-corp_1.eu"));
+        assert!(!is_valid_identifier(""));
+        assert!(!is_valid_identifier("acme corp"));
+        assert!(!is_valid_identifier("acme:prod"));
+        assert!(!is_valid_identifier(&"a".repeat(129)));
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use spin_sdk::http::Request;
+
+/// Header the auth gateway passes downstream tokens in, a JSON object keyed by tool name
+const TOOL_TOKENS_HEADER: &str = "x-ftl-tool-tokens";
+
+/// Header passing a tool the downstream token exchanged for it
+pub const TOOL_TOKEN_HEADER: &str = "X-FTL-Tool-Token";
+
+/// Downstream tokens the auth gateway exchanged for the request's tool calls.
+///
+/// The auth gateway strips any value the client sent. A missing or malformed header yields no
+/// tokens, so tools are called without one.
+pub fn tool_tokens_from_request(req: &Request) -> BTreeMap<String, String> {
+    parse_tool_tokens(
+        req.headers()
+            .find(|(name, _)| name.eq_ignore_ascii_case(TOOL_TOKENS_HEADER))
+            .and_then(|(_, value)| value.as_str()),
+    )
+}
+
+/// Parse the tool tokens header, a JSON object of token strings keyed by tool name
+fn parse_tool_tokens(value: Option<&str>) -> BTreeMap<String, String> {
+    value
+        .and_then(|value| serde_json::from_str(value).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_tokens() {
+        let tokens = parse_tool_tokens(Some(r#"{"weather_rs":"a.b.c","echo":"d.e.f"}"#));
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens.get("weather_rs").map(String::as_str), Some("a.b.c"));
+
+        assert!(parse_tool_tokens(None).is_empty());
+        assert!(parse_tool_tokens(Some("{}")).is_empty());
+    }
+
+    #[test]
+    fn test_malformed_tool_tokens() {
+        for malformed in [
+            "",
+            "a.b.c",
+            "{\"weather_rs\":",
+            r#"["a.b.c"]"#,
+            r#"{"weather_rs":42}"#,
+            r#"{"weather_rs":{"token":"a.b.c"}}"#,
+            "null",
+        ] {
+            assert!(parse_tool_tokens(Some(malformed)).is_empty(), "{malformed}");
+        }
+    }
+}
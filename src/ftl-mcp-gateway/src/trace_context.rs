@@ -0,0 +1,60 @@
+use spin_sdk::http::Request;
+
+/// Headers carrying the request's trace: W3C Trace Context and the auth gateway's trace ID
+const TRACE_HEADERS: &[&str] = &["traceparent", "tracestate", "X-Trace-Id"];
+
+/// Longest trace header value passed on
+const MAX_TRACE_HEADER_LEN: usize = 8192;
+
+/// The request's trace headers, passed unchanged to tool components so their logs and
+/// downstream calls join the same trace. The auth gateway sets them, replacing any the client
+/// sent.
+pub fn trace_headers(req: &Request) -> Vec<(&'static str, String)> {
+    select_trace_headers(|name| {
+        req.headers()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| value.as_str())
+    })
+}
+
+/// The trace headers found by `header`, skipping empty and oversized values
+fn select_trace_headers<'a>(
+    header: impl Fn(&str) -> Option<&'a str>,
+) -> Vec<(&'static str, String)> {
+    TRACE_HEADERS
+        .iter()
+        .filter_map(|&name| {
+            header(name)
+                .map(str::trim)
+                .filter(|value| !value.is_empty() && value.len() <= MAX_TRACE_HEADER_LEN)
+                .map(|value| (name, value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_trace_headers() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let oversized = "a".repeat(MAX_TRACE_HEADER_LEN + 1);
+        let headers = select_trace_headers(|name| match name {
+            "traceparent" => Some(traceparent),
+            "tracestate" => Some(oversized.as_str()),
+            "X-Trace-Id" => Some(" client-trace-1 "),
+            _ => None,
+        });
+        assert_eq!(
+            headers,
+            vec![
+                ("traceparent", traceparent.to_string()),
+                ("X-Trace-Id", "client-trace-1".to_string()),
+            ]
+        );
+
+        assert!(select_trace_headers(|_| Some("  ")).is_empty());
+        assert!(select_trace_headers(|_| None).is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+//! Origin matching shared by the FTL gateways, so their CORS policies and DNS-rebinding checks
+//! accept exactly the same origins.
+
+/// Whether an origin matches an allowlist entry: an exact origin, `https://*.example.com` for
+/// any subdomain over the same scheme, or `*` for any origin
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+
+    // `https://*.example.com` matches any subdomain over the same scheme
+    if let Some((scheme, suffix)) = pattern.split_once("://*.") {
+        return origin
+            .split_once("://")
+            .is_some_and(|(origin_scheme, host)| {
+                origin_scheme.eq_ignore_ascii_case(scheme)
+                    && host.len() > suffix.len() + 1
+                    && host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", suffix.to_ascii_lowercase()))
+            });
+    }
+
+    pattern.eq_ignore_ascii_case(origin)
+}
+
+/// Whether an origin points at the local machine (`localhost`, `127.0.0.1` or `[::1]`)
+pub fn is_loopback_origin(origin: &str) -> bool {
+    let Some((scheme, authority)) = origin.split_once("://") else {
+        return false;
+    };
+    if !matches!(scheme, "http" | "https") {
+        return false;
+    }
+
+    let host = if authority.starts_with('[') {
+        authority
+            .split_once(']')
+            .map_or(authority, |(host, _)| host.trim_start_matches('['))
+    } else {
+        authority
+            .split_once(':')
+            .map_or(authority, |(host, _)| host)
+    };
+
+    host.eq_ignore_ascii_case("localhost") || host == "127.0.0.1" || host == "::1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_matches() {
+        assert!(origin_matches("*", "https://anything.example"));
+        assert!(origin_matches(
+            "https://app.example.com",
+            "HTTPS://App.Example.com"
+        ));
+        assert!(!origin_matches(
+            "https://app.example.com",
+            "https://app.example.com:8443"
+        ));
+
+        let pattern = "https://*.example.com";
+        assert!(origin_matches(pattern, "https://ide.example.com"));
+        assert!(origin_matches(pattern, "https://a.b.example.com"));
+        assert!(origin_matches(pattern, "HTTPS://IDE.EXAMPLE.COM"));
+        // The bare domain, other schemes and look-alike domains do not match
+        assert!(!origin_matches(pattern, "https://example.com"));
+        assert!(!origin_matches(pattern, "https://.example.com"));
+        assert!(!origin_matches(pattern, "http://ide.example.com"));
+        assert!(!origin_matches(pattern, "https://evil-example.com"));
+        assert!(!origin_matches(pattern, "https://example.com.evil.com"));
+        assert!(!origin_matches(pattern, "ide.example.com"));
+    }
+
+    #[test]
+    fn test_is_loopback_origin() {
+        assert!(is_loopback_origin("http://localhost:6274"));
+        assert!(is_loopback_origin("https://LOCALHOST"));
+        assert!(is_loopback_origin("http://127.0.0.1:3000"));
+        assert!(is_loopback_origin("http://[::1]:3000"));
+        assert!(!is_loopback_origin("http://evil.example.com:3000"));
+        assert!(!is_loopback_origin("http://localhost.evil.com"));
+        assert!(!is_loopback_origin("http://127.0.0.1.evil.com"));
+        assert!(!is_loopback_origin("file://localhost"));
+        assert!(!is_loopback_origin("localhost"));
+        assert!(!is_loopback_origin("null"));
+    }
+}
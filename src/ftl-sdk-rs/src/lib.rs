@@ -7,9 +7,27 @@
 // Re-export macros when the feature is enabled
 #[cfg(feature = "macros")]
 pub use ftl_sdk_macros::tool;
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+/// Header carrying the tenant ID the gateway bound a tool call to
+pub const TENANT_HEADER: &str = "X-FTL-Tenant";
+
+/// Header carrying the tenant's key-value namespace
+pub const TENANT_NAMESPACE_HEADER: &str = "X-FTL-Tenant-Namespace";
+
+/// Header carrying the tenant's variable overrides as a JSON object
+pub const TENANT_VARIABLES_HEADER: &str = "X-FTL-Tenant-Variables";
+
+/// Header carrying the downstream token the auth gateway exchanged for this tool
+pub const TOOL_TOKEN_HEADER: &str = "X-FTL-Tool-Token";
+
+/// W3C Trace Context headers the gateway passes the request's trace in
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
 /// Tool metadata returned by GET requests to tool endpoints
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolMetadata {
@@ -220,6 +238,163 @@ impl ToolContent {
     }
 }
 
+/// The tenant a tool call is bound to, passed by the MCP gateway in request headers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenantContext {
+    /// Tenant ID
+    pub id: String,
+
+    /// Prefix for the tenant's key-value keys
+    pub namespace: String,
+
+    /// Variable overrides configured for the tenant
+    pub variables: BTreeMap<String, String>,
+}
+
+impl TenantContext {
+    /// Read the tenant from request headers, or `None` when the call is not tenant-bound
+    pub fn from_headers<'a>(
+        headers: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    ) -> Option<Self> {
+        let mut context = Self::default();
+        for (name, value) in headers {
+            let Ok(value) = std::str::from_utf8(value) else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case(TENANT_HEADER) {
+                context.id = value.trim().to_string();
+            } else if name.eq_ignore_ascii_case(TENANT_NAMESPACE_HEADER) {
+                context.namespace = value.trim().to_string();
+            } else if name.eq_ignore_ascii_case(TENANT_VARIABLES_HEADER) {
+                context.variables = serde_json::from_str(value).unwrap_or_default();
+            }
+        }
+
+        if context.id.is_empty() {
+            return None;
+        }
+        if context.namespace.is_empty() {
+            context.namespace.clone_from(&context.id);
+        }
+        Some(context)
+    }
+
+    /// A variable override for this tenant; fall back to the component's own variable when
+    /// this returns `None`
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    /// A key-value key scoped to this tenant's namespace
+    pub fn key(&self, key: &str) -> String {
+        format!("{}:{key}", self.namespace)
+    }
+}
+
+/// The W3C trace a tool call belongs to, passed by the gateway in request headers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits identifying the whole trace
+    pub trace_id: String,
+
+    /// 16 lowercase hex digits identifying the calling span
+    pub parent_id: String,
+
+    /// 16 lowercase hex digits identifying this tool call's own span, a child of `parent_id`
+    pub span_id: String,
+
+    /// Trace flags; bit 0 is `sampled`
+    pub flags: u8,
+
+    /// Vendor-specific trace state
+    pub state: Option<String>,
+}
+
+impl TraceContext {
+    /// Read the trace from request headers, or `None` without a valid `traceparent`
+    pub fn from_headers<'a>(
+        headers: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    ) -> Option<Self> {
+        let mut traceparent = None;
+        let mut state = None;
+        for (name, value) in headers {
+            let Ok(value) = std::str::from_utf8(value) else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+                traceparent = Some(value.trim());
+            } else if name.eq_ignore_ascii_case(TRACESTATE_HEADER) {
+                state = Some(value.trim()).filter(|state| !state.is_empty());
+            }
+        }
+
+        let mut fields = traceparent?.split('-');
+        let (version, trace_id, parent_id, flags) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        let is_id = |id: &str, len: usize| {
+            id.len() == len
+                && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+                && id.bytes().any(|b| b != b'0')
+        };
+        if version.len() != 2
+            || version == "ff"
+            || (version == "00" && fields.next().is_some())
+            || !is_id(trace_id, 32)
+            || !is_id(parent_id, 16)
+            || flags.len() != 2
+        {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            span_id: new_span_id(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+            state: state.map(String::from),
+        })
+    }
+
+    /// Whether the caller is recording this trace
+    pub const fn is_sampled(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// The `traceparent` header to send with a downstream call made for this tool call, naming
+    /// the tool call's span as the parent
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+/// A random span ID: 16 lowercase hex digits, never all zeros
+fn new_span_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    // Every `RandomState` is keyed from the host's random source, so hashing nothing with a
+    // fresh one yields a random value without another dependency
+    let id = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    format!("{:016x}", id.max(1))
+}
+
+/// Read the downstream token for calling the tool's API on the user's behalf, or `None` when
+/// the gateway did not exchange one for this call
+pub fn tool_token<'a>(headers: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Option<String> {
+    headers
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(TOOL_TOKEN_HEADER))
+        .and_then(|(_, value)| std::str::from_utf8(value).ok())
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(String::from)
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -261,4 +436,72 @@ mod tests {
         assert!(json.contains("\"title\":\"Test Tool\""));
         assert!(!json.contains("\"description\""));
     }
+
+    #[test]
+    fn test_tenant_context() {
+        let headers = [
+            ("x-ftl-tenant", b"acme".as_slice()),
+            (
+                "X-FTL-Tenant-Variables",
+                br#"{"api_base":"https://acme.example.com"}"#,
+            ),
+        ];
+        let tenant = TenantContext::from_headers(headers).unwrap();
+        assert_eq!(tenant.id, "acme");
+        assert_eq!(tenant.key("session:1"), "acme:session:1");
+        assert_eq!(
+            tenant.variable("api_base"),
+            Some("https://acme.example.com")
+        );
+        assert!(tenant.variable("missing").is_none());
+
+        assert!(
+            TenantContext::from_headers([("content-type", b"text/plain".as_slice())]).is_none()
+        );
+    }
+
+    #[test]
+    fn test_trace_context() {
+        let headers = [
+            (
+                "traceparent",
+                b"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".as_slice(),
+            ),
+            ("tracestate", b"vendor=value"),
+        ];
+        let trace = TraceContext::from_headers(headers).unwrap();
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(trace.parent_id, "00f067aa0ba902b7");
+        assert!(trace.is_sampled());
+        assert_eq!(trace.state.as_deref(), Some("vendor=value"));
+
+        // Downstream calls are children of the tool call's own span
+        assert_eq!(trace.span_id.len(), 16);
+        assert_ne!(trace.span_id, trace.parent_id);
+        assert_eq!(
+            trace.traceparent(),
+            format!("00-4bf92f3577b34da6a3ce929d0e0e4736-{}-01", trace.span_id)
+        );
+        assert_ne!(
+            TraceContext::from_headers(headers).map(|other| other.span_id),
+            Some(trace.span_id)
+        );
+
+        assert!(TraceContext::from_headers([(
+            "traceparent",
+            b"00-00000000000000000000000000000000-00f067aa0ba902b7-01".as_slice()
+        )])
+        .is_none());
+        assert!(TraceContext::from_headers([("x-trace-id", b"gen-1".as_slice())]).is_none());
+    }
+
+    #[test]
+    fn test_tool_token() {
+        let headers = [
+            ("content-type", b"application/json".as_slice()),
+            ("x-ftl-tool-token", b"downstream-token"),
+        ];
+        assert_eq!(tool_token(headers).as_deref(), Some("downstream-token"));
+        assert!(tool_token([("x-ftl-tool-token", b" ".as_slice())]).is_none());
+    }
 }
@@ -276,6 +276,71 @@ export function isResourceContent(content: ToolContent): content is ResourceCont
   return content.type === 'resource'
 }
 
+/**
+ * The W3C trace a tool call belongs to, passed by the gateway in request headers
+ */
+export interface TraceContext {
+  /** 32 lowercase hex digits identifying the whole trace */
+  traceId: string
+  /** 16 lowercase hex digits identifying the calling span */
+  parentId: string
+  /** 16 lowercase hex digits identifying this tool call's own span, a child of `parentId` */
+  spanId: string
+  /** Trace flags; bit 0 is `sampled` */
+  flags: number
+  /** Vendor-specific trace state */
+  state?: string
+  /** The `traceparent` header to send with downstream calls, naming `spanId` as the parent */
+  traceparent: string
+}
+
+/** A random span ID: 16 lowercase hex digits, never all zeros */
+function newSpanId(): string {
+  const bytes = crypto.getRandomValues(new Uint8Array(8))
+  if (bytes.every((byte) => byte === 0)) {
+    bytes[7] = 1
+  }
+  return Array.from(bytes, (byte) => byte.toString(16).padStart(2, '0')).join('')
+}
+
+const TRACEPARENT = /^([0-9a-f]{2})-([0-9a-f]{32})-([0-9a-f]{16})-([0-9a-f]{2})(-.*)?$/
+
+/**
+ * Reads the trace from request headers, or `undefined` without a valid `traceparent`.
+ *
+ * @example
+ * ```typescript
+ * const trace = getTraceContext(request.headers)
+ * await fetch(apiUrl, { headers: trace ? { traceparent: trace.traceparent } : {} })
+ * ```
+ */
+export function getTraceContext(headers: Headers): TraceContext | undefined {
+  const match = TRACEPARENT.exec(headers.get('traceparent')?.trim() ?? '')
+  if (!match) {
+    return undefined
+  }
+  const [, version, traceId, parentId, flags, rest] = match
+  const invalid =
+    version === 'ff' ||
+    (version === '00' && rest !== undefined) ||
+    /^0+$/.test(traceId) ||
+    /^0+$/.test(parentId)
+  if (invalid) {
+    return undefined
+  }
+
+  const state = headers.get('tracestate')?.trim()
+  const spanId = newSpanId()
+  return {
+    traceId,
+    parentId,
+    spanId,
+    flags: parseInt(flags, 16),
+    ...(state ? { state } : {}),
+    traceparent: `00-${traceId}-${spanId}-${flags}`,
+  }
+}
+
 /**
  * Handler function type for tool execution
  */
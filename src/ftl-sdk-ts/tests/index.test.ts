@@ -13,6 +13,7 @@ import {
   isAudioContent,
   isResourceContent,
   createTool,
+  getTraceContext,
 } from '../src/index'
 
 describe('ToolResponse convenience methods', () => {
@@ -357,3 +358,47 @@ describe('createTool helper', () => {
     expect((body.content[0] as TextContent).text).toContain('Tool execution failed')
   })
 })
+
+describe('getTraceContext', () => {
+  test('reads a valid traceparent and tracestate', () => {
+    const headers = new Headers({
+      traceparent: '00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01',
+      tracestate: 'vendor=value',
+    })
+
+    const trace = getTraceContext(headers)
+    expect(trace).toEqual({
+      traceId: '4bf92f3577b34da6a3ce929d0e0e4736',
+      parentId: '00f067aa0ba902b7',
+      spanId: expect.stringMatching(/^[0-9a-f]{16}$/),
+      flags: 1,
+      state: 'vendor=value',
+      traceparent: `00-4bf92f3577b34da6a3ce929d0e0e4736-${trace?.spanId}-01`,
+    })
+  })
+
+  test('names a new span of the tool call as the parent of downstream calls', () => {
+    const headers = new Headers({
+      traceparent: '01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra',
+    })
+
+    const trace = getTraceContext(headers)
+    expect(trace?.spanId).not.toBe('00f067aa0ba902b7')
+    expect(trace?.traceparent).toBe(`00-4bf92f3577b34da6a3ce929d0e0e4736-${trace?.spanId}-00`)
+    expect(getTraceContext(headers)?.spanId).not.toBe(trace?.spanId)
+  })
+
+  test('rejects missing or invalid traceparent headers', () => {
+    expect(getTraceContext(new Headers())).toBeUndefined()
+    expect(
+      getTraceContext(
+        new Headers({ traceparent: '00-00000000000000000000000000000000-00f067aa0ba902b7-01' }),
+      ),
+    ).toBeUndefined()
+    expect(
+      getTraceContext(
+        new Headers({ traceparent: 'ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01' }),
+      ),
+    ).toBeUndefined()
+  })
+})